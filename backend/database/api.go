@@ -60,4 +60,5 @@ type RenderUpdate struct {
 	Started  time.Time  `json:"started" bson:"started"`
 	Finished *time.Time `json:"finished, omitempty" bson:"finished, omitempty"`
 	Icon     string     `json:"icon" bson:"icon"`
+	Error    string     `json:"error,omitempty" bson:"error,omitempty"`
 }
@@ -9,4 +9,6 @@ type Render struct {
 	Started  time.Time   `json:"started" bson:"started"`
 	Finished *time.Time  `json:"finished, omitempty" bson:"finished, omitempty"`
 	Icon     string      `json:"icon" bson:"icon"`
+	// Why the render was dropped by the raytracer, empty unless it failed
+	Error    string      `json:"error,omitempty" bson:"error,omitempty"`
 }
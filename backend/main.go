@@ -87,6 +87,8 @@ func main() {
 	auth.HandleFunc("/project/{id}/data", authenticated.ProjectData).Methods("GET", "POST")
 	auth.HandleFunc("/project/{id}/render/{width}/{height}/{samples}", authenticated.Render).Methods("POST")
 	auth.HandleFunc("/project/{id}/render/{render}", authenticated.Render).Methods("DELETE")
+	auth.HandleFunc("/project/{id}/animation/{width}/{height}/{samples}", authenticated.Animation).Methods("POST")
+	auth.HandleFunc("/project/{id}/lightmap", authenticated.Lightmap).Methods("GET", "POST")
 
 	http.Handle("/", r)
 	fmt.Printf("Listening on port %d\n", port)
@@ -8,6 +8,7 @@ import (
 type ProjectLoader interface {
 	GetProject(w http.ResponseWriter, r *http.Request, path string) error
 	SaveProject(r io.Reader, path string) error
+	RemoveProject(path string) error
 	NewPath() string
 }
 
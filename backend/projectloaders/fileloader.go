@@ -47,6 +47,14 @@ func (f FileProjectLoader) GetProject(w http.ResponseWriter, r *http.Request, p
 	return nil
 }
 
+func (f FileProjectLoader) RemoveProject(p string) error {
+	err := os.Remove(f.projectPath(p))
+	if os.IsNotExist(err) {
+		return nil
+	}
+	return err
+}
+
 func (f FileProjectLoader) SaveProject(r io.Reader, p string) error {
 	p = f.projectPath(p)
 	file, err := os.Create(p)
@@ -0,0 +1,100 @@
+package authenticated
+
+import (
+	"context"
+	"fmt"
+	"io/ioutil"
+	"net/http"
+	"strconv"
+	"time"
+
+	"github.com/Texyfore/Archytex/backend/database"
+	"github.com/Texyfore/Archytex/backend/database/models"
+	"github.com/Texyfore/Archytex/backend/logging"
+	"github.com/gorilla/mux"
+	"github.com/streadway/amqp"
+	"go.mongodb.org/mongo-driver/bson/primitive"
+)
+
+// Reads an uploaded file of a multipart form
+func formFile(r *http.Request, name string) ([]byte, error) {
+	file, _, err := r.FormFile(name)
+	if err != nil {
+		return nil, err
+	}
+	defer file.Close()
+	return ioutil.ReadAll(file)
+}
+
+func Animation(w http.ResponseWriter, r *http.Request) {
+	session := models.UseSession(r.Context())
+	params := mux.Vars(r)
+	_projectId, ok := params["id"]
+	if !ok {
+		logging.Error(w, r, nil, "Project not specified", http.StatusBadRequest)
+		return
+	}
+	settings, ok := parseRenderSettings(w, r)
+	if !ok {
+		return
+	}
+	fps := 24.0
+	if _fps := r.URL.Query().Get("fps"); _fps != "" {
+		var err error
+		fps, err = strconv.ParseFloat(_fps, 64)
+		if err != nil || fps <= 0 {
+			logging.Error(w, r, err, "invalid fps field", http.StatusBadRequest)
+			return
+		}
+	}
+	projectId, err := primitive.ObjectIDFromHex(_projectId)
+	if err != nil {
+		logging.Error(w, r, err, "invalid project id", http.StatusBadRequest)
+		return
+	}
+	project, err := database.CurrentDatabase.GetProject(session.User.Id, projectId)
+	if err != nil || project == nil {
+		logging.Error(w, r, err, "could not find project", http.StatusNotFound)
+		return
+	}
+	scene, err := formFile(r, "scene")
+	if err != nil {
+		logging.Error(w, r, err, "invalid scene field", http.StatusBadRequest)
+		return
+	}
+	path, err := formFile(r, "path")
+	if err != nil {
+		logging.Error(w, r, err, "invalid path field", http.StatusBadRequest)
+		return
+	}
+	// The dispatcher adds a render for each frame, named after the animation
+	name := fmt.Sprintf("%s-%d", project.Title, len(project.Renders)+1)
+	task_id := primitive.NewObjectID().Hex()
+	ctx, cancel := context.WithTimeout(context.Background(), time.Second*5)
+	defer cancel()
+	err = storeRender(ctx, task_id, settings, scene)
+	if err != nil {
+		logging.Error(w, r, err, "couldn't create animation", http.StatusInternalServerError)
+		return
+	}
+	values := map[string]interface{}{
+		"path": path,
+		"fps":  fps,
+		"name": name,
+	}
+	for key, value := range values {
+		err = database.RedisClient.Set(ctx, fmt.Sprintf("archyrt:%s:%s", task_id, key), value, 0).Err()
+		if err != nil {
+			logging.Error(w, r, err, "couldn't create animation", http.StatusInternalServerError)
+			return
+		}
+	}
+	err = database.RabbitmqChannel.Publish("", "archyrt:animation", false, false, amqp.Publishing{
+		ContentType: "text/plain",
+		Body:        []byte(task_id + "#" + session.User.Id.(primitive.ObjectID).Hex() + "#" + projectId.Hex()),
+	})
+	if err != nil {
+		logging.Error(w, r, err, "couldn't create animation", http.StatusInternalServerError)
+		return
+	}
+}
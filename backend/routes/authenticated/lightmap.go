@@ -0,0 +1,120 @@
+package authenticated
+
+import (
+	"context"
+	"fmt"
+	"io/ioutil"
+	"net/http"
+	"strconv"
+	"time"
+
+	"github.com/Texyfore/Archytex/backend/database"
+	"github.com/Texyfore/Archytex/backend/logging"
+	"github.com/Texyfore/Archytex/backend/projectloaders"
+	"github.com/gorilla/mux"
+	"github.com/streadway/amqp"
+	"go.mongodb.org/mongo-driver/bson/primitive"
+)
+
+// Key of the marker the dispatcher leaves when the bake of a project fails
+func lightmapErrorKey(projectPath string) string {
+	return fmt.Sprintf("archyrt:lightmap:%s:error", projectPath)
+}
+
+func Lightmap(w http.ResponseWriter, r *http.Request) {
+	params := mux.Vars(r)
+	_projectId, ok := params["id"]
+	if !ok {
+		logging.Error(w, r, nil, "Project not specified", http.StatusBadRequest)
+		return
+	}
+	project, err := loadProject(r, _projectId)
+	if err == database.ErrProjectNotFound {
+		logging.Error(w, r, err, "Project not found", http.StatusNotFound)
+		return
+	}
+	if err != nil {
+		logging.Error(w, r, err, "Project Unavailable", http.StatusInternalServerError)
+		return
+	}
+	// Baked lightmaps are stored next to the project file
+	path := project.Path + ".almp"
+	if r.Method == "GET" {
+		failed, err := database.RedisClient.Exists(r.Context(), lightmapErrorKey(project.Path)).Result()
+		if err != nil {
+			logging.Error(w, r, err, "Lightmap Unavailable", http.StatusInternalServerError)
+			return
+		}
+		if failed > 0 {
+			logging.Error(w, r, nil, "couldn't bake lightmap", http.StatusInternalServerError)
+			return
+		}
+		w.Header().Set("Content-Type", "application/octet-stream")
+		err = projectloaders.CurrentProjectLoader.GetProject(w, r, path)
+		if err != nil {
+			logging.Error(w, r, err, "Lightmap Unavailable", http.StatusInternalServerError)
+			return
+		}
+	} else if r.Method == "POST" {
+		samples := 64
+		if _samples := r.URL.Query().Get("samples"); _samples != "" {
+			samples, err = strconv.Atoi(_samples)
+			if err != nil || samples <= 0 {
+				logging.Error(w, r, err, "invalid samples field", http.StatusBadRequest)
+				return
+			}
+		}
+		atmosphere := r.URL.Query().Get("atmosphere")
+		if atmosphere != "" {
+			_, err = validateAtmosphere(atmosphere)
+			if err != nil {
+				logging.Error(w, r, err, "invalid atmosphere field", http.StatusBadRequest)
+				return
+			}
+		}
+		bytes, err := ioutil.ReadAll(r.Body)
+		if err != nil {
+			logging.Error(w, r, err, "couldn't bake lightmap", http.StatusBadGateway)
+			return
+		}
+		// The lightmap of the earlier scene is removed, so the editor can wait for the new one
+		err = projectloaders.CurrentProjectLoader.RemoveProject(path)
+		if err != nil {
+			logging.Error(w, r, err, "couldn't bake lightmap", http.StatusInternalServerError)
+			return
+		}
+		task_id := primitive.NewObjectID().Hex()
+		ctx, cancel := context.WithTimeout(context.Background(), time.Second*5)
+		defer cancel()
+		err = database.RedisClient.Del(ctx, lightmapErrorKey(project.Path)).Err()
+		if err != nil {
+			logging.Error(w, r, err, "couldn't bake lightmap", http.StatusInternalServerError)
+			return
+		}
+		err = database.RedisClient.Set(ctx, fmt.Sprintf("archyrt:%s:samples", task_id), samples, 0).Err()
+		if err != nil {
+			logging.Error(w, r, err, "couldn't bake lightmap", http.StatusInternalServerError)
+			return
+		}
+		if atmosphere != "" {
+			err = database.RedisClient.Set(ctx, fmt.Sprintf("archyrt:%s:atmosphere", task_id), atmosphere, 0).Err()
+			if err != nil {
+				logging.Error(w, r, err, "couldn't bake lightmap", http.StatusInternalServerError)
+				return
+			}
+		}
+		err = database.RedisClient.Set(ctx, fmt.Sprintf("archyrt:%s:scene", task_id), bytes, 0).Err()
+		if err != nil {
+			logging.Error(w, r, err, "couldn't bake lightmap", http.StatusInternalServerError)
+			return
+		}
+		err = database.RabbitmqChannel.Publish("", "archyrt:bake", false, false, amqp.Publishing{
+			ContentType: "text/plain",
+			Body:        []byte(task_id + "#" + project.Path),
+		})
+		if err != nil {
+			logging.Error(w, r, err, "couldn't bake lightmap", http.StatusInternalServerError)
+			return
+		}
+	}
+}
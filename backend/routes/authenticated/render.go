@@ -55,26 +55,8 @@ func Render(w http.ResponseWriter, r *http.Request) {
 			logging.Error(w, r, nil, "Project not specified", http.StatusBadRequest)
 			return
 		}
-		_width, _ := params["width"]
-		width, err := strconv.Atoi(_width)
-		if err != nil {
-			logging.Error(w, r, err, "invalid width field", http.StatusBadRequest)
-			return
-		}
-		_height, _ := params["height"]
-		height, err := strconv.Atoi(_height)
-		if err != nil {
-			logging.Error(w, r, err, "invalid height field", http.StatusBadRequest)
-			return
-		}
-		_samples, _ := params["samples"]
-		samples, err := strconv.Atoi(_samples)
-		if err != nil {
-			logging.Error(w, r, err, "invalid samples field", http.StatusBadRequest)
-			return
-		}
-		if width%4 != 0 || height%4 != 0 {
-			logging.Error(w, r, err, "Width and Height have to be divisible by 4", http.StatusBadRequest)
+		settings, ok := parseRenderSettings(w, r)
+		if !ok {
 			return
 		}
 		projectId, err := primitive.ObjectIDFromHex(_projectId)
@@ -101,33 +83,144 @@ func Render(w http.ResponseWriter, r *http.Request) {
 			logging.Error(w, r, err, "couldn't create render", http.StatusBadGateway)
 			return
 		}
-		err = database.RedisClient.Set(ctx, fmt.Sprintf("archyrt:%s:width", task_id), width, 0).Err()
+		err = storeRender(ctx, task_id, settings, bytes)
 		if err != nil {
 			logging.Error(w, r, err, "couldn't create render", http.StatusInternalServerError)
 			return
 		}
-		err = database.RedisClient.Set(ctx, fmt.Sprintf("archyrt:%s:height", task_id), height, 0).Err()
+		err = database.RabbitmqChannel.Publish("", "archyrt:dispatch", false, false, amqp.Publishing{
+			ContentType: "text/plain",
+			Body:        []byte(task_id + "#" + session.User.Id.(primitive.ObjectID).Hex() + "#" + projectId.Hex()),
+		})
 		if err != nil {
 			logging.Error(w, r, err, "couldn't create render", http.StatusInternalServerError)
 			return
 		}
-		err = database.RedisClient.Set(ctx, fmt.Sprintf("archyrt:%s:samples", task_id), samples, 0).Err()
+	}
+}
+
+// Settings of a render given in the path and the query of its request
+type renderSettings struct {
+	width       int
+	height      int
+	samples     int
+	integrator  string
+	clay        bool
+	postprocess string
+	display     string
+	atmosphere  string
+}
+
+// Reads the settings of a render from a request, responding with an error if they are invalid
+func parseRenderSettings(w http.ResponseWriter, r *http.Request) (*renderSettings, bool) {
+	params := mux.Vars(r)
+	_width, _ := params["width"]
+	width, err := strconv.Atoi(_width)
+	if err != nil {
+		logging.Error(w, r, err, "invalid width field", http.StatusBadRequest)
+		return nil, false
+	}
+	_height, _ := params["height"]
+	height, err := strconv.Atoi(_height)
+	if err != nil {
+		logging.Error(w, r, err, "invalid height field", http.StatusBadRequest)
+		return nil, false
+	}
+	_samples, _ := params["samples"]
+	samples, err := strconv.Atoi(_samples)
+	if err != nil {
+		logging.Error(w, r, err, "invalid samples field", http.StatusBadRequest)
+		return nil, false
+	}
+	if width%4 != 0 || height%4 != 0 {
+		logging.Error(w, r, err, "Width and Height have to be divisible by 4", http.StatusBadRequest)
+		return nil, false
+	}
+	integrator := r.URL.Query().Get("integrator")
+	if integrator == "" {
+		integrator = "path"
+	}
+	// The workers publish the names of the integrators they support
+	supported, err := database.RedisClient.SIsMember(r.Context(), "archyrt:integrators", integrator).Result()
+	if err != nil {
+		logging.Error(w, r, err, "couldn't check integrator", http.StatusInternalServerError)
+		return nil, false
+	}
+	if !supported {
+		logging.Error(w, r, nil, "invalid integrator field", http.StatusBadRequest)
+		return nil, false
+	}
+	clay := false
+	if _clay := r.URL.Query().Get("clay"); _clay != "" {
+		clay, err = strconv.ParseBool(_clay)
+		if err != nil {
+			logging.Error(w, r, err, "invalid clay field", http.StatusBadRequest)
+			return nil, false
+		}
+	}
+	postprocess := r.URL.Query().Get("postprocess")
+	if postprocess != "" {
+		err = validatePostprocess(postprocess)
 		if err != nil {
-			logging.Error(w, r, err, "couldn't create render", http.StatusInternalServerError)
-			return
+			logging.Error(w, r, err, "invalid postprocess field", http.StatusBadRequest)
+			return nil, false
 		}
-		err = database.RedisClient.Set(ctx, fmt.Sprintf("archyrt:%s:scene", task_id), bytes, 0).Err()
+	}
+	display := r.URL.Query().Get("display")
+	if display == "" {
+		display = "srgb"
+	}
+	if display != "srgb" && display != "display_p3" && display != "rec2020" {
+		logging.Error(w, r, nil, "invalid display field", http.StatusBadRequest)
+		return nil, false
+	}
+	atmosphere := r.URL.Query().Get("atmosphere")
+	if atmosphere != "" {
+		settings, err := validateAtmosphere(atmosphere)
 		if err != nil {
-			logging.Error(w, r, err, "couldn't create render", http.StatusInternalServerError)
-			return
+			logging.Error(w, r, err, "invalid atmosphere field", http.StatusBadRequest)
+			return nil, false
 		}
-		err = database.RabbitmqChannel.Publish("", "archyrt:dispatch", false, false, amqp.Publishing{
-			ContentType: "text/plain",
-			Body:        []byte(task_id + "#" + session.User.Id.(primitive.ObjectID).Hex() + "#" + projectId.Hex()),
-		})
+		// The bidirectional path tracer renders neither media nor the sun
+		if integrator == "bdpt" && (settings["fog"] != nil || settings["volumes"] != nil || settings["sun"] != nil) {
+			logging.Error(w, r, nil, "the bdpt integrator doesn't render fog or sunlight", http.StatusBadRequest)
+			return nil, false
+		}
+	}
+	return &renderSettings{
+		width:       width,
+		height:      height,
+		samples:     samples,
+		integrator:  integrator,
+		clay:        clay,
+		postprocess: postprocess,
+		display:     display,
+		atmosphere:  atmosphere,
+	}, true
+}
+
+// Puts the settings and the scene of a render on Redis for the raytracer
+func storeRender(ctx context.Context, task_id string, settings *renderSettings, scene []byte) error {
+	values := map[string]interface{}{
+		"width":      settings.width,
+		"height":     settings.height,
+		"samples":    settings.samples,
+		"integrator": settings.integrator,
+		"clay":       settings.clay,
+		"display":    settings.display,
+		"scene":      scene,
+	}
+	if settings.postprocess != "" {
+		values["postprocess"] = settings.postprocess
+	}
+	if settings.atmosphere != "" {
+		values["atmosphere"] = settings.atmosphere
+	}
+	for key, value := range values {
+		err := database.RedisClient.Set(ctx, fmt.Sprintf("archyrt:%s:%s", task_id, key), value, 0).Err()
 		if err != nil {
-			logging.Error(w, r, err, "couldn't create render", http.StatusInternalServerError)
-			return
+			return err
 		}
 	}
+	return nil
 }
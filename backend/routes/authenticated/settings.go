@@ -0,0 +1,169 @@
+package authenticated
+
+import (
+	"encoding/json"
+	"errors"
+	"fmt"
+	"strconv"
+	"strings"
+)
+
+// Kinds of values the raytracer accepts for a setting
+type settingKind int
+
+const (
+	numberSetting settingKind = iota
+	// Non-negative whole number
+	countSetting
+	// List of three numbers
+	vectorSetting
+)
+
+// Settings of each effect of the post-processing chain
+var effectSettings = map[string]map[string]settingKind{
+	"bloom":                {"threshold": numberSetting, "intensity": numberSetting, "radius": numberSetting},
+	"glare":                {"threshold": numberSetting, "intensity": numberSetting, "length": numberSetting, "streaks": countSetting, "angle": numberSetting},
+	"vignette":             {"strength": numberSetting, "falloff": numberSetting},
+	"lens_distortion":      {"amount": numberSetting},
+	"chromatic_aberration": {"strength": numberSetting},
+	"contrast":             {"amount": numberSetting},
+	"saturation":           {"amount": numberSetting},
+}
+
+// Settings of the media of the atmosphere
+var mediumSettings = map[string]settingKind{"density": numberSetting, "albedo": vectorSetting, "anisotropy": numberSetting}
+
+// Settings of the sun of the atmosphere
+var sunSettings = map[string]settingKind{"direction": vectorSetting, "color": vectorSetting}
+
+// Parses JSON keeping numbers as they are written, so counts can be told apart from fractions
+func decodeSettings(data string, v interface{}) error {
+	decoder := json.NewDecoder(strings.NewReader(data))
+	decoder.UseNumber()
+	err := decoder.Decode(v)
+	if err != nil {
+		return err
+	}
+	if decoder.More() {
+		return errors.New("unexpected data after the settings")
+	}
+	return nil
+}
+
+// Checks that a value is of the kind of its setting
+func checkSetting(name string, value interface{}, kind settingKind) error {
+	if kind == vectorSetting {
+		components, ok := value.([]interface{})
+		if !ok || len(components) != 3 {
+			return fmt.Errorf("%s is not a list of three numbers", name)
+		}
+		for _, component := range components {
+			err := checkSetting(name, component, numberSetting)
+			if err != nil {
+				return err
+			}
+		}
+		return nil
+	}
+	number, ok := value.(json.Number)
+	if !ok {
+		return fmt.Errorf("%s is not a number", name)
+	}
+	if kind == countSetting {
+		_, err := strconv.ParseUint(number.String(), 10, 64)
+		if err != nil {
+			return fmt.Errorf("%s is not a whole number", name)
+		}
+		return nil
+	}
+	_, err := number.Float64()
+	if err != nil {
+		return fmt.Errorf("%s is not a number", name)
+	}
+	return nil
+}
+
+// Checks that an object only has known settings, each with a value of the right kind
+func checkSettings(object map[string]interface{}, settings map[string]settingKind) error {
+	for name, value := range object {
+		kind, ok := settings[name]
+		if !ok {
+			return fmt.Errorf("unknown setting %s", name)
+		}
+		err := checkSetting(name, value, kind)
+		if err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
+// Checks a post-processing chain the way the raytracer reads it, so renders don't fail after being queued
+func validatePostprocess(data string) error {
+	var effects []map[string]interface{}
+	err := decodeSettings(data, &effects)
+	if err != nil {
+		return err
+	}
+	if effects == nil {
+		return errors.New("effects are not a list")
+	}
+	for _, effect := range effects {
+		kind, ok := effect["type"].(string)
+		if !ok {
+			return errors.New("effect without a type")
+		}
+		settings, ok := effectSettings[kind]
+		if !ok {
+			return fmt.Errorf("unknown effect %s", kind)
+		}
+		delete(effect, "type")
+		err = checkSettings(effect, settings)
+		if err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
+// Checks fog and sunlight settings the way the raytracer reads them, returning them for further checks
+func validateAtmosphere(data string) (map[string]interface{}, error) {
+	var atmosphere map[string]interface{}
+	err := decodeSettings(data, &atmosphere)
+	if err != nil {
+		return nil, err
+	}
+	if atmosphere == nil {
+		return nil, errors.New("atmosphere is not an object")
+	}
+	for name, value := range atmosphere {
+		var settings map[string]settingKind
+		switch name {
+		case "fog_extent":
+			err = checkSetting(name, value, numberSetting)
+			if err != nil {
+				return nil, err
+			}
+			continue
+		case "fog", "volumes":
+			settings = mediumSettings
+		case "sun":
+			settings = sunSettings
+		default:
+			return nil, fmt.Errorf("unknown setting %s", name)
+		}
+		// Fog and sun are optional, the medium of fog volumes always exists
+		if value == nil && name != "volumes" {
+			continue
+		}
+		object, ok := value.(map[string]interface{})
+		if !ok {
+			return nil, fmt.Errorf("%s is not an object", name)
+		}
+		err = checkSettings(object, settings)
+		if err != nil {
+			return nil, err
+		}
+	}
+	return atmosphere, nil
+}
@@ -1,6 +1,7 @@
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc};
 
 use asset::{BoundingBox, GizmoID, PropID, TextureID};
+use cgmath::Vector2;
 use gpu::{BufferUsages, Gpu, Image, Sampler, Texture};
 
 use crate::{Resource, ResourceKind};
@@ -44,6 +45,11 @@ impl ResourceLoader {
                     let mesh = load_gizmo(&self.gpu, &resource.buf);
                     return Some(LoadedResource::Gizmo { id, mesh });
                 }
+                ResourceKind::Lightmap => {
+                    let (texture, texcoords) =
+                        load_lightmap(&self.gpu, &self.sampler, &resource.buf);
+                    return Some(LoadedResource::Lightmap { texture, texcoords });
+                }
             }
         }
 
@@ -65,6 +71,10 @@ pub enum LoadedResource {
         id: GizmoID,
         mesh: GizmoMesh,
     },
+    Lightmap {
+        texture: Texture,
+        texcoords: HashMap<[[i32; 3]; 4], [Vector2<f32>; 4]>,
+    },
 }
 
 fn load_texture(gpu: &Gpu, sampler: &Sampler, buf: &[u8]) -> Texture {
@@ -104,3 +114,21 @@ fn load_gizmo(gpu: &Gpu, buf: &[u8]) -> GizmoMesh {
         triangles: gpu.create_buffer(&gizmo.triangles, BufferUsages::INDEX),
     }
 }
+
+fn load_lightmap(
+    gpu: &Gpu,
+    sampler: &Sampler,
+    buf: &[u8],
+) -> (Texture, HashMap<[[i32; 3]; 4], [Vector2<f32>; 4]>) {
+    let lightmap = asset::Lightmap::decode(buf).unwrap();
+    let texture = gpu.create_texture(
+        sampler,
+        Image {
+            width: lightmap.width,
+            height: lightmap.height,
+            buf: &lightmap.rgbm8,
+        },
+    );
+
+    (texture, lightmap.texcoords())
+}
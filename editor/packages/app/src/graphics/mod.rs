@@ -4,9 +4,10 @@ mod renderer;
 
 pub mod structures;
 
-use std::{mem::size_of, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, mem::size_of, rc::Rc};
 
 use asset::TextureID;
+use cgmath::Vector2;
 use gpu::{BufferUsages, Gpu};
 use winit::window::Window;
 
@@ -33,16 +34,29 @@ pub fn init(window: &Window) -> (Renderer, Graphics, ResourceLoader) {
 
     (
         Renderer::new(gpu.clone(), surface),
-        Graphics { gpu: gpu.clone() },
+        Graphics {
+            gpu: gpu.clone(),
+            lightmap: RefCell::default(),
+        },
         ResourceLoader::new(gpu, sampler),
     )
 }
 
 pub struct Graphics {
     gpu: Rc<Gpu>,
+    lightmap: RefCell<HashMap<[[i32; 3]; 4], [Vector2<f32>; 4]>>,
 }
 
 impl Graphics {
+    pub fn set_lightmap(&self, texcoords: HashMap<[[i32; 3]; 4], [Vector2<f32>; 4]>) {
+        *self.lightmap.borrow_mut() = texcoords;
+    }
+
+    /// Lightmap coordinates of the corners of a face, if it was baked
+    pub fn lightmap_texcoords(&self, corners: [[i32; 3]; 4]) -> Option<[Vector2<f32>; 4]> {
+        self.lightmap.borrow().get(&corners).copied()
+    }
+
     pub fn create_line_mesh(&self, descriptor: LineMeshDescriptor) -> LineMesh {
         LineMesh {
             vertices: Rc::new(
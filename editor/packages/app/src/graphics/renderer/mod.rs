@@ -5,8 +5,8 @@ use std::rc::Rc;
 
 use asset::{GizmoID, PropID, TextureID};
 use gpu::{
-    Buffer, BufferUsages, DepthBuffer, Gpu, InstanceConfig, MsaaFramebuffer, Surface, Texture,
-    Uniform,
+    Buffer, BufferUsages, DepthBuffer, Gpu, Image, InstanceConfig, MsaaFramebuffer, Surface,
+    Texture, Uniform,
 };
 
 use self::{pipelines::Pipelines, resources::Resources};
@@ -25,6 +25,7 @@ pub struct Renderer {
     camera: Uniform<CameraMatrices>,
     grid: Uniform<[i32; 4]>,
     solid_tris: Buffer<[u16; 3]>,
+    lightmap: Texture,
 }
 
 impl Renderer {
@@ -36,6 +37,15 @@ impl Renderer {
         let camera = gpu.create_uniform(&CameraMatrices::default());
         let grid = gpu.create_uniform(&[100; 4]);
         let solid_tris = gpu.create_buffer(&gen_solid_tris(), BufferUsages::INDEX);
+        // bound until a lightmap is loaded, unlit faces don't sample it
+        let lightmap = gpu.create_texture(
+            &gpu.create_sampler(),
+            Image {
+                width: 1,
+                height: 1,
+                buf: &[255; 4],
+            },
+        );
 
         Self {
             gpu,
@@ -47,6 +57,7 @@ impl Renderer {
             camera,
             grid,
             solid_tris,
+            lightmap,
         }
     }
 
@@ -62,6 +73,10 @@ impl Renderer {
         self.resources.add_gizmo(id, mesh);
     }
 
+    pub fn set_lightmap(&mut self, texture: Texture) {
+        self.lightmap = texture;
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         self.surface.configure(&self.gpu, width, height);
         self.depth_buffer = self.gpu.create_depth_buffer(width, height);
@@ -89,6 +104,7 @@ impl Renderer {
 
             pass.set_pipeline(&self.pipelines.solid);
             pass.set_uniform(2, &self.grid);
+            pass.set_texture(3, &self.lightmap);
             pass.set_triangles(&self.solid_tris);
 
             for (texture, geometry) in canvas.solids {
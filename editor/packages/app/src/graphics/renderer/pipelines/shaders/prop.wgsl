@@ -77,12 +77,19 @@ var t_diffuse: texture_2d<f32>;
 [[group(2), binding(1)]]
 var s_diffuse: sampler;
 
+// Must match ALPHA_CUTOFF in the raytracer
+let ALPHA_CUTOFF: f32 = 0.5;
+
 [[stage(fragment)]]
 fn fragment(vertex: Vertex) -> Fragment {
     var color = textureSample(t_diffuse, s_diffuse, vertex.texcoord);
     var color_rgb = color.rgb;
     var color_a = color.a;
 
+    if (color_a < ALPHA_CUTOFF) {
+        discard;
+    }
+
     var light_dir = normalize(vertex.camera_position - vertex.world_position);
     var diffuse = (max(dot(light_dir, vertex.normal), 0.0) + 0.8) * 0.4;
     color_rgb = color_rgb * diffuse;
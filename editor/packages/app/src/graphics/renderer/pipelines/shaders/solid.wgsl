@@ -10,6 +10,9 @@ struct Attribs {
 
     [[location(3)]]
     tint: vec4<f32>;
+
+    [[location(4)]]
+    lightmap: vec2<f32>;
 };
 
 struct Vertex {
@@ -33,6 +36,9 @@ struct Vertex {
 
     [[location(5)]]
     grid_len: i32;
+
+    [[location(6)]]
+    lightmap: vec2<f32>;
 };
 
 struct Fragment {
@@ -71,6 +77,7 @@ fn vertex(attribs: Attribs) -> Vertex {
     vertex.camera_position = (camera.view_to_world * vec4<f32>(0.0, 0.0, 0.0, 1.0)).xyz;
     vertex.world_position = attribs.position;
     vertex.grid_len = grid.len;
+    vertex.lightmap = attribs.lightmap;
 
     return vertex;
 }
@@ -110,6 +117,12 @@ var t_diffuse: texture_2d<f32>;
 [[group(1), binding(1)]]
 var s_diffuse: sampler;
 
+[[group(3), binding(0)]]
+var t_lightmap: texture_2d<f32>;
+
+[[group(3), binding(1)]]
+var s_lightmap: sampler;
+
 [[stage(fragment)]]
 fn fragment(vertex: Vertex) -> Fragment {
     var color = textureSample(t_diffuse, s_diffuse, vertex.texcoord);
@@ -126,9 +139,18 @@ fn fragment(vertex: Vertex) -> Fragment {
 
     color_rgb = color_rgb + vec3<f32>(g * 0.5);
 
-    var light_dir = normalize(vertex.camera_position - vertex.world_position);
-    var diffuse = (max(dot(light_dir, vertex.normal), 0.0) + 0.8) * 0.4;
-    color_rgb = color_rgb * diffuse;
+    var light = textureSample(t_lightmap, s_lightmap, vertex.lightmap);
+    if (vertex.lightmap.x >= 0.0) {
+        // rgbm encoded, LIGHTMAP_RANGE and TONEMAP_EXPOSURE are declared by the pipeline
+        color_rgb = color_rgb * light.rgb * light.a * LIGHTMAP_RANGE;
+        // Exposed and tonemapped like the raytracer's output transform, which scales renders up before the Reinhard curve
+        color_rgb = color_rgb * TONEMAP_EXPOSURE;
+        color_rgb = color_rgb / (color_rgb + vec3<f32>(1.0));
+    } else {
+        var light_dir = normalize(vertex.camera_position - vertex.world_position);
+        var diffuse = (max(dot(light_dir, vertex.normal), 0.0) + 0.8) * 0.4;
+        color_rgb = color_rgb * diffuse;
+    }
 
     var dist = distance(vertex.world_position, vertex.camera_position);
     var mixval = pow(clamp(dist / 200.0, 0.0, 1.0), 4.0);
@@ -1,5 +1,6 @@
 use std::mem::size_of;
 
+use asset::{LIGHTMAP_RANGE, TONEMAP_EXPOSURE};
 use gpu::{
     vertex_attr_array, Gpu, Pipeline, PipelineConfig, PipelineInput, PipelineTopology, Surface,
     VertexBufferLayout, VertexStepMode,
@@ -8,14 +9,22 @@ use gpu::{
 use crate::graphics::structures::SolidVertex;
 
 pub fn pipeline(gpu: &Gpu, surface: &Surface) -> Pipeline {
+    // Baked lighting is decoded and tonemapped with the constants of the raytracer
+    let shader_source = format!(
+        "let LIGHTMAP_RANGE: f32 = {:?};\nlet TONEMAP_EXPOSURE: f32 = {:?};\n{}",
+        LIGHTMAP_RANGE,
+        TONEMAP_EXPOSURE,
+        include_str!("shaders/solid.wgsl")
+    );
     gpu.create_pipeline(
         surface,
         &PipelineConfig {
-            shader_source: include_str!("shaders/solid.wgsl"),
+            shader_source: &shader_source,
             inputs: &[
                 PipelineInput::Uniform, // Camera
                 PipelineInput::Texture, // Texture
                 PipelineInput::Uniform, // Grid
+                PipelineInput::Texture, // Lightmap
             ],
             vertex_buffers: &[VertexBufferLayout {
                 array_stride: size_of::<SolidVertex>() as u64,
@@ -25,6 +34,7 @@ pub fn pipeline(gpu: &Gpu, surface: &Surface) -> Pipeline {
                     1 => Float32x3, // Normal
                     2 => Float32x2, // Texcoord
                     3 => Float32x4, // Tint
+                    4 => Float32x2, // Lightmap
                 ],
             }],
             topology: PipelineTopology::Triangles,
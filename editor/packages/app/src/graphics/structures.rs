@@ -22,6 +22,8 @@ pub struct SolidVertex {
     pub normal: Vector3<f32>,
     pub texcoord: Vector2<f32>,
     pub tint: [f32; 4],
+    /// Negative for faces without baked lighting
+    pub lightmap: Vector2<f32>,
 }
 
 #[repr(C)]
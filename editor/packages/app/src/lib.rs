@@ -23,6 +23,7 @@ use winit::{
 use self::graphics::Canvas;
 
 pub use ascn::Ascn;
+pub use asset::scene::{FaceKind, SolidKind, TextureTransform, TextureWrap};
 
 pub fn run(init: Init) {
     let window = init.winit.window;
@@ -122,12 +123,62 @@ pub fn run(init: Init) {
                                 ascn.scene(),
                             );
                         }
+                        FromHost::AddKeyframe { time, fov } => {
+                            logic.add_keyframe(time, fov);
+                        }
+                        FromHost::ClearKeyframes => {
+                            logic.clear_keyframes();
+                        }
+                        FromHost::SaveCameraPath(id) => {
+                            logic.save_camera_path(
+                                logic::Context {
+                                    host: host.as_ref(),
+                                    graphics: &graphics,
+                                    prop_infos: &prop_info,
+                                    delta,
+                                },
+                                id,
+                            );
+                        }
                         FromHost::Texture(id) => {
                             logic.set_texture(TextureID(id));
                         }
                         FromHost::Prop(id) => {
                             logic.set_prop(PropID(id));
                         }
+                        FromHost::FaceKind(kind) => {
+                            logic.set_face_kind(
+                                logic::Context {
+                                    host: host.as_ref(),
+                                    graphics: &graphics,
+                                    prop_infos: &prop_info,
+                                    delta,
+                                },
+                                kind,
+                            );
+                        }
+                        FromHost::SolidKind(kind) => {
+                            logic.set_solid_kind(
+                                logic::Context {
+                                    host: host.as_ref(),
+                                    graphics: &graphics,
+                                    prop_infos: &prop_info,
+                                    delta,
+                                },
+                                kind,
+                            );
+                        }
+                        FromHost::TextureTransform(transform) => {
+                            logic.set_texture_transform(
+                                logic::Context {
+                                    host: host.as_ref(),
+                                    graphics: &graphics,
+                                    prop_infos: &prop_info,
+                                    delta,
+                                },
+                                transform,
+                            );
+                        }
                         FromHost::Button(button) => match button {
                             button::PROP => {
                                 logic.set_editor_mode(
@@ -207,6 +258,16 @@ pub fn run(init: Init) {
                         LoadedResource::Gizmo { id, mesh } => {
                             renderer.add_gizmo(id, mesh);
                         }
+                        LoadedResource::Lightmap { texture, texcoords } => {
+                            renderer.set_lightmap(texture);
+                            graphics.set_lightmap(texcoords);
+                            logic.recalc_solids(logic::Context {
+                                host: host.as_ref(),
+                                graphics: &graphics,
+                                prop_infos: &prop_info,
+                                delta,
+                            });
+                        }
                     }
                 }
 
@@ -249,6 +310,7 @@ pub trait Host {
 
 pub enum ToHost {
     SceneSaved(i32, Vec<u8>),
+    CameraPathSaved(i32, Vec<u8>),
     Button(i32),
     PointerLocked(bool),
 }
@@ -257,8 +319,18 @@ pub enum FromHost {
     Resolution { width: u32, height: u32 },
     SaveScene(i32),
     LoadScene(Ascn),
+    /// Keyframe of the current view, `time` seconds into the camera path and with a vertical field of view in degrees
+    AddKeyframe { time: f32, fov: f32 },
+    ClearKeyframes,
+    SaveCameraPath(i32),
     Prop(u32),
     Texture(u32),
+    /// Kind given to the selected faces
+    FaceKind(FaceKind),
+    /// Kind given to the selected solids
+    SolidKind(SolidKind),
+    /// Texture placement given to the selected faces
+    TextureTransform(TextureTransform),
     Button(i32),
     Movement(f32, f32),
     LockPointer(bool),
@@ -276,6 +348,8 @@ pub enum ResourceKind {
     Texture,
     Prop,
     Gizmo,
+    /// Baked lighting of the loaded scene's solids
+    Lightmap,
 }
 
 macro_rules! resource {
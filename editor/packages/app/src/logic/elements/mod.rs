@@ -1,6 +1,9 @@
 mod raycast;
 
-use asset::{scene, GizmoID, PropID, TextureID};
+use asset::{
+    scene::{self, FaceKind, SolidKind, TextureTransform},
+    GizmoID, PropID, TextureID,
+};
 use cgmath::{vec2, vec3, ElementWise, InnerSpace, Matrix4, Quaternion, Transform, Vector3, Zero};
 use libsm64::{LevelTriangle, Point3, Surface, Terrain};
 
@@ -82,6 +85,18 @@ impl Solid {
         self.geometry.retexture(face, texture)
     }
 
+    pub fn set_face_kind(&mut self, face: usize, kind: FaceKind) -> FaceKind {
+        std::mem::replace(&mut self.geometry.faces[face].kind, kind)
+    }
+
+    pub fn set_face_transform(&mut self, face: usize, transform: TextureTransform) -> TextureTransform {
+        std::mem::replace(&mut self.geometry.faces[face].transform, transform)
+    }
+
+    pub fn set_kind(&mut self, kind: SolidKind) -> SolidKind {
+        std::mem::replace(&mut self.geometry.kind, kind)
+    }
+
     pub fn save(&self) -> scene::Solid {
         let points = self
             .geometry
@@ -102,13 +117,19 @@ impl Solid {
             .map(|face| scene::Face {
                 texture: face.texture,
                 indices: face.indices.map(|i| i as u32),
+                transform: face.transform,
+                kind: face.kind,
             })
             .collect::<Vec<_>>()
             .try_into()
             .ok()
             .unwrap();
 
-        scene::Solid { points, faces }
+        scene::Solid {
+            points,
+            faces,
+            kind: self.geometry.kind,
+        }
     }
 
     pub fn clone(&self, gfx: &Graphics) -> Self {
@@ -282,6 +303,8 @@ pub struct PointLocator {
 struct Face {
     texture: TextureID,
     indices: [usize; 4],
+    transform: TextureTransform,
+    kind: FaceKind,
     selected: bool,
 }
 
@@ -290,6 +313,8 @@ impl From<(TextureID, [usize; 4])> for Face {
         Self {
             texture: tuple.0,
             indices: tuple.1,
+            transform: TextureTransform::default(),
+            kind: FaceKind::default(),
             selected: false,
         }
     }
@@ -305,6 +330,7 @@ pub struct FaceLocator {
 struct SolidGeometry {
     points: [Point; 8],
     faces: [Face; 6],
+    kind: SolidKind,
 }
 
 impl SolidGeometry {
@@ -331,7 +357,11 @@ impl SolidGeometry {
         ]
         .map(|indices| (TextureID(0), indices).into());
 
-        Self { points, faces }
+        Self {
+            points,
+            faces,
+            kind: SolidKind::default(),
+        }
     }
 
     pub fn load(solid: &scene::Solid) -> Self {
@@ -353,6 +383,8 @@ impl SolidGeometry {
             .map(|face| Face {
                 texture: face.texture,
                 indices: face.indices.map(|i| i as usize),
+                transform: face.transform,
+                kind: face.kind,
                 selected: false,
             })
             .collect::<Vec<_>>()
@@ -360,7 +392,11 @@ impl SolidGeometry {
             .ok()
             .unwrap();
 
-        Self { points, faces }
+        Self {
+            points,
+            faces,
+            kind: solid.kind,
+        }
     }
 
     fn displace(&mut self, selected: bool, delta: Vector3<i32>, mask: ElementKind) -> bool {
@@ -474,7 +510,12 @@ impl SolidGraphics {
                 edge0.cross(edge1).normalize()
             };
 
-            for index in face.indices {
+            let corners = face
+                .indices
+                .map(|index| geometry.points[index].position.into());
+            let lightmap = graphics.lightmap_texcoords(corners);
+
+            for (corner, index) in face.indices.into_iter().enumerate() {
                 let position = geometry.points[index].meters();
                 let texcoord = if normal.x.abs() > normal.y.abs() {
                     if normal.x.abs() > normal.z.abs() {
@@ -487,6 +528,7 @@ impl SolidGraphics {
                 } else {
                     vec2(position.x, position.y)
                 };
+                let texcoord = face.transform.apply(texcoord);
 
                 vertices.push(SolidVertex {
                     position,
@@ -494,9 +536,12 @@ impl SolidGraphics {
                     texcoord,
                     tint: if selected || face.selected {
                         color!("39a0ed80")
+                    } else if geometry.kind == SolidKind::Fog {
+                        color!("ffffff60")
                     } else {
                         [0.0; 4]
                     },
+                    lightmap: lightmap.map_or(vec2(-1.0, -1.0), |texcoords| texcoords[corner]),
                 })
             }
         }
@@ -6,7 +6,10 @@ mod elements;
 mod input;
 mod scene;
 
-use asset::{GizmoID, PropID, TextureID};
+use asset::{
+    scene::{CameraKeyframe, CameraPath, FaceKind, SolidKind, TextureTransform},
+    GizmoID, PropID, TextureID,
+};
 use cgmath::{vec2, vec3, Matrix4, Zero};
 use winit::event::{ElementState, MouseButton, VirtualKeyCode};
 
@@ -16,7 +19,13 @@ use crate::{
     Host, ToHost,
 };
 
-use self::{archy64::Archy64, camera::Camera, editor::Editor, input::Input, scene::Scene};
+use self::{
+    archy64::Archy64,
+    camera::Camera,
+    editor::Editor,
+    input::Input,
+    scene::{Action, Scene},
+};
 
 pub use elements::ElementKind;
 
@@ -27,6 +36,7 @@ pub struct Logic {
     editor: Editor,
     dummy: GizmoInstances,
     archy64: Archy64,
+    camera_path: CameraPath,
 }
 
 impl Logic {
@@ -63,6 +73,7 @@ impl Logic {
             editor,
             dummy,
             archy64: Archy64::default(),
+            camera_path: CameraPath::default(),
         }
     }
 
@@ -133,6 +144,36 @@ impl Logic {
         );
     }
 
+    /// Adds the current view to the camera path `time` seconds from its start, replacing the keyframe at that time
+    pub fn add_keyframe(&mut self, time: f32, fov: f32) {
+        let camera = self.camera.save();
+        let keyframes = &mut self.camera_path.keyframes;
+        keyframes.retain(|keyframe| keyframe.time != time);
+        keyframes.push(CameraKeyframe {
+            time,
+            position: camera.position,
+            rotation: camera.rotation,
+            fov,
+        });
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    pub fn clear_keyframes(&mut self) {
+        self.camera_path.keyframes.clear();
+    }
+
+    pub fn save_camera_path(&self, ctx: Context, id: i32) {
+        let buf = self.camera_path.encode().unwrap();
+        ctx.host.callback(ToHost::CameraPathSaved(id, buf));
+    }
+
+    /// Updates the solids for a newly loaded lightmap
+    pub fn recalc_solids(&mut self, ctx: Context) {
+        self.scene.recalc_solids(scene::Context {
+            graphics: ctx.graphics,
+        });
+    }
+
     pub fn set_texture(&mut self, texture: TextureID) {
         self.editor.set_texture(texture);
     }
@@ -141,6 +182,36 @@ impl Logic {
         self.editor.set_prop(prop);
     }
 
+    /// Turns the selected faces into portals or back into surfaces
+    pub fn set_face_kind(&mut self, ctx: Context, kind: FaceKind) {
+        self.scene.act(
+            scene::Context {
+                graphics: ctx.graphics,
+            },
+            Action::AssignFaceKind(kind),
+        );
+    }
+
+    /// Fills the selected solids with fog, or turns them back into solids
+    pub fn set_solid_kind(&mut self, ctx: Context, kind: SolidKind) {
+        self.scene.act(
+            scene::Context {
+                graphics: ctx.graphics,
+            },
+            Action::AssignSolidKind(kind),
+        );
+    }
+
+    /// Places the textures of the selected faces
+    pub fn set_texture_transform(&mut self, ctx: Context, transform: TextureTransform) {
+        self.scene.act(
+            scene::Context {
+                graphics: ctx.graphics,
+            },
+            Action::AssignTextureTransform(transform),
+        );
+    }
+
     pub fn set_editor_mode(&mut self, ctx: Context, mode: ElementKind) {
         self.editor.set_mode(
             editor::Context {
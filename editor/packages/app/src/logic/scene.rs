@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 
-use asset::{scene, TextureID};
+use asset::{
+    scene::{self, FaceKind, SolidKind, TextureTransform},
+    TextureID,
+};
 use cgmath::{Quaternion, Rotation, Vector2, Vector3, Zero};
 use libsm64::LevelTriangle;
 
@@ -299,6 +302,12 @@ impl Scene {
         }
     }
 
+    pub fn recalc_solids(&mut self, ctx: Context) {
+        for solid in self.solids.values_mut() {
+            solid.recalc(ctx.graphics);
+        }
+    }
+
     pub fn save(&self) -> scene::World {
         let solids = self.solids.values().map(|solid| solid.save()).collect();
         let props = self.props.values().map(|prop| prop.save()).collect();
@@ -655,6 +664,101 @@ impl Scene {
                 (!changes.is_empty()).then(|| Action::AssignTextures(changes))
             }
 
+            Action::AssignFaceKind(kind) => {
+                let mut changes = Vec::new();
+                for (sid, solid) in &mut self.solids {
+                    for fid in 0..6 {
+                        if solid.face_selected(fid) {
+                            let old = solid.set_face_kind(fid, kind);
+                            if old != kind {
+                                changes.push((
+                                    FaceLocator {
+                                        solid: *sid,
+                                        face: fid,
+                                    },
+                                    old,
+                                ))
+                            }
+                        }
+                    }
+                }
+                (!changes.is_empty()).then(|| Action::AssignFaceKinds(changes))
+            }
+
+            Action::AssignFaceKinds(kinds) => {
+                let mut changes = Vec::new();
+                for (locator, kind) in kinds {
+                    let solid = self.solids.get_mut(&locator.solid).unwrap();
+                    let old = solid.set_face_kind(locator.face, kind);
+                    if old != kind {
+                        changes.push((locator, old));
+                    }
+                }
+                (!changes.is_empty()).then(|| Action::AssignFaceKinds(changes))
+            }
+
+            Action::AssignTextureTransform(transform) => {
+                let mut changes = Vec::new();
+                for (sid, solid) in &mut self.solids {
+                    for fid in 0..6 {
+                        if solid.face_selected(fid) {
+                            let old = solid.set_face_transform(fid, transform);
+                            if old != transform {
+                                solid.recalc(ctx.graphics);
+                                changes.push((
+                                    FaceLocator {
+                                        solid: *sid,
+                                        face: fid,
+                                    },
+                                    old,
+                                ))
+                            }
+                        }
+                    }
+                }
+                (!changes.is_empty()).then(|| Action::AssignTextureTransforms(changes))
+            }
+
+            Action::AssignTextureTransforms(transforms) => {
+                let mut changes = Vec::new();
+                for (locator, transform) in transforms {
+                    let solid = self.solids.get_mut(&locator.solid).unwrap();
+                    let old = solid.set_face_transform(locator.face, transform);
+                    if old != transform {
+                        solid.recalc(ctx.graphics);
+                        changes.push((locator, old));
+                    }
+                }
+                (!changes.is_empty()).then(|| Action::AssignTextureTransforms(changes))
+            }
+
+            Action::AssignSolidKind(kind) => {
+                let mut changes = Vec::new();
+                for (sid, solid) in &mut self.solids {
+                    if solid.selected() {
+                        let old = solid.set_kind(kind);
+                        if old != kind {
+                            solid.recalc(ctx.graphics);
+                            changes.push((*sid, old));
+                        }
+                    }
+                }
+                (!changes.is_empty()).then(|| Action::AssignSolidKinds(changes))
+            }
+
+            Action::AssignSolidKinds(kinds) => {
+                let mut changes = Vec::new();
+                for (sid, kind) in kinds {
+                    let solid = self.solids.get_mut(&sid).unwrap();
+                    let old = solid.set_kind(kind);
+                    if old != kind {
+                        solid.recalc(ctx.graphics);
+                        changes.push((sid, old));
+                    }
+                }
+                (!changes.is_empty()).then(|| Action::AssignSolidKinds(changes))
+            }
+
             Action::DeleteSolids => {
                 let ids = self
                     .solids
@@ -809,6 +913,12 @@ pub enum Action {
     SetPropRotations(Vec<(usize, Quaternion<f32>)>),
     AssignTexture(TextureID),
     AssignTextures(Vec<(FaceLocator, TextureID)>),
+    AssignFaceKind(FaceKind),
+    AssignFaceKinds(Vec<(FaceLocator, FaceKind)>),
+    AssignTextureTransform(TextureTransform),
+    AssignTextureTransforms(Vec<(FaceLocator, TextureTransform)>),
+    AssignSolidKind(SolidKind),
+    AssignSolidKinds(Vec<(usize, SolidKind)>),
 
     DeleteSolids,
     DeleteProps,
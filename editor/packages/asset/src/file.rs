@@ -14,4 +14,5 @@ macro_rules! encdec {
 
 encdec!(crate::Prop);
 encdec!(crate::Gizmo);
-encdec!(crate::scene::Scene);
+encdec!(crate::Lightmap);
+encdec!(crate::scene::CameraPath);
@@ -1,10 +1,12 @@
 mod file;
 mod gizmo;
+mod lightmap;
 mod prop;
 mod texture;
 
 pub mod scene;
 
 pub use gizmo::*;
+pub use lightmap::*;
 pub use prop::*;
 pub use texture::*;
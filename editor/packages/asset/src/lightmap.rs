@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use cgmath::{vec2, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+/// Brightest light a lightmap texel can store
+pub const LIGHTMAP_RANGE: f32 = 8.0;
+/// Scale of the light of renders before tonemapping, shared by the raytracer and the editor's baked lighting
+pub const TONEMAP_EXPOSURE: f32 = 4.0;
+
+/// Baked lighting of the faces of a scene's solids, stored next to the scene
+#[derive(Serialize, Deserialize)]
+pub struct Lightmap {
+    pub width: u32,
+    pub height: u32,
+    /// Linear sRGB light of the texels, see [`encode_rgbm`]. Faces are shaded by multiplying their texture with it.
+    pub rgbm8: Vec<u8>,
+    pub faces: Vec<LightmapFace>,
+}
+
+/// Texels of a face in the atlas
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct LightmapFace {
+    /// Positions of the face's points in the order of its indices, identifying the face
+    pub corners: [Vector3<i32>; 4],
+    /// First texel of the face
+    pub offset: Vector2<u32>,
+    /// Texels along the first edge and the last edge of the face
+    pub size: Vector2<u32>,
+}
+
+impl LightmapFace {
+    /// Atlas coordinates of the corners, between 0 and 1
+    pub fn texcoords(&self, width: u32, height: u32) -> [Vector2<f32>; 4] {
+        let min = self.offset;
+        let max = self.offset + self.size;
+        [
+            vec2(min.x, min.y),
+            vec2(max.x, min.y),
+            vec2(max.x, max.y),
+            vec2(min.x, max.y),
+        ]
+        .map(|texel| vec2(texel.x as f32 / width as f32, texel.y as f32 / height as f32))
+    }
+}
+
+impl Lightmap {
+    /// Atlas coordinates of the corners of each face, by the positions of the corners
+    pub fn texcoords(&self) -> HashMap<[[i32; 3]; 4], [Vector2<f32>; 4]> {
+        self.faces
+            .iter()
+            .map(|face| {
+                (
+                    face.corners.map(Into::into),
+                    face.texcoords(self.width, self.height),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Encodes linear light as the sRGB encoded color divided by alpha and [`LIGHTMAP_RANGE`]
+pub fn encode_rgbm(color: [f32; 3]) -> [u8; 4] {
+    let max = color.iter().fold(0.0f32, |a, c| a.max(*c));
+    let alpha = (max / LIGHTMAP_RANGE).clamp(1.0 / 255.0, 1.0);
+    let alpha = (alpha * 255.0).ceil() / 255.0;
+    let [r, g, b] = color.map(|c| {
+        let c = (c / (alpha * LIGHTMAP_RANGE)).clamp(0.0, 1.0);
+        let c = if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (c * 255.0).round() as u8
+    });
+    [r, g, b, (alpha * 255.0).round() as u8]
+}
+
+/// Linear light of an encoded texel, see [`encode_rgbm`]
+pub fn decode_rgbm(texel: [u8; 4]) -> [f32; 3] {
+    let alpha = texel[3] as f32 / 255.0;
+    let [r, g, b, _] = texel.map(|c| {
+        let c = c as f32 / 255.0;
+        let c = if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        };
+        c * alpha * LIGHTMAP_RANGE
+    });
+    [r, g, b]
+}
@@ -1,4 +1,5 @@
-use cgmath::{Quaternion, Vector2, Vector3};
+use bincode::Options;
+use cgmath::{vec2, Quaternion, Vector2, Vector3};
 use serde::{Deserialize, Serialize};
 
 use crate::{PropID, TextureID};
@@ -21,16 +22,98 @@ pub struct Camera {
     pub rotation: Vector2<f32>,
 }
 
+/// Keyframed camera movement through a scene, stored next to it
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+/// Camera of a [`CameraPath`] at a point in time
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct CameraKeyframe {
+    /// Seconds since the start of the path
+    pub time: f32,
+    pub position: Vector3<f32>,
+    /// Pitch and yaw in degrees, like [`Camera::rotation`]
+    pub rotation: Vector2<f32>,
+    /// Vertical field of view in degrees, scene cameras see 80
+    pub fov: f32,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Solid {
     pub points: [Point; 8],
     pub faces: [Face; 6],
+    pub kind: SolidKind,
+}
+
+/// What a solid is rendered as
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SolidKind {
+    #[default]
+    Solid,
+    /// Not rendered, the region it encloses is filled with fog instead
+    Fog,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Face {
     pub texture: TextureID,
     pub indices: [u32; 4],
+    pub transform: TextureTransform,
+    pub kind: FaceKind,
+}
+
+/// What a face of a solid is rendered as
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FaceKind {
+    #[default]
+    Surface,
+    /// Not rendered, lets the light of the skybox in through an opening like a window
+    Portal,
+}
+
+/// Placement of a texture on a face, applied to the projected texture coordinates
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct TextureTransform {
+    /// World size of one texture repetition, in meters
+    pub scale: Vector2<f32>,
+    /// Counterclockwise rotation, in degrees
+    pub rotation: f32,
+    pub offset: Vector2<f32>,
+    pub wrap: TextureWrap,
+}
+
+/// How the texture continues past its edges
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TextureWrap {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        Self {
+            scale: vec2(1.0, 1.0),
+            rotation: 0.0,
+            offset: vec2(0.0, 0.0),
+            wrap: TextureWrap::default(),
+        }
+    }
+}
+
+impl TextureTransform {
+    pub fn apply(&self, texcoord: Vector2<f32>) -> Vector2<f32> {
+        let scaled = vec2(texcoord.x / self.scale.x, texcoord.y / self.scale.y);
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let rotated = vec2(
+            scaled.x * cos - scaled.y * sin,
+            scaled.x * sin + scaled.y * cos,
+        );
+        rotated + self.offset
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -44,3 +127,84 @@ pub struct Prop {
     pub position: Vector3<i32>,
     pub rotation: Quaternion<f32>,
 }
+
+impl Scene {
+    pub fn encode(&self) -> Option<Vec<u8>> {
+        bincode::serialize(&self).ok()
+    }
+
+    /// Decodes the scene, falling back to the format before faces had texture transforms
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .reject_trailing_bytes()
+            .deserialize::<Self>(buf)
+            .ok()
+            .or_else(|| {
+                bincode::deserialize::<legacy::Scene>(buf)
+                    .ok()
+                    .map(Into::into)
+            })
+    }
+}
+
+/// Format before faces had texture transforms
+mod legacy {
+    use serde::Deserialize;
+
+    use crate::TextureID;
+
+    use super::{Camera, FaceKind, Point, Prop, SolidKind, TextureTransform};
+
+    #[derive(Deserialize)]
+    pub struct Scene {
+        pub camera: Camera,
+        pub world: World,
+    }
+
+    #[derive(Deserialize)]
+    pub struct World {
+        pub solids: Vec<Solid>,
+        pub props: Vec<Prop>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Solid {
+        pub points: [Point; 8],
+        pub faces: [Face; 6],
+    }
+
+    #[derive(Deserialize)]
+    pub struct Face {
+        pub texture: TextureID,
+        pub indices: [u32; 4],
+    }
+
+    impl From<Scene> for super::Scene {
+        fn from(scene: Scene) -> Self {
+            let solids = scene
+                .world
+                .solids
+                .into_iter()
+                .map(|solid| super::Solid {
+                    points: solid.points,
+                    faces: solid.faces.map(|face| super::Face {
+                        texture: face.texture,
+                        indices: face.indices,
+                        transform: TextureTransform::default(),
+                        kind: FaceKind::default(),
+                    }),
+                    kind: SolidKind::default(),
+                })
+                .collect();
+
+            Self {
+                camera: scene.camera,
+                world: super::World {
+                    solids,
+                    props: scene.world.props,
+                },
+            }
+        }
+    }
+}
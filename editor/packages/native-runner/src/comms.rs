@@ -5,7 +5,9 @@ use std::{
     thread::{spawn, JoinHandle},
 };
 
-use app::{Ascn, FromHost};
+use app::{
+    Ascn, FaceKind, FromHost, Resource, ResourceKind, SolidKind, TextureTransform, TextureWrap,
+};
 
 pub struct AsyncStdin {
     thread: Option<JoinHandle<()>>,
@@ -43,6 +45,34 @@ impl AsyncStdin {
                             sender.send(FromHost::LoadScene(scene)).unwrap();
                             println!("[native-runner] loading `{}`", path);
                         }
+                        "keyframe" => {
+                            //keyframe <time> [fov]
+                            let time = tokens.next().unwrap().parse().unwrap();
+                            let fov = tokens.next().map_or(80.0, |fov| fov.parse().unwrap());
+                            sender.send(FromHost::AddKeyframe { time, fov }).unwrap();
+                            println!("[native-runner] added keyframe at {}s", time);
+                        }
+                        "clearpath" => {
+                            sender.send(FromHost::ClearKeyframes).unwrap();
+                            println!("[native-runner] cleared the camera path");
+                        }
+                        "savepath" => {
+                            sender.send(FromHost::SaveCameraPath(0)).unwrap();
+                            println!("[native-runner] requested camera path save");
+                        }
+                        "lightmap" => {
+                            let name = tokens.next().unwrap();
+                            let path = format!("{}.almp", name);
+                            let buf = fs::read(&path).unwrap();
+                            sender
+                                .send(FromHost::LoadResource(Resource {
+                                    id: 0,
+                                    buf,
+                                    kind: ResourceKind::Lightmap,
+                                }))
+                                .unwrap();
+                            println!("[native-runner] loading lightmap `{}`", path);
+                        }
                         "texture" => {
                             let texture = tokens.next().unwrap().parse().unwrap();
                             sender.send(FromHost::Texture(texture)).unwrap();
@@ -53,6 +83,42 @@ impl AsyncStdin {
                             sender.send(FromHost::Prop(prop)).unwrap();
                             println!("[native-runner] picked prop `{}`", prop);
                         }
+                        "portal" => {
+                            sender.send(FromHost::FaceKind(FaceKind::Portal)).unwrap();
+                            println!("[native-runner] made the selected faces portals");
+                        }
+                        "surface" => {
+                            sender.send(FromHost::FaceKind(FaceKind::Surface)).unwrap();
+                            println!("[native-runner] made the selected faces surfaces");
+                        }
+                        "fog" => {
+                            sender.send(FromHost::SolidKind(SolidKind::Fog)).unwrap();
+                            println!("[native-runner] filled the selected solids with fog");
+                        }
+                        "solid" => {
+                            sender.send(FromHost::SolidKind(SolidKind::Solid)).unwrap();
+                            println!("[native-runner] made the selected solids solid");
+                        }
+                        "transform" => {
+                            //transform <scale x> <scale y> <rotation> <offset x> <offset y> <repeat|clamp|mirror>
+                            let mut number = || -> f32 { tokens.next().unwrap().parse().unwrap() };
+                            let scale = [number(), number()];
+                            let rotation = number();
+                            let offset = [number(), number()];
+                            let wrap = match tokens.next() {
+                                Some("clamp") => TextureWrap::Clamp,
+                                Some("mirror") => TextureWrap::Mirror,
+                                _ => TextureWrap::Repeat,
+                            };
+                            let transform = TextureTransform {
+                                scale: scale.into(),
+                                rotation,
+                                offset: offset.into(),
+                                wrap,
+                            };
+                            sender.send(FromHost::TextureTransform(transform)).unwrap();
+                            println!("[native-runner] placed the textures of the selected faces");
+                        }
                         "button" => {
                             let button = tokens.next().unwrap().parse().unwrap();
                             sender.send(FromHost::Button(button)).unwrap();
@@ -41,6 +41,16 @@ impl Host for NativeHost {
                 fs::write(&fname, &scene).unwrap();
                 println!("[native-runner] saving scene `{}`", fname);
             }
+            ToHost::CameraPathSaved(_, path) => {
+                let stamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+
+                let fname = format!("{}.acam", stamp);
+                fs::write(&fname, &path).unwrap();
+                println!("[native-runner] saving camera path `{}`", fname);
+            }
             ToHost::Button(button) => {
                 println!("[native-runner] button feedback for {}", button);
             }
@@ -5,7 +5,10 @@ use std::sync::mpsc;
 use js_sys::{Function, Uint8Array};
 use wasm_bindgen::{prelude::*, JsCast};
 
-use app::{builtin_resources, Ascn, FromHost, Host, Init, Resource, ResourceKind, ToHost, Winit};
+use app::{
+    builtin_resources, Ascn, FaceKind, FromHost, Host, Init, Resource, ResourceKind, SolidKind,
+    TextureTransform, TextureWrap, ToHost, Winit,
+};
 use winit::{event_loop::EventLoop, platform::web::WindowBuilderExtWebSys, window::WindowBuilder};
 
 #[wasm_bindgen]
@@ -73,11 +76,71 @@ impl Sender {
         self.tx.send(FromHost::LoadScene(scene.inner)).unwrap();
     }
 
+    /// Adds the current view to the camera path, `time` seconds from its start
+    #[wasm_bindgen(js_name = "addKeyframe")]
+    pub fn add_keyframe(&self, time: f32, fov: f32) {
+        self.tx.send(FromHost::AddKeyframe { time, fov }).unwrap();
+    }
+
+    #[wasm_bindgen(js_name = "clearKeyframes")]
+    pub fn clear_keyframes(&self) {
+        self.tx.send(FromHost::ClearKeyframes).unwrap();
+    }
+
+    #[wasm_bindgen(js_name = "saveCameraPath")]
+    pub fn save_camera_path(&self, id: i32) {
+        self.tx.send(FromHost::SaveCameraPath(id)).unwrap();
+    }
+
     #[wasm_bindgen(js_name = "setTexture")]
     pub fn set_texture(&self, id: u32) {
         self.tx.send(FromHost::Texture(id)).unwrap();
     }
 
+    /// Turns the selected faces into light portals, or back into surfaces
+    #[wasm_bindgen(js_name = "setPortal")]
+    pub fn set_portal(&self, portal: bool) {
+        let kind = if portal {
+            FaceKind::Portal
+        } else {
+            FaceKind::Surface
+        };
+        self.tx.send(FromHost::FaceKind(kind)).unwrap();
+    }
+
+    /// Fills the selected solids with fog, or turns them back into solids
+    #[wasm_bindgen(js_name = "setFog")]
+    pub fn set_fog(&self, fog: bool) {
+        let kind = if fog { SolidKind::Fog } else { SolidKind::Solid };
+        self.tx.send(FromHost::SolidKind(kind)).unwrap();
+    }
+
+    /// Places the textures of the selected faces, `wrap` is "repeat", "clamp" or "mirror"
+    #[wasm_bindgen(js_name = "setTextureTransform")]
+    pub fn set_texture_transform(
+        &self,
+        scale_x: f32,
+        scale_y: f32,
+        rotation: f32,
+        offset_x: f32,
+        offset_y: f32,
+        wrap: &str,
+    ) {
+        let wrap = match wrap {
+            "clamp" => TextureWrap::Clamp,
+            "mirror" => TextureWrap::Mirror,
+            _ => TextureWrap::Repeat,
+        };
+        self.tx
+            .send(FromHost::TextureTransform(TextureTransform {
+                scale: [scale_x, scale_y].into(),
+                rotation,
+                offset: [offset_x, offset_y].into(),
+                wrap,
+            }))
+            .unwrap();
+    }
+
     #[wasm_bindgen(js_name = "setProp")]
     pub fn set_prop(&self, id: u32) {
         self.tx.send(FromHost::Prop(id)).unwrap();
@@ -117,6 +180,17 @@ impl Sender {
             }))
             .unwrap();
     }
+
+    #[wasm_bindgen(js_name = "loadLightmap")]
+    pub fn load_lightmap(&self, buf: Vec<u8>) {
+        self.tx
+            .send(FromHost::LoadResource(Resource {
+                id: 0,
+                buf,
+                kind: ResourceKind::Lightmap,
+            }))
+            .unwrap();
+    }
 }
 
 #[wasm_bindgen]
@@ -124,17 +198,24 @@ pub struct Callback {
     scene_saved: Function,
     button_feedback: Function,
     pointer_locked: Function,
+    camera_path_saved: Function,
 }
 
 #[wasm_bindgen]
 impl Callback {
     #[allow(clippy::new_without_default)]
     #[wasm_bindgen(constructor)]
-    pub fn new(scene_saved: Function, button_feedback: Function, pointer_locked: Function) -> Self {
+    pub fn new(
+        scene_saved: Function,
+        button_feedback: Function,
+        pointer_locked: Function,
+        camera_path_saved: Function,
+    ) -> Self {
         Self {
             scene_saved,
             button_feedback,
             pointer_locked,
+            camera_path_saved,
         }
     }
 }
@@ -151,6 +232,15 @@ impl Host for Callback {
                     )
                     .ok();
             }
+            ToHost::CameraPathSaved(id, buf) => {
+                self.camera_path_saved
+                    .call2(
+                        &JsValue::NULL,
+                        &JsValue::from(id),
+                        &Uint8Array::from(buf.as_slice()),
+                    )
+                    .ok();
+            }
             ToHost::Button(button) => {
                 self.button_feedback
                     .call1(&JsValue::NULL, &JsValue::from(button))
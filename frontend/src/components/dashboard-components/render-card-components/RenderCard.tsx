@@ -78,7 +78,11 @@ export default function RenderCard({ render, project }: RenderCardProps) {
                 bgcolor='rgba(0, 0, 0, 0.7)'
               >
                 <Box>
-                  {render.status === 0 ? (
+                  {render.error !== undefined ? (
+                    <Tooltip title={render.error} placement='top'>
+                      <Typography color='#F4F4F4'>{t("render_failed")}</Typography>
+                    </Tooltip>
+                  ) : render.status === 0 ? (
                     <Typography color='#F4F4F4'>{t("in_queue")}</Typography>
                   ) : (
                     <CircularProgressWithLabel
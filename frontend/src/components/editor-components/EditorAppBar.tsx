@@ -28,9 +28,20 @@ const CustomEditorAppBar = styled(AppBar)(({ theme }) => ({
 interface EditorAppBarProps {
   onSave: () => Promise<void>;
   onRender: (width: number, height: number, samples: number) => Promise<void>;
+  onRenderAnimation: (
+    width: number,
+    height: number,
+    samples: number
+  ) => Promise<void>;
+  onBake: () => Promise<void>;
 }
 
-export default function EditorAppBar({ onSave, onRender }: EditorAppBarProps) {
+export default function EditorAppBar({
+  onSave,
+  onRender,
+  onRenderAnimation,
+  onBake,
+}: EditorAppBarProps) {
   const { t } = useTranslation();
   const tooltipText: string =
     t("archytex") + " " + t("version") + " " + t("version_number");
@@ -41,6 +52,11 @@ export default function EditorAppBar({ onSave, onRender }: EditorAppBarProps) {
   const handleRenderSetupModalOpen = () => setRenderSetupModalOpen(true);
   const handleRenderSetupModalClose = () => setRenderSetupModalOpen(false);
 
+  const [animationSetupModalOpen, setAnimationSetupModalOpen] = useState(false);
+  const handleAnimationSetupModalOpen = () => setAnimationSetupModalOpen(true);
+  const handleAnimationSetupModalClose = () =>
+    setAnimationSetupModalOpen(false);
+
   return (
     <>
       <CustomEditorAppBar elevation={0}>
@@ -86,6 +102,22 @@ export default function EditorAppBar({ onSave, onRender }: EditorAppBarProps) {
                 >
                   {t("render")}
                 </Button>
+                <Button
+                  variant='text'
+                  color='inherit'
+                  sx={{ textTransform: "none" }}
+                  onClick={handleAnimationSetupModalOpen}
+                >
+                  {t("render_animation")}
+                </Button>
+                <Button
+                  variant='text'
+                  color='inherit'
+                  sx={{ textTransform: "none" }}
+                  onClick={() => onBake()}
+                >
+                  {t("bake_lighting")}
+                </Button>
                 <Button
                   variant='text'
                   color='inherit'
@@ -114,6 +146,13 @@ export default function EditorAppBar({ onSave, onRender }: EditorAppBarProps) {
         handleModalClose={handleRenderSetupModalClose}
         onRender={onRender}
       />
+
+      <RenderSetupModal
+        modalOpen={animationSetupModalOpen}
+        handleModalOpen={handleAnimationSetupModalOpen}
+        handleModalClose={handleAnimationSetupModalClose}
+        onRender={onRenderAnimation}
+      />
     </>
   );
 }
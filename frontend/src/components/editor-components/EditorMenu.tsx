@@ -6,8 +6,18 @@ import Box from "@mui/material/Box";
 import Button from "@mui/material/Button";
 import Typography from "@mui/material/Typography";
 import Divider from "@mui/material/Divider";
+import TextField from "@mui/material/TextField";
+import MenuItem from "@mui/material/MenuItem";
 
-import { Chair, Settings, Texture as TextureIcon } from "@mui/icons-material";
+import {
+  Chair,
+  Cloud,
+  LightMode,
+  OpenWith,
+  Settings,
+  Texture as TextureIcon,
+  Videocam,
+} from "@mui/icons-material";
 
 import LibraryDialog from "./library/LibraryDialog";
 
@@ -19,6 +29,18 @@ import Environment from "../../env";
 
 type LibraryType = "textureLibrary" | "propLibrary";
 
+export type TextureWrap = "repeat" | "clamp" | "mirror";
+
+// Placement of the texture on the selected faces
+export interface TextureTransform {
+  scaleX: number;
+  scaleY: number;
+  rotation: number;
+  offsetX: number;
+  offsetY: number;
+  wrap: TextureWrap;
+}
+
 interface Props {
   texture: Texture;
   handleTextureChange: (texture: Texture) => void;
@@ -26,6 +48,12 @@ interface Props {
   handlePropChange: (prop: Prop) => void;
   textures: Texture[];
   props: Prop[];
+  handlePortalChange: (portal: boolean) => void;
+  handleFogChange: (fog: boolean) => void;
+  handleTextureTransformChange: (transform: TextureTransform) => void;
+  keyframes: number[];
+  handleAddKeyframe: (time: number, fov: number) => void;
+  handleClearKeyframes: () => void;
 }
 
 export default function EditorMenu({
@@ -35,6 +63,12 @@ export default function EditorMenu({
   handlePropChange,
   textures,
   props,
+  handlePortalChange,
+  handleFogChange,
+  handleTextureTransformChange,
+  keyframes,
+  handleAddKeyframe,
+  handleClearKeyframes,
 }: Props) {
   const { t } = useTranslation();
 
@@ -51,12 +85,45 @@ export default function EditorMenu({
     setLibraryOpen(false);
   };
 
+  //Texture placement
+  const [transform, setTransform] = useState<TextureTransform>({
+    scaleX: 1,
+    scaleY: 1,
+    rotation: 0,
+    offsetX: 0,
+    offsetY: 0,
+    wrap: "repeat",
+  });
+  const numberField = (
+    key: "scaleX" | "scaleY" | "rotation" | "offsetX" | "offsetY",
+    label: string
+  ) => (
+    <TextField
+      label={t(label)}
+      type='number'
+      size='small'
+      value={transform[key]}
+      onChange={(e) =>
+        setTransform({ ...transform, [key]: Number(e.target.value) })
+      }
+    />
+  );
+
+  //Camera path, the next keyframe follows a second after the last one
+  const [keyframeTime, setKeyframeTime] = useState(0);
+  const [keyframeFov, setKeyframeFov] = useState(80);
+  const addKeyframe = () => {
+    handleAddKeyframe(keyframeTime, keyframeFov);
+    setKeyframeTime(keyframeTime + 1);
+  };
+
   return (
     <>
       <Box
         width='400px'
         display='flex'
         flexDirection='column'
+        overflow='auto'
         borderLeft={
           colorMode === ColorMode.Dark
             ? "1px solid #2E2E2E"
@@ -120,6 +187,134 @@ export default function EditorMenu({
 
         <Divider />
 
+        <Box display='flex' p={1}>
+          <OpenWith />
+          <Typography ml={1}>{t("texture_placement")}</Typography>
+        </Box>
+        <Box p={1} mb={1} display='flex' flexDirection='column' gap={1}>
+          <Typography variant='caption'>
+            {t("texture_placement_description")}
+          </Typography>
+          <Box display='flex' gap={1}>
+            {numberField("scaleX", "scale_x")}
+            {numberField("scaleY", "scale_y")}
+          </Box>
+          <Box display='flex' gap={1}>
+            {numberField("offsetX", "offset_x")}
+            {numberField("offsetY", "offset_y")}
+          </Box>
+          <Box display='flex' gap={1}>
+            {numberField("rotation", "rotation")}
+            <TextField
+              select
+              fullWidth
+              label={t("wrap")}
+              size='small'
+              value={transform.wrap}
+              onChange={(e) =>
+                setTransform({
+                  ...transform,
+                  wrap: e.target.value as TextureWrap,
+                })
+              }
+            >
+              <MenuItem value='repeat'>{t("wrap_repeat")}</MenuItem>
+              <MenuItem value='clamp'>{t("wrap_clamp")}</MenuItem>
+              <MenuItem value='mirror'>{t("wrap_mirror")}</MenuItem>
+            </TextField>
+          </Box>
+          <Button
+            variant='outlined'
+            onClick={() => handleTextureTransformChange(transform)}
+          >
+            {t("apply_texture_placement")}
+          </Button>
+        </Box>
+
+        <Divider />
+
+        <Box display='flex' p={1}>
+          <LightMode />
+          <Typography ml={1}>{t("portal")}</Typography>
+        </Box>
+        <Box p={1} mb={1} display='flex' flexDirection='column' gap={1}>
+          <Typography variant='caption'>{t("portal_description")}</Typography>
+          <Box display='flex' gap={1}>
+            <Button variant='outlined' onClick={() => handlePortalChange(true)}>
+              {t("make_portal")}
+            </Button>
+            <Button
+              variant='outlined'
+              onClick={() => handlePortalChange(false)}
+            >
+              {t("make_surface")}
+            </Button>
+          </Box>
+        </Box>
+
+        <Divider />
+
+        <Box display='flex' p={1}>
+          <Cloud />
+          <Typography ml={1}>{t("fog")}</Typography>
+        </Box>
+        <Box p={1} mb={1} display='flex' flexDirection='column' gap={1}>
+          <Typography variant='caption'>{t("fog_description")}</Typography>
+          <Box display='flex' gap={1}>
+            <Button variant='outlined' onClick={() => handleFogChange(true)}>
+              {t("make_fog")}
+            </Button>
+            <Button variant='outlined' onClick={() => handleFogChange(false)}>
+              {t("make_solid")}
+            </Button>
+          </Box>
+        </Box>
+
+        <Divider />
+
+        <Box display='flex' p={1}>
+          <Videocam />
+          <Typography ml={1}>{t("camera_path")}</Typography>
+        </Box>
+        <Box p={1} mb={1} display='flex' flexDirection='column' gap={1}>
+          <Typography variant='caption'>
+            {t("camera_path_description")}
+          </Typography>
+          <Box display='flex' gap={1}>
+            <TextField
+              label={t("keyframe_time")}
+              type='number'
+              size='small'
+              value={keyframeTime}
+              onChange={(e) => setKeyframeTime(Number(e.target.value))}
+            />
+            <TextField
+              label={t("field_of_view")}
+              type='number'
+              size='small'
+              value={keyframeFov}
+              onChange={(e) => setKeyframeFov(Number(e.target.value))}
+            />
+          </Box>
+          <Typography variant='caption'>
+            {keyframes.length === 0
+              ? t("no_keyframes")
+              : `${t("keyframes")}: ${keyframes
+                  .map((time) => `${time}s`)
+                  .join(", ")}`}
+          </Typography>
+          <Box display='flex' gap={1}>
+            <Button variant='outlined' onClick={addKeyframe}>
+              {t("add_keyframe")}
+            </Button>
+            <Button variant='outlined' onClick={() => handleClearKeyframes()}>
+              {t("clear_keyframes")}
+            </Button>
+          </Box>
+        </Box>
+
+        <Divider />
+
         <Box display='flex' p={1}>
           <Chair />
           <Typography ml={1}>{t("prop")}</Typography>
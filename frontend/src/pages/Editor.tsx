@@ -12,7 +12,9 @@ import Typography from "@mui/material/Typography";
 import { PhotoSizeSelectLarge } from "@mui/icons-material";
 
 import EditorAppBar from "../components/editor-components/EditorAppBar";
-import EditorMenu from "../components/editor-components/EditorMenu";
+import EditorMenu, {
+  TextureTransform,
+} from "../components/editor-components/EditorMenu";
 import EditorModeButtons from "../components/editor-components/EditorModeButtons";
 
 import useNotification from "../services/hooks/useNotification";
@@ -27,6 +29,7 @@ let listeners: { [key: number]: (value: Uint8Array) => void } = {};
 let rightDown = false;
 let loadedTextures = new Set<number>();
 let loadedProps = new Set<number>();
+let lightmapPoll: ReturnType<typeof setInterval> | undefined;
 
 export default function Editor() {
   const { t } = useTranslation();
@@ -119,6 +122,53 @@ export default function Editor() {
     }
   }, [prop, sender, textures]);
 
+  // Portals of the selected faces
+  const handlePortalChange = (portal: boolean) => {
+    if (sender !== null) {
+      sender.setPortal(portal);
+    }
+  };
+
+  // Fog of the selected solids
+  const handleFogChange = (fog: boolean) => {
+    if (sender !== null) {
+      sender.setFog(fog);
+    }
+  };
+
+  // Times of the keyframes of the camera path
+  const [keyframes, setKeyframes] = useState<number[]>([]);
+  const handleAddKeyframe = (time: number, fov: number) => {
+    if (sender !== null) {
+      sender.addKeyframe(time, fov);
+      setKeyframes(
+        [...keyframes.filter((other) => other !== time), time].sort(
+          (a, b) => a - b
+        )
+      );
+    }
+  };
+  const handleClearKeyframes = () => {
+    if (sender !== null) {
+      sender.clearKeyframes();
+      setKeyframes([]);
+    }
+  };
+
+  // Texture placement of the selected faces
+  const handleTextureTransformChange = (transform: TextureTransform) => {
+    if (sender !== null) {
+      sender.setTextureTransform(
+        transform.scaleX,
+        transform.scaleY,
+        transform.rotation,
+        transform.offsetX,
+        transform.offsetY,
+        transform.wrap
+      );
+    }
+  };
+
   const [width, setWidth] = useState(1);
   const [height, setHeight] = useState(1);
 
@@ -179,6 +229,9 @@ export default function Editor() {
             document.exitPointerLock();
             rightDown = false;
           }
+        },
+        (id: number, path: Uint8Array) => {
+          listeners[id](path);
         }
       );
       setVp(viewport);
@@ -276,6 +329,14 @@ export default function Editor() {
             });
 
             sender.loadScene(scene);
+
+            // Projects whose last bake failed are opened without a lightmap
+            const lightmap = await api
+              .loadLightmap(projectId)
+              .catch(() => new Uint8Array());
+            if (lightmap.length > 0) {
+              sender.loadLightmap(lightmap);
+            }
           }
         }
       }
@@ -293,6 +354,36 @@ export default function Editor() {
     [sender]
   );
 
+  let saveCameraPath = useCallback(
+    () =>
+      new Promise((resolve: (value: Uint8Array) => void) => {
+        const n = current_event;
+        current_event++;
+        listeners[n] = resolve;
+        sender.saveCameraPath(n);
+      }),
+    [sender]
+  );
+
+  const onRenderAnimation = async (
+    width: number,
+    height: number,
+    samples: number
+  ) => {
+    if (api?.state === "logged-in") {
+      if (keyframes.length === 0) {
+        addNotification(t("no_keyframes"), "error");
+        return;
+      }
+      addNotification(t("rendering_started"), "info");
+      const data = await save();
+      const path = await saveCameraPath();
+      await api.renderAnimation(data, path, projectId, width, height, samples);
+    } else {
+      addNotification(t("not_logged_in"), "error");
+    }
+  };
+
   const onRender = async (width: number, height: number, samples: number) => {
     if (api?.state === "logged-in") {
       addNotification(t("rendering_started"), "info");
@@ -303,6 +394,37 @@ export default function Editor() {
     }
   };
 
+  // Stop waiting for a lightmap when leaving the editor
+  useEffect(() => () => clearInterval(lightmapPoll), []);
+
+  const onBake = async () => {
+    if (api?.state === "logged-in") {
+      addNotification(t("baking_started"), "info");
+      const data = await save();
+      await api.bake(data, projectId);
+      // The earlier lightmap is removed until the new one is baked
+      clearInterval(lightmapPoll);
+      lightmapPoll = setInterval(async () => {
+        let lightmap;
+        try {
+          lightmap = await api.loadLightmap(projectId);
+        } catch {
+          // The bake failed, no lightmap is coming
+          clearInterval(lightmapPoll);
+          addNotification(t("could_not_bake_lighting"), "error");
+          return;
+        }
+        if (lightmap.length > 0) {
+          clearInterval(lightmapPoll);
+          sender.loadLightmap(lightmap);
+          addNotification(t("lighting_baked"), "success");
+        }
+      }, 5000);
+    } else {
+      addNotification(t("not_logged_in"), "error");
+    }
+  };
+
   // App bar button click
   const handleSaveProject = async () => {
     console.log("Got Save event");
@@ -360,7 +482,12 @@ export default function Editor() {
 
   return (
     <>
-      <EditorAppBar onSave={handleSaveProject} onRender={onRender} />
+      <EditorAppBar
+        onSave={handleSaveProject}
+        onRender={onRender}
+        onRenderAnimation={onRenderAnimation}
+        onBake={onBake}
+      />
       <Box width='100%' height='48px'></Box>
       <Box display='flex' height={`calc(100vh - 48px)`} overflow='hidden'>
         <Box width='100%' height='100%' ref={observe} bgcolor='#0c0c0c' />
@@ -372,6 +499,12 @@ export default function Editor() {
             handlePropChange={handlePropChange}
             textures={textures}
             props={props}
+            handlePortalChange={handlePortalChange}
+            handleFogChange={handleFogChange}
+            handleTextureTransformChange={handleTextureTransformChange}
+            keyframes={keyframes}
+            handleAddKeyframe={handleAddKeyframe}
+            handleClearKeyframes={handleClearKeyframes}
           />
         )}
       </Box>
@@ -10,6 +10,7 @@ interface Render{
     started: Date,
     finished: Date,
     icon: string
+    error?: string
 }
 
 interface Project{
@@ -26,6 +26,9 @@ interface UserLoggedIn {
   save: (data: Uint8Array, id: string) => Promise<void>;
   load: (id: string) => Promise<Uint8Array | undefined>;
   render: (data: Uint8Array, id: string, width: number, height: number, samples: number) => Promise<void>;
+  renderAnimation: (data: Uint8Array, path: Uint8Array, id: string, width: number, height: number, samples: number) => Promise<void>;
+  bake: (data: Uint8Array, id: string) => Promise<void>;
+  loadLightmap: (id: string) => Promise<Uint8Array>;
   modifyUser: (data: ModifyUserType) => Promise<void>;
 }
 interface UserNotLoggedIn {
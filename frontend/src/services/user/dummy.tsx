@@ -88,6 +88,15 @@ const DummyProvider = ({
           render: async (data: Uint8Array, id: string) => {
             console.log(data.length);
           },
+          renderAnimation: async (data: Uint8Array, path: Uint8Array, id: string) => {
+            console.log(data.length, path.length);
+          },
+          bake: async (data: Uint8Array, id: string) => {
+            console.log(data.length);
+          },
+          loadLightmap: async (id: string) => {
+            return new Uint8Array();
+          },
           load: async(id: string) => {
             return undefined;
           },
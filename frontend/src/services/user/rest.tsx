@@ -91,6 +91,7 @@ interface RenderUpdate {
   started: string;
   finished: string;
   icon: string;
+  error?: string;
 }
 
 interface ProjectUpdate {
@@ -239,6 +240,31 @@ const RestProvider = ({
           headers: { "Content-Type": "application/octet-stream" },
         });
       },
+      renderAnimation: async (data: Uint8Array, path: Uint8Array, id: string, width: number, height: number, samples: number) => {
+        const form = new FormData();
+        form.append("scene", new Blob([data]));
+        form.append("path", new Blob([path]));
+        internal.fetch(`${Environment.base_url}auth/project/${id}/animation/${width}/${height}/${samples}`, {
+          method: "POST",
+          body: form,
+        });
+      },
+      bake: async (data: Uint8Array, id: string) => {
+        await internal.fetch(`${Environment.base_url}auth/project/${id}/lightmap`, {
+          method: "POST",
+          body: data,
+          headers: { "Content-Type": "application/octet-stream" },
+        });
+      },
+      loadLightmap: async (id: string) => {
+        const result = await internal.fetch(
+          `${Environment.base_url}auth/project/${id}/lightmap`,
+          {
+            method: "GET",
+          }
+        );
+        return new Uint8Array(await result.arrayBuffer());
+      },
       load: async (id: string) => {
         const result = await internal.fetch(
           `${Environment.base_url}auth/project/${id}/data`,
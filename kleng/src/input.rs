@@ -12,9 +12,35 @@ pub struct Assets {
 pub struct Texture {
     pub diffuse: String,
     pub emissive: Option<String>,
+    pub normal: Option<String>,
+    pub bump: Option<String>,
+    pub roughness: Option<String>,
+    pub metallic: Option<String>,
+    pub occlusion: Option<String>,
+    #[serde(default)]
+    pub double_sided: bool,
+    /// Color space of the diffuse and emissive images, such as "srgb", "display_p3" or "acescg"
+    #[serde(default)]
+    pub color_space: Option<String>,
     pub categories: Vec<String>,
 }
 
+impl Texture {
+    /// Paths of the surface maps, by the suffix they are exported with
+    pub fn maps(&self) -> Vec<(&'static str, &String)> {
+        [
+            ("normal", &self.normal),
+            ("bump", &self.bump),
+            ("roughness", &self.roughness),
+            ("metallic", &self.metallic),
+            ("occlusion", &self.occlusion),
+        ]
+        .into_iter()
+        .filter_map(|(suffix, path)| path.as_ref().map(|path| (suffix, path)))
+        .collect()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Prop {
     pub source: String,
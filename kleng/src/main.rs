@@ -60,6 +60,15 @@ fn main() {
                 )))
                 .unwrap();
         }
+
+        for (suffix, path) in texture.maps() {
+            let file = File::open(root.join("textures").join(path)).unwrap();
+            let reader = BufReader::new(file);
+            image::load(reader, ImageFormat::Png)
+                .unwrap()
+                .save(root.join(format!("out/raytracer/textures/{}_{}.png", name, suffix)))
+                .unwrap();
+        }
     }
 
     let indexed_textures: HashMap<String, Indexed<input::Texture>> = assets
@@ -100,11 +109,23 @@ fn main() {
 
         let textures = indexed_textures
             .into_iter()
-            .map(|(name, texture)| repo::Texture {
-                id: texture.id,
-                name,
-                categories: texture.value.categories,
-                emissive: texture.value.emissive,
+            .map(|(name, texture)| {
+                let map = |path: &Option<String>, suffix: &str| {
+                    path.as_ref().map(|_| format!("{}_{}", name, suffix))
+                };
+                repo::Texture {
+                    id: texture.id,
+                    normal: map(&texture.value.normal, "normal"),
+                    bump: map(&texture.value.bump, "bump"),
+                    roughness: map(&texture.value.roughness, "roughness"),
+                    metallic: map(&texture.value.metallic, "metallic"),
+                    occlusion: map(&texture.value.occlusion, "occlusion"),
+                    double_sided: texture.value.double_sided,
+                    color_space: texture.value.color_space,
+                    categories: texture.value.categories,
+                    emissive: texture.value.emissive,
+                    name,
+                }
             })
             .collect();
 
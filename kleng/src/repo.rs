@@ -12,6 +12,13 @@ pub struct Texture {
     pub name: String,
     pub categories: Vec<String>,
     pub emissive: Option<String>,
+    pub normal: Option<String>,
+    pub bump: Option<String>,
+    pub roughness: Option<String>,
+    pub metallic: Option<String>,
+    pub occlusion: Option<String>,
+    pub double_sided: bool,
+    pub color_space: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1,15 +1,16 @@
 use archyrt_core::{
     api::fragment_collector::FragmentCollector,
     collector::image_collector::ImageCollector,
-    intersectables::sphere::Sphere,
-    renderers::basic_renderer::BasicRenderer,
-    textures::texture_repo::TextureRepository,
+    intersectables::{bvh::BVH, packed_bvh::PackedBVH, sphere::Sphere, triangle::Triangle},
+    renderers::{basic_renderer::BasicRenderer, path_tracer::Material},
+    textures::{texture_repo::TextureRepository, TextureID},
     utilities::{
         math::Vec3,
         ray::{Intersectable, Ray},
     },
 };
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 pub fn sphere_ray_intersection(c: &mut Criterion) {
     let sphere = Sphere {
@@ -43,5 +44,69 @@ pub fn rendering(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, sphere_ray_intersection, rendering);
+/// Small random triangles scattered in a box
+fn triangle_soup(count: usize) -> Vec<Triangle> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut point = |scale: f64| Vec3::new(rng.gen(), rng.gen(), rng.gen()) * scale;
+    (0..count)
+        .map(|_| {
+            let a = point(100.0);
+            let b = a + point(1.0);
+            let c = a + point(1.0);
+            Triangle::new(
+                [a, b, c],
+                [Default::default(); 3],
+                TextureID::new(&0),
+                Material::Diffuse,
+            )
+        })
+        .collect()
+}
+
+pub fn bvh_construction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bvh-construction");
+    group.sample_size(10);
+    for count in [10_000, 100_000, 1_000_000, 2_000_000] {
+        let triangles = triangle_soup(count);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &triangles,
+            |b, triangles| b.iter(|| black_box(BVH::from_triangles(triangles))),
+        );
+    }
+    group.finish();
+}
+
+/// Rays from the middle of the triangle soup in random directions
+fn random_rays(count: usize) -> Vec<Ray> {
+    let mut rng = StdRng::seed_from_u64(1);
+    (0..count)
+        .map(|_| {
+            let direction = Vec3::new(rng.gen(), rng.gen(), rng.gen()) - Vec3::from_single(0.5);
+            Ray::new(Vec3::from_single(50.0), direction.normalized())
+        })
+        .collect()
+}
+
+pub fn bvh_intersection(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bvh-intersection");
+    let bvh = BVH::from_triangles(&triangle_soup(100_000)).unwrap();
+    let packed = PackedBVH::new(&bvh);
+    let rays = random_rays(1000);
+    group.bench_function("double", |b| {
+        b.iter(|| rays.iter().filter_map(|ray| bvh.intersect(*ray)).count())
+    });
+    group.bench_function("packed", |b| {
+        b.iter(|| rays.iter().filter_map(|ray| packed.intersect(*ray)).count())
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    sphere_ray_intersection,
+    rendering,
+    bvh_construction,
+    bvh_intersection
+);
 criterion_main!(benches);
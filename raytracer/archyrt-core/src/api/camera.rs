@@ -6,6 +6,10 @@ use super::fragment_render::FragmentContext;
 
 pub trait Camera {
     fn get_ray(&self, ctx: &FragmentContext, pos: Vec2) -> Ray;
+    /// Angle between the rays of neighbouring pixels
+    fn spread(&self, _ctx: &FragmentContext) -> f64 {
+        0.0
+    }
 }
 
 impl<T> Camera for &T
@@ -15,4 +19,7 @@ where
     fn get_ray(&self, ctx: &FragmentContext, pos: Vec2) -> Ray {
         (*self).get_ray(ctx, pos)
     }
+    fn spread(&self, ctx: &FragmentContext) -> f64 {
+        (*self).spread(ctx)
+    }
 }
@@ -0,0 +1,116 @@
+use std::ops::{AddAssign, Div, Mul};
+
+use smallvec::SmallVec;
+
+use crate::{
+    loaders::amdl::repo::PropID,
+    textures::TextureID,
+    utilities::math::{Vec2, Vec3},
+};
+
+use super::fragment_render::{FragmentContext, FragmentRender};
+
+/// Source of emitted light
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum LightGroup {
+    Sky,
+    /// Distant light of [`crate::renderers::media::Sun`]
+    Sun,
+    /// Objects with a constant emissive material
+    Emissive,
+    /// Surfaces with an emissive texture, which aren't part of a prop
+    EmissiveTexture(TextureID),
+    /// Lamps and other props with emissive textures, one group for every instance of the same prop
+    Prop(PropID),
+}
+
+impl LightGroup {
+    /// Name of the group, usable in file names
+    pub fn name(&self) -> String {
+        match self {
+            Self::Sky => "sky".to_string(),
+            Self::Sun => "sun".to_string(),
+            Self::Emissive => "emissive".to_string(),
+            Self::EmissiveTexture(texture) => format!("texture_{}", texture),
+            Self::Prop(prop) => format!("prop_{}", prop),
+        }
+    }
+}
+
+/// Groups stored without allocating, enough for the sky, the sun and a few emitters
+const INLINE_GROUPS: usize = 4;
+
+/// Light arriving at a fragment, split by its source
+#[derive(Debug, Clone, Default)]
+pub struct LightLayers {
+    pub layers: SmallVec<[(LightGroup, Vec3); INLINE_GROUPS]>,
+}
+
+impl LightLayers {
+    pub fn new() -> Self {
+        Self {
+            layers: SmallVec::new(),
+        }
+    }
+    pub fn add(&mut self, group: LightGroup, color: Vec3) {
+        match self.layers.iter_mut().find(|(g, _)| *g == group) {
+            Some((_, c)) => *c += color,
+            None => self.layers.push((group, color)),
+        }
+    }
+    pub fn get(&self, group: LightGroup) -> Vec3 {
+        self.layers
+            .iter()
+            .find(|(g, _)| *g == group)
+            .map(|(_, c)| *c)
+            .unwrap_or_default()
+    }
+    /// Sum of every layer
+    pub fn beauty(&self) -> Vec3 {
+        self.layers
+            .iter()
+            .fold(Vec3::default(), |a, (_, c)| a + *c)
+    }
+}
+
+impl AddAssign for LightLayers {
+    fn add_assign(&mut self, rhs: Self) {
+        for (group, color) in rhs.layers {
+            self.add(group, color);
+        }
+    }
+}
+
+impl Div<f64> for LightLayers {
+    type Output = Self;
+
+    fn div(self, rhs: f64) -> Self::Output {
+        let mut o = self;
+        for (_, c) in o.layers.iter_mut() {
+            *c /= rhs;
+        }
+        o
+    }
+}
+
+impl Mul<Vec3> for LightLayers {
+    type Output = Self;
+
+    fn mul(self, rhs: Vec3) -> Self::Output {
+        let mut o = self;
+        for (_, c) in o.layers.iter_mut() {
+            *c *= rhs;
+        }
+        o
+    }
+}
+
+pub trait LayeredFragmentRender: FragmentRender {
+    fn render_layers(&self, ctx: &FragmentContext, pos: Vec2) -> LightLayers;
+}
+
+impl<T: LayeredFragmentRender> LayeredFragmentRender for &T {
+    fn render_layers(&self, ctx: &FragmentContext, pos: Vec2) -> LightLayers {
+        (*self).render_layers(ctx, pos)
+    }
+}
@@ -1,3 +1,4 @@
 pub mod camera;
 pub mod fragment_collector;
 pub mod fragment_render;
+pub mod light_layers;
\ No newline at end of file
@@ -1,4 +1,3 @@
-use rand::thread_rng;
 use rand_distr::{Uniform, Distribution};
 
 use crate::utilities::math::Matrix3x3;
@@ -7,6 +6,7 @@ use crate::{
     matrix,
     utilities::{
         math::{Matrix, Vec2, Vec3},
+        random,
         ray::Ray,
     },
     vector,
@@ -33,10 +33,13 @@ impl<C: Camera> JitterCamera<C> {
 
 impl<C: Camera> Camera for JitterCamera<C> {
     fn get_ray(&self, ctx: &FragmentContext, pos: Vec2) -> Ray {
-        let mut rng = thread_rng();
+        let mut rng = random::rng();
         let x: f64 = self.x_dist.sample(&mut rng);
         let y: f64 = self.y_dist.sample(&mut rng);
         let jitter = vector![x, y];
         self.inner.get_ray(ctx, pos + jitter)
     }
+    fn spread(&self, ctx: &FragmentContext) -> f64 {
+        self.inner.spread(ctx)
+    }
 }
@@ -1,2 +1,5 @@
 pub mod perspective;
 pub mod jitter;
+pub mod path;
+#[cfg(test)]
+mod tests;
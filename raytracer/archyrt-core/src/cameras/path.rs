@@ -0,0 +1,150 @@
+use anyhow::{anyhow, Result};
+use asset::scene;
+use cgmath::{vec2, vec3};
+
+use crate::{
+    cameras::perspective::PerspectiveCamera,
+    loaders::ascn::editor_camera,
+    utilities::math::{Vec2, Vec3},
+    vector,
+};
+
+/// Focal distance of a camera with this vertical field of view, in degrees
+pub fn focal_distance(fov: f64) -> f64 {
+    0.5 / (fov.to_radians() * 0.5).tan()
+}
+
+/// Camera seeing from a keyframe
+pub fn keyframe_camera(keyframe: &scene::CameraKeyframe) -> PerspectiveCamera {
+    editor_camera(
+        keyframe.position.into(),
+        keyframe.rotation.into(),
+        focal_distance(keyframe.fov as f64),
+    )
+}
+
+/// Keyframe a single frame is rendered from, stored as JSON with the other settings of its render
+pub fn keyframe_from_json(json: &str) -> Result<scene::CameraKeyframe> {
+    Ok(serde_json::from_str(json)?)
+}
+
+pub fn keyframe_to_json(keyframe: &scene::CameraKeyframe) -> String {
+    serde_json::to_string(keyframe).unwrap()
+}
+
+/// Position, pitch, yaw and field of view of a keyframe
+type Values = [f64; 6];
+
+/// Camera moving smoothly through keyframes, interpolated with a Catmull-Rom spline.
+/// The camera rests at the first and last keyframes.
+#[derive(Debug, Clone)]
+pub struct CameraPath {
+    times: Vec<f64>,
+    values: Vec<Values>,
+}
+
+impl CameraPath {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let path = scene::CameraPath::decode(data)
+            .ok_or_else(|| anyhow!("Could not decode camera path"))?;
+        Self::new(&path)
+    }
+    pub fn new(path: &scene::CameraPath) -> Result<Self> {
+        let mut keyframes = path.keyframes.clone();
+        if keyframes.is_empty() {
+            return Err(anyhow!("Camera path has no keyframes"));
+        }
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let times = keyframes
+            .iter()
+            .map(|keyframe| keyframe.time as f64)
+            .collect();
+        let mut values: Vec<Values> = keyframes
+            .iter()
+            .map(|keyframe| {
+                let [x, y, z]: [f32; 3] = keyframe.position.into();
+                let [pitch, yaw]: [f32; 2] = keyframe.rotation.into();
+                [x, y, z, pitch, yaw, keyframe.fov].map(|value| value as f64)
+            })
+            .collect();
+        //Turn the short way around between keyframes
+        for i in 1..values.len() {
+            for angle in [3, 4] {
+                let previous = values[i - 1][angle];
+                let turn = (values[i][angle] - previous + 180.0).rem_euclid(360.0) - 180.0;
+                values[i][angle] = previous + turn;
+            }
+        }
+        Ok(Self { times, values })
+    }
+    /// Seconds from the first keyframe to the last
+    pub fn duration(&self) -> f64 {
+        self.times[self.times.len() - 1] - self.times[0]
+    }
+    /// Tangent of the spline at a keyframe
+    fn tangent(&self, i: usize) -> Values {
+        if i == 0 || i == self.times.len() - 1 {
+            return [0.0; 6];
+        }
+        let dt = self.times[i + 1] - self.times[i - 1];
+        let mut tangent = [0.0; 6];
+        for (j, value) in tangent.iter_mut().enumerate() {
+            *value = (self.values[i + 1][j] - self.values[i - 1][j]) / dt;
+        }
+        tangent
+    }
+    fn values(&self, time: f64) -> Values {
+        let time = time + self.times[0];
+        let next = self.times.partition_point(|t| *t <= time);
+        if next == 0 {
+            return self.values[0];
+        }
+        if next == self.times.len() {
+            return self.values[next - 1];
+        }
+        let i = next - 1;
+        let dt = self.times[next] - self.times[i];
+        let s = (time - self.times[i]) / dt;
+        //Cubic Hermite basis
+        let h00 = 2.0 * s * s * s - 3.0 * s * s + 1.0;
+        let h10 = s * s * s - 2.0 * s * s + s;
+        let h01 = -2.0 * s * s * s + 3.0 * s * s;
+        let h11 = s * s * s - s * s;
+        let (m0, m1) = (self.tangent(i), self.tangent(next));
+        let (p0, p1) = (self.values[i], self.values[next]);
+        let mut values = [0.0; 6];
+        for (j, value) in values.iter_mut().enumerate() {
+            *value = h00 * p0[j] + h10 * dt * m0[j] + h01 * p1[j] + h11 * dt * m1[j];
+        }
+        values
+    }
+    /// Camera `time` seconds after the first keyframe
+    pub fn camera(&self, time: f64) -> PerspectiveCamera {
+        let [x, y, z, pitch, yaw, fov] = self.values(time);
+        let position: Vec3 = vector![x, y, z];
+        let rotation: Vec2 = vector![pitch, yaw];
+        editor_camera(position, rotation, focal_distance(fov))
+    }
+    /// Keyframe `time` seconds after the first keyframe, timed from the first keyframe
+    pub fn keyframe(&self, time: f64) -> scene::CameraKeyframe {
+        let [x, y, z, pitch, yaw, fov] = self.values(time);
+        scene::CameraKeyframe {
+            time: time as f32,
+            position: vec3(x, y, z).cast().unwrap(),
+            rotation: vec2(pitch, yaw).cast().unwrap(),
+            fov: fov as f32,
+        }
+    }
+    /// Number of frames of a video with `fps` frames per second, from the first keyframe to the last
+    pub fn frame_count(&self, fps: f64) -> usize {
+        (self.duration() * fps).floor() as usize + 1
+    }
+    /// Cameras of the frames of a video with `fps` frames per second
+    pub fn frames(&self, fps: f64) -> impl Iterator<Item = PerspectiveCamera> + '_ {
+        (0..self.frame_count(fps)).map(move |frame| self.camera(frame as f64 / fps))
+    }
+    /// Keyframes of the frames of a video with `fps` frames per second, for rendering them separately
+    pub fn frame_keyframes(&self, fps: f64) -> impl Iterator<Item = scene::CameraKeyframe> + '_ {
+        (0..self.frame_count(fps)).map(move |frame| self.keyframe(frame as f64 / fps))
+    }
+}
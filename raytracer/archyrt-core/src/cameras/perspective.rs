@@ -60,4 +60,8 @@ impl Camera for PerspectiveCamera {
             direction: dir,
         }
     }
+    fn spread(&self, ctx: &FragmentContext) -> f64 {
+        //The image plane is one unit tall
+        (1.0 / ctx.height / self.focal_distance).atan()
+    }
 }
@@ -0,0 +1,106 @@
+mod path {
+    use asset::scene::{CameraKeyframe, CameraPath as Keyframes};
+    use cgmath::{vec2, vec3};
+
+    use crate::{
+        cameras::{
+            path::{focal_distance, keyframe_camera, keyframe_from_json, keyframe_to_json, CameraPath},
+            perspective::PerspectiveCamera,
+        },
+        loaders::ascn::{editor_camera, FOCAL_DISTANCE},
+        utilities::math::{Vec2, Vec3},
+        vector,
+    };
+
+    fn keyframe(time: f32, x: f32, yaw: f32) -> CameraKeyframe {
+        CameraKeyframe {
+            time,
+            position: vec3(x, 1.0, 2.0),
+            rotation: vec2(10.0, yaw),
+            fov: 80.0,
+        }
+    }
+
+    fn path(keyframes: Vec<CameraKeyframe>) -> CameraPath {
+        CameraPath::new(&Keyframes { keyframes }).unwrap()
+    }
+
+    fn same(a: &PerspectiveCamera, b: &PerspectiveCamera) -> bool {
+        let matrix = (0..3).all(|i| (a.matrix[i] - b.matrix[i]).length() < 1e-6);
+        matrix
+            && (a.position - b.position).length() < 1e-6
+            && (a.focal_distance - b.focal_distance).abs() < 1e-6
+    }
+
+    #[test]
+    fn fov() {
+        assert!((focal_distance(80.0) - FOCAL_DISTANCE).abs() < 1e-5);
+    }
+    #[test]
+    fn keyframes() {
+        let path = path(vec![
+            keyframe(1.0, 0.0, 0.0),
+            keyframe(3.0, 4.0, 90.0),
+            keyframe(2.0, 1.0, 30.0),
+        ]);
+        assert_eq!(path.duration(), 2.0);
+        for (time, x, yaw) in [(0.0, 0.0, 0.0), (1.0, 1.0, 30.0), (2.0, 4.0, 90.0)] {
+            let position: Vec3 = vector![x, 1.0, 2.0];
+            let rotation: Vec2 = vector![10.0, yaw];
+            let expected = editor_camera(position, rotation, FOCAL_DISTANCE);
+            assert!(same(&path.camera(time), &expected));
+        }
+        //Times outside the path hold the first and last keyframes
+        assert!(same(&path.camera(-1.0), &path.camera(0.0)));
+        assert!(same(&path.camera(5.0), &path.camera(2.0)));
+    }
+    #[test]
+    fn smooth() {
+        let path = path(vec![
+            keyframe(0.0, 0.0, 0.0),
+            keyframe(1.0, 1.0, 0.0),
+            keyframe(2.0, 2.0, 0.0),
+        ]);
+        let x = |time: f64| path.camera(time).position.x();
+        //Starts at rest and moves steadily through the middle keyframe
+        assert!(x(0.01) < 0.001);
+        let before = x(1.0) - x(0.99);
+        let after = x(1.01) - x(1.0);
+        assert!((before - after).abs() < 1e-4);
+        let positions: Vec<f64> = (0..=20).map(|i| x(i as f64 / 10.0)).collect();
+        assert!(positions.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+    #[test]
+    fn short_turn() {
+        let path = path(vec![keyframe(0.0, 0.0, 350.0), keyframe(1.0, 0.0, 10.0)]);
+        let position: Vec3 = vector![0.0, 1.0, 2.0];
+        let rotation: Vec2 = vector![10.0, 0.0];
+        let expected = editor_camera(position, rotation, FOCAL_DISTANCE);
+        assert!(same(&path.camera(0.5), &expected));
+    }
+    #[test]
+    fn frames() {
+        let path = path(vec![keyframe(0.0, 0.0, 0.0), keyframe(2.0, 4.0, 0.0)]);
+        let frames: Vec<PerspectiveCamera> = path.frames(24.0).collect();
+        assert_eq!(frames.len(), 49);
+        assert!(same(&frames[0], &path.camera(0.0)));
+        assert!(same(&frames[48], &path.camera(2.0)));
+        assert!(CameraPath::new(&Keyframes::default()).is_err());
+    }
+    #[test]
+    fn frame_keyframes() {
+        let path = path(vec![keyframe(0.0, 0.0, 0.0), keyframe(2.0, 4.0, 90.0)]);
+        let keyframes: Vec<CameraKeyframe> = path.frame_keyframes(24.0).collect();
+        assert_eq!(keyframes.len(), 49);
+        for (frame, keyframe) in keyframes.iter().enumerate() {
+            let expected = path.camera(frame as f64 / 24.0);
+            let camera = keyframe_camera(keyframe);
+            assert!((camera.position - expected.position).length() < 1e-5);
+            assert!((0..3).all(|i| (camera.matrix[i] - expected.matrix[i]).length() < 1e-5));
+        }
+        //Frames are sent to the workers as JSON
+        let json = keyframe_to_json(&keyframes[10]);
+        assert_eq!(keyframe_from_json(&json).unwrap(), keyframes[10]);
+        assert!(keyframe_from_json("{}").is_err());
+    }
+}
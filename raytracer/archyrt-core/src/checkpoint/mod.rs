@@ -0,0 +1,205 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use asset::scene::CameraKeyframe;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    postprocess::FloatImage,
+    renderers::media::Atmosphere,
+    utilities::{files::write_atomic, math::Vec3},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Settings the samples of a render depend on. Only checkpoints with the same settings can be resumed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    /// See [`crate::renderers::integrator::NamedIntegrator::from_name`]
+    pub integrator: String,
+    pub clay: bool,
+    pub atmosphere: Atmosphere,
+    /// Keyframe the render is seen from instead of the scene's camera, for the frames of camera paths
+    pub camera: Option<CameraKeyframe>,
+}
+
+/// Running per-pixel sums of the samples of a render
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulator {
+    pub sum: FloatImage,
+    pub sum_squared: FloatImage,
+    /// Number of samples added to each pixel
+    pub samples: Vec<u32>,
+}
+
+impl Accumulator {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            sum: FloatImage::new(width, height),
+            sum_squared: FloatImage::new(width, height),
+            samples: vec![0; width * height],
+        }
+    }
+    /// Accumulator from sums of three floats per pixel
+    pub fn from_raw(
+        width: usize,
+        height: usize,
+        sum: &[f32],
+        sum_squared: &[f32],
+        samples: Vec<u32>,
+    ) -> Self {
+        Self {
+            sum: FloatImage::from_raw(width, height, sum),
+            sum_squared: FloatImage::from_raw(width, height, sum_squared),
+            samples,
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.sum.width
+    }
+    pub fn height(&self) -> usize {
+        self.sum.height
+    }
+    pub fn add(&mut self, x: usize, y: usize, color: Vec3) {
+        let index = y * self.width() + x;
+        self.sum.pixels[index] += color;
+        self.sum_squared.pixels[index] += color * color;
+        self.samples[index] += 1;
+    }
+    /// Adds one sample of every pixel of a tile starting at a pixel
+    pub fn add_tile(&mut self, x: usize, y: usize, tile: &FloatImage) {
+        for ty in 0..tile.height {
+            for tx in 0..tile.width {
+                self.add(x + tx, y + ty, tile.get(tx, ty));
+            }
+        }
+    }
+    /// Fewest samples of a pixel of a region
+    pub fn region_samples(&self, x: usize, y: usize, width: usize, height: usize) -> u32 {
+        (y..y + height)
+            .flat_map(|py| (x..x + width).map(move |px| (px, py)))
+            .map(|(px, py)| self.samples[py * self.width() + px])
+            .min()
+            .unwrap_or(0)
+    }
+    /// Average of the samples, pixels without samples are black
+    pub fn mean(&self) -> FloatImage {
+        let mut image = FloatImage::new(self.width(), self.height());
+        for (i, pixel) in image.pixels.iter_mut().enumerate() {
+            if self.samples[i] > 0 {
+                *pixel = self.sum.pixels[i] / self.samples[i] as f64;
+            }
+        }
+        image
+    }
+    /// Unbiased variance of the samples, pixels with less than two samples have none
+    pub fn variance(&self) -> FloatImage {
+        let mut image = FloatImage::new(self.width(), self.height());
+        for (i, pixel) in image.pixels.iter_mut().enumerate() {
+            let n = self.samples[i] as f64;
+            if n > 1.0 {
+                let mean = self.sum.pixels[i] / n;
+                let variance = (self.sum_squared.pixels[i] / n - mean * mean) * (n / (n - 1.0));
+                *pixel = variance.max(Vec3::default());
+            }
+        }
+        image
+    }
+}
+
+/// Accumulated samples of an unfinished render, saved so a later render of the same scene can resume it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// Content key of the scene file, see [`crate::loaders::bvh_cache::content_key`]
+    pub scene: String,
+    pub settings: RenderSettings,
+    pub accumulator: Accumulator,
+}
+
+/// Stored form of a checkpoint, with single precision sums
+#[derive(Serialize, Deserialize)]
+struct StoredCheckpoint {
+    scene: String,
+    settings: RenderSettings,
+    sum: Vec<f32>,
+    sum_squared: Vec<f32>,
+    samples: Vec<u32>,
+}
+
+impl Checkpoint {
+    /// Identifies the checkpoints of renders of a scene with these settings
+    pub fn key(scene: &str, settings: &RenderSettings) -> String {
+        let settings = serde_json::to_string(settings).unwrap();
+        Sha256::digest(format!("{}#{}", scene, settings).as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+    /// Whether a render of a scene with these settings can continue from this checkpoint
+    pub fn resumes(&self, scene: &str, settings: &RenderSettings) -> bool {
+        self.scene == scene && &self.settings == settings
+    }
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(&StoredCheckpoint {
+            scene: self.scene.clone(),
+            settings: self.settings.clone(),
+            sum: self.accumulator.sum.to_raw(),
+            sum_squared: self.accumulator.sum_squared.to_raw(),
+            samples: self.accumulator.samples.clone(),
+        })?)
+    }
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let stored: StoredCheckpoint = bincode::deserialize(data)?;
+        let (width, height) = (stored.settings.width, stored.settings.height);
+        let pixels = width * height;
+        if stored.sum.len() != pixels * 3
+            || stored.sum_squared.len() != pixels * 3
+            || stored.samples.len() != pixels
+        {
+            return Err(anyhow!("Checkpoint doesn't match its resolution"));
+        }
+        Ok(Self {
+            accumulator: Accumulator::from_raw(
+                width,
+                height,
+                &stored.sum,
+                &stored.sum_squared,
+                stored.samples,
+            ),
+            scene: stored.scene,
+            settings: stored.settings,
+        })
+    }
+}
+
+/// Checkpoints stored in a local directory, for dispatchers that shouldn't keep full buffers on Redis
+pub struct CheckpointDirectory {
+    directory: PathBuf,
+}
+
+impl CheckpointDirectory {
+    pub fn new<P: AsRef<Path>>(directory: P) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+        }
+    }
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(key).with_extension("ackp")
+    }
+    /// Stored checkpoint, `None` if it is missing or can't be decoded
+    pub fn load(&self, key: &str) -> Option<Checkpoint> {
+        let data = fs::read(self.path(key)).ok()?;
+        Checkpoint::decode(&data).ok()
+    }
+    /// Stores a checkpoint through a temporary file, so a restart while saving keeps the previous one
+    pub fn store(&self, key: &str, checkpoint: &Checkpoint) -> Result<()> {
+        write_atomic(self.path(key), &checkpoint.encode()?)
+    }
+}
@@ -0,0 +1,118 @@
+mod checkpoint {
+    use asset::scene::CameraKeyframe;
+    use cgmath::{vec2, vec3};
+
+    use crate::{
+        checkpoint::{Accumulator, Checkpoint, CheckpointDirectory, RenderSettings},
+        postprocess::FloatImage,
+        renderers::media::Atmosphere,
+        utilities::math::Vec3,
+    };
+
+    fn settings() -> RenderSettings {
+        RenderSettings {
+            width: 4,
+            height: 2,
+            integrator: "path".to_string(),
+            clay: false,
+            atmosphere: Atmosphere::default(),
+            camera: None,
+        }
+    }
+
+    #[test]
+    fn statistics() {
+        let mut accumulator = Accumulator::new(4, 2);
+        for value in [1.0, 2.0, 3.0, 6.0] {
+            accumulator.add(1, 1, Vec3::from_single(value));
+        }
+        accumulator.add(0, 0, Vec3::from_single(5.0));
+        let mean = accumulator.mean();
+        let variance = accumulator.variance();
+        assert_eq!(mean.get(1, 1), Vec3::from_single(3.0));
+        assert!((variance.get(1, 1).x() - 14.0 / 3.0).abs() < 1e-9);
+        //Single and missing samples have no variance
+        assert_eq!(mean.get(0, 0), Vec3::from_single(5.0));
+        assert_eq!(variance.get(0, 0), Vec3::default());
+        assert_eq!(mean.get(3, 0), Vec3::default());
+    }
+    #[test]
+    fn tiles() {
+        let mut accumulator = Accumulator::new(4, 2);
+        let mut tile = FloatImage::new(2, 2);
+        tile.pixels[3] = Vec3::new(1.0, 2.0, 3.0);
+        accumulator.add_tile(2, 0, &tile);
+        accumulator.add_tile(2, 0, &tile);
+        accumulator.add_tile(0, 0, &tile);
+        assert_eq!(accumulator.region_samples(2, 0, 2, 2), 2);
+        assert_eq!(accumulator.region_samples(0, 0, 4, 2), 1);
+        assert_eq!(accumulator.mean().get(3, 1), Vec3::new(1.0, 2.0, 3.0));
+    }
+    #[test]
+    fn resume() {
+        let mut accumulator = Accumulator::new(4, 2);
+        accumulator.add(2, 1, Vec3::new(0.5, 0.25, 2.0));
+        let checkpoint = Checkpoint {
+            scene: "v2-scene".to_string(),
+            settings: settings(),
+            accumulator,
+        };
+        let decoded = Checkpoint::decode(&checkpoint.encode().unwrap()).unwrap();
+        assert_eq!(decoded, checkpoint);
+        assert!(decoded.resumes("v2-scene", &settings()));
+        assert!(!decoded.resumes("v2-other", &settings()));
+        let clay = RenderSettings {
+            clay: true,
+            ..settings()
+        };
+        assert!(!decoded.resumes("v2-scene", &clay));
+        //Keys tell renders apart
+        assert_eq!(
+            Checkpoint::key("v2-scene", &settings()),
+            Checkpoint::key("v2-scene", &settings())
+        );
+        assert_ne!(
+            Checkpoint::key("v2-scene", &settings()),
+            Checkpoint::key("v2-scene", &clay)
+        );
+        //Frames of a camera path are separate renders of the same scene
+        let frame = RenderSettings {
+            camera: Some(CameraKeyframe {
+                time: 0.5,
+                position: vec3(1.0, 2.0, 3.0),
+                rotation: vec2(0.0, 90.0),
+                fov: 60.0,
+            }),
+            ..settings()
+        };
+        assert!(!decoded.resumes("v2-scene", &frame));
+        assert_ne!(
+            Checkpoint::key("v2-scene", &settings()),
+            Checkpoint::key("v2-scene", &frame)
+        );
+        assert!(Checkpoint::decode(&[1, 2, 3]).is_err());
+    }
+    #[test]
+    fn directory() {
+        let directory =
+            std::env::temp_dir().join(format!("archyrt-checkpoints-{}", std::process::id()));
+        let checkpoints = CheckpointDirectory::new(&directory);
+        let key = Checkpoint::key("v2-scene", &settings());
+        assert!(checkpoints.load(&key).is_none());
+        let mut accumulator = Accumulator::new(4, 2);
+        accumulator.add(3, 0, Vec3::new(1.0, 0.5, 0.25));
+        let checkpoint = Checkpoint {
+            scene: "v2-scene".to_string(),
+            settings: settings(),
+            accumulator,
+        };
+        checkpoints.store(&key, &checkpoint).unwrap();
+        assert_eq!(checkpoints.load(&key), Some(checkpoint.clone()));
+        //Saving again replaces the checkpoint
+        let mut resumed = checkpoint;
+        resumed.accumulator.add(0, 1, Vec3::from_single(2.0));
+        checkpoints.store(&key, &resumed).unwrap();
+        assert_eq!(checkpoints.load(&key), Some(resumed));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use crate::{
+    api::{
+        fragment_collector::FragmentCollector,
+        fragment_render::{FragmentContext, FragmentRender},
+        light_layers::{LayeredFragmentRender, LightGroup},
+    },
+    textures::texture_repo::TextureRepository,
+    utilities::math::Vec3,
+    vector,
+};
+
+/// Collects every light group into a separate image. The layers add up to the beauty image.
+pub struct LayerCollector {}
+
+impl<T: FragmentRender + LayeredFragmentRender> FragmentCollector<T> for LayerCollector {
+    type Output = HashMap<LightGroup, Vec<Vec<Vec3>>>;
+    fn collect(
+        &self,
+        fragment_render: T,
+        texture_repo: &TextureRepository,
+        width: usize,
+        height: usize,
+    ) -> Self::Output {
+        let ctx = FragmentContext {
+            width: width as _,
+            height: height as _,
+            repo: texture_repo,
+        };
+        let mut layers: Self::Output = HashMap::new();
+        for y in 0..height {
+            let v = y as f64 / (ctx.height - 1.0);
+            for x in 0..width {
+                let u = x as f64 / (ctx.width - 1.0);
+                let fragment = fragment_render.render_layers(&ctx, vector!(u, v));
+                for (group, color) in fragment.layers {
+                    let layer = layers
+                        .entry(group)
+                        .or_insert_with(|| vec![vec![Vec3::default(); width]; height]);
+                    layer[y][x] = color;
+                }
+            }
+        }
+        layers
+    }
+}
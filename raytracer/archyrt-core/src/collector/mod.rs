@@ -1,5 +1,6 @@
 pub mod array_collector;
 pub mod image_collector;
+pub mod layer_collector;
 pub mod raw_collector;
 #[cfg(test)]
 mod tests;
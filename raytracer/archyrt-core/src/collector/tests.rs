@@ -83,3 +83,63 @@ mod image_collector {
         assert_eq!(image.get_pixel(3, 1).0[0], 170);
     }
 }
+
+mod layer_collector {
+    use crate::{
+        api::{
+            fragment_collector::FragmentCollector,
+            fragment_render::{FragmentContext, FragmentRender},
+            light_layers::{LayeredFragmentRender, LightGroup, LightLayers},
+        },
+        collector::{array_collector::ArrayCollector, layer_collector::LayerCollector},
+        textures::texture_repo::TextureRepository,
+        utilities::math::{Vec2, Vec3},
+    };
+
+    struct DummyLayeredRenderer {}
+
+    impl FragmentRender for DummyLayeredRenderer {
+        fn render_fragment(&self, ctx: &FragmentContext, pos: Vec2) -> Vec3 {
+            self.render_layers(ctx, pos).beauty()
+        }
+    }
+
+    impl LayeredFragmentRender for DummyLayeredRenderer {
+        fn render_layers(&self, _: &FragmentContext, pos: Vec2) -> LightLayers {
+            let mut layers = LightLayers::new();
+            layers.add(LightGroup::Sky, Vec3::from_single(pos.x()));
+            if pos.y() > 0.5 {
+                layers.add(LightGroup::Emissive, Vec3::from_single(pos.y()));
+            }
+            layers
+        }
+    }
+
+    #[test]
+    fn layers_sum_to_beauty() {
+        let repo = TextureRepository::new();
+        let layers = LayerCollector {}.collect(DummyLayeredRenderer {}, &repo, 4, 4);
+        let beauty = ArrayCollector {}.collect(DummyLayeredRenderer {}, &repo, 4, 4);
+        assert_eq!(layers.len(), 2);
+        for y in 0..4 {
+            for x in 0..4 {
+                let sum = layers
+                    .values()
+                    .fold(Vec3::default(), |a, layer| a + layer[y][x]);
+                assert_eq!(sum, beauty[y][x]);
+            }
+        }
+        assert_eq!(layers[&LightGroup::Emissive][0][3], Vec3::default());
+    }
+    #[test]
+    fn common_groups_inline() {
+        let mut layers = LightLayers::new();
+        layers.add(LightGroup::Sky, Vec3::from_single(1.0));
+        layers.add(LightGroup::Sun, Vec3::from_single(2.0));
+        layers.add(LightGroup::Emissive, Vec3::from_single(3.0));
+        layers.add(LightGroup::Sky, Vec3::from_single(1.0));
+        assert!(!layers.layers.spilled());
+        assert_eq!(layers.get(LightGroup::Sky), Vec3::from_single(2.0));
+        assert_eq!(layers.beauty(), Vec3::from_single(7.0));
+    }
+}
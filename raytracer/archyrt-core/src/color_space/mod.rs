@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    matrix,
+    utilities::math::{Matrix3x3, Vec3},
+    vector,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// CIE xy chromaticity
+pub type Chromaticity = [f64; 2];
+
+pub const D65: Chromaticity = [0.3127, 0.3290];
+/// White point of the ACES color spaces
+pub const D60: Chromaticity = [0.32168, 0.33767];
+
+/// Chromaticities of the red, green and blue primaries and of white
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gamut {
+    pub red: Chromaticity,
+    pub green: Chromaticity,
+    pub blue: Chromaticity,
+    pub white: Chromaticity,
+}
+
+pub const REC709: Gamut = Gamut {
+    red: [0.64, 0.33],
+    green: [0.30, 0.60],
+    blue: [0.15, 0.06],
+    white: D65,
+};
+pub const P3_D65: Gamut = Gamut {
+    red: [0.680, 0.320],
+    green: [0.265, 0.690],
+    blue: [0.150, 0.060],
+    white: D65,
+};
+pub const REC2020: Gamut = Gamut {
+    red: [0.708, 0.292],
+    green: [0.170, 0.797],
+    blue: [0.131, 0.046],
+    white: D65,
+};
+/// Primaries of ACEScg
+pub const AP1: Gamut = Gamut {
+    red: [0.713, 0.293],
+    green: [0.165, 0.830],
+    blue: [0.128, 0.044],
+    white: D60,
+};
+
+/// XYZ of a chromaticity with a luminance of 1
+fn xyz([x, y]: Chromaticity) -> Vec3 {
+    Vec3::new(x / y, 1.0, (1.0 - x - y) / y)
+}
+
+/// Matrix applying `first`, then `second`
+fn then(first: Matrix3x3, second: Matrix3x3) -> Matrix3x3 {
+    first * second
+}
+
+fn diagonal(v: Vec3) -> Matrix3x3 {
+    matrix![
+        vector![v[0], 0.0, 0.0],
+        vector![0.0, v[1], 0.0],
+        vector![0.0, 0.0, v[2]]
+    ]
+}
+
+/// Bradford chromatic adaptation between white points, in XYZ
+fn adaptation(from: Chromaticity, to: Chromaticity) -> Matrix3x3 {
+    if from == to {
+        return Matrix3x3::identity();
+    }
+    let bradford = matrix![
+        vector![0.8951, 0.2664, -0.1614],
+        vector![-0.7502, 1.7135, 0.0367],
+        vector![0.0389, -0.0685, 1.0296]
+    ]
+    .transpose();
+    let scale = (bradford * xyz(to)) / (bradford * xyz(from));
+    then(then(bradford, diagonal(scale)), bradford.inverse().unwrap())
+}
+
+impl Gamut {
+    /// Converts linear RGB of the gamut to CIE XYZ
+    pub fn to_xyz(self) -> Matrix3x3 {
+        let primaries = matrix![xyz(self.red), xyz(self.green), xyz(self.blue)];
+        //Scales the primaries so they add up to white
+        let scale = primaries.cramer(xyz(self.white)).unwrap();
+        matrix![
+            primaries[0] * scale[0],
+            primaries[1] * scale[1],
+            primaries[2] * scale[2]
+        ]
+    }
+}
+
+/// Encoding of linear light in a color space's values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transfer {
+    Linear,
+    /// Piecewise sRGB curve, also used by Display P3
+    Srgb,
+    /// Pure 2.4 gamma of BT.1886 displays, used for Rec.2020
+    Bt1886,
+}
+
+impl Transfer {
+    pub fn decode(self, value: f64) -> f64 {
+        match self {
+            Self::Linear => value,
+            Self::Srgb if value <= 0.04045 => value / 12.92,
+            Self::Srgb => ((value + 0.055) / 1.055).powf(2.4),
+            Self::Bt1886 => value.max(0.0).powf(2.4),
+        }
+    }
+    pub fn encode(self, value: f64) -> f64 {
+        match self {
+            Self::Linear => value,
+            Self::Srgb if value <= 0.0031308 => value * 12.92,
+            Self::Srgb => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+            Self::Bt1886 => value.max(0.0).powf(1.0 / 2.4),
+        }
+    }
+}
+
+/// Color spaces of textures and displays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    /// Linear Rec.709, such as EXR images
+    LinearSrgb,
+    #[serde(rename = "acescg")]
+    AcesCg,
+    DisplayP3,
+    Rec2020,
+}
+
+/// Linear space colors are rendered in, textures are converted into it when they are loaded
+pub const WORKING_SPACE: ColorSpace = ColorSpace::AcesCg;
+
+impl ColorSpace {
+    /// Names accepted by [`ColorSpace::from_name`]
+    pub const NAMES: [&'static str; 5] = ["srgb", "linear_srgb", "acescg", "display_p3", "rec2020"];
+    /// Spaces rendered images can be encoded for
+    pub const DISPLAYS: [ColorSpace; 3] = [Self::Srgb, Self::DisplayP3, Self::Rec2020];
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "srgb" => Some(Self::Srgb),
+            "linear_srgb" => Some(Self::LinearSrgb),
+            "acescg" => Some(Self::AcesCg),
+            "display_p3" => Some(Self::DisplayP3),
+            "rec2020" => Some(Self::Rec2020),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Srgb => "srgb",
+            Self::LinearSrgb => "linear_srgb",
+            Self::AcesCg => "acescg",
+            Self::DisplayP3 => "display_p3",
+            Self::Rec2020 => "rec2020",
+        }
+    }
+    pub fn gamut(self) -> Gamut {
+        match self {
+            Self::Srgb | Self::LinearSrgb => REC709,
+            Self::AcesCg => AP1,
+            Self::DisplayP3 => P3_D65,
+            Self::Rec2020 => REC2020,
+        }
+    }
+    pub fn transfer(self) -> Transfer {
+        match self {
+            Self::LinearSrgb | Self::AcesCg => Transfer::Linear,
+            Self::Srgb | Self::DisplayP3 => Transfer::Srgb,
+            Self::Rec2020 => Transfer::Bt1886,
+        }
+    }
+    /// Linear color of an encoded one
+    pub fn decode(self, color: Vec3) -> Vec3 {
+        let transfer = self.transfer();
+        Vec3::from_array(color.inner.map(|c| transfer.decode(c)))
+    }
+    /// Encoded color of a linear one
+    pub fn encode(self, color: Vec3) -> Vec3 {
+        let transfer = self.transfer();
+        Vec3::from_array(color.inner.map(|c| transfer.encode(c)))
+    }
+}
+
+/// Converts colors between two color spaces
+#[derive(Debug, Clone, Copy)]
+pub struct ColorTransform {
+    pub from: ColorSpace,
+    pub to: ColorSpace,
+    /// Linear RGB of `from` to linear RGB of `to`
+    pub matrix: Matrix3x3,
+}
+
+impl ColorTransform {
+    pub fn new(from: ColorSpace, to: ColorSpace) -> Self {
+        let (source, target) = (from.gamut(), to.gamut());
+        let matrix = if source == target {
+            Matrix3x3::identity()
+        } else {
+            then(
+                then(source.to_xyz(), adaptation(source.white, target.white)),
+                target.to_xyz().inverse().unwrap(),
+            )
+        };
+        Self { from, to, matrix }
+    }
+    /// Converts encoded colors of a space into linear colors of the working space
+    pub fn to_working(from: ColorSpace) -> Self {
+        Self::new(from, WORKING_SPACE)
+    }
+    /// Converts linear colors of the working space into encoded colors of a space
+    pub fn from_working(to: ColorSpace) -> Self {
+        Self::new(WORKING_SPACE, to)
+    }
+    /// Converts between the linear colors of the two spaces
+    pub fn linear(&self, color: Vec3) -> Vec3 {
+        self.matrix * color
+    }
+    /// Converts an encoded color of `from` to an encoded color of `to`
+    pub fn apply(&self, color: Vec3) -> Vec3 {
+        self.to.encode(self.linear(self.from.decode(color)))
+    }
+}
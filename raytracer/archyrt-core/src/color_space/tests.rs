@@ -0,0 +1,85 @@
+mod color_space {
+    use crate::{
+        color_space::{ColorSpace, ColorTransform, Transfer, REC709, WORKING_SPACE},
+        postprocess,
+        tonemapping::OutputTransform,
+        utilities::math::Vec3,
+    };
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-3
+    }
+
+    #[test]
+    fn names() {
+        for name in ColorSpace::NAMES {
+            assert_eq!(ColorSpace::from_name(name).unwrap().name(), name);
+        }
+        assert!(ColorSpace::from_name("adobe_rgb").is_none());
+        let space: ColorSpace = serde_json::from_str("\"acescg\"").unwrap();
+        assert_eq!(space, ColorSpace::AcesCg);
+    }
+    #[test]
+    fn transfer() {
+        for transfer in [Transfer::Linear, Transfer::Srgb, Transfer::Bt1886] {
+            for value in [0.0, 0.002, 0.2, 0.5, 1.0] {
+                assert!((transfer.decode(transfer.encode(value)) - value).abs() < 1e-9);
+            }
+        }
+        assert!((Transfer::Srgb.decode(0.5) - 0.2140).abs() < 1e-4);
+    }
+    #[test]
+    fn luminance() {
+        let y = REC709.to_xyz().transpose()[1];
+        assert!(close(y, Vec3::new(0.2126, 0.7152, 0.0722)));
+        //Weights used by the post-processing effects
+        let y = WORKING_SPACE.gamut().to_xyz().transpose()[1];
+        let color = Vec3::new(0.2, 0.5, 0.9);
+        assert!((postprocess::luminance(color) - y.dot(color)).abs() < 1e-6);
+    }
+    #[test]
+    fn srgb_to_acescg() {
+        let transform = ColorTransform::to_working(ColorSpace::Srgb);
+        //White stays white, the white points are adapted
+        assert!(close(
+            transform.apply(Vec3::from_single(1.0)),
+            Vec3::from_single(1.0)
+        ));
+        assert!(close(
+            transform.apply(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.6131, 0.0702, 0.0206)
+        ));
+    }
+    #[test]
+    fn round_trip() {
+        let color = Vec3::new(0.8, 0.3, 0.1);
+        for space in ColorSpace::DISPLAYS {
+            let there = ColorTransform::from_working(space);
+            let back = ColorTransform::to_working(space);
+            assert!(close(back.apply(there.apply(color)), color));
+        }
+    }
+    #[test]
+    fn wider_gamuts() {
+        //Saturated sRGB red is less saturated in wider gamuts
+        let red = ColorTransform::new(ColorSpace::LinearSrgb, ColorSpace::Rec2020)
+            .linear(Vec3::new(1.0, 0.0, 0.0));
+        assert!(red.x() < 1.0 && red.y() > 0.0 && red.z() > 0.0);
+        let identity = ColorTransform::new(ColorSpace::Srgb, ColorSpace::LinearSrgb);
+        assert!(close(
+            identity.linear(Vec3::new(0.2, 0.4, 0.6)),
+            Vec3::new(0.2, 0.4, 0.6)
+        ));
+    }
+    #[test]
+    fn output() {
+        for display in ColorSpace::DISPLAYS {
+            let output = OutputTransform::new(display);
+            assert_eq!(output.apply(Vec3::default()), Vec3::default());
+            //Grey stays grey and bright colors approach white
+            let grey = output.apply(Vec3::from_single(0.1));
+            assert!((grey.x() - grey.y()).abs() < 1e-6 && (grey.y() - grey.z()).abs() < 1e-6);
+            assert!(output.apply(Vec3::from_single(1000.0)).x() > 0.99);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{
+    postprocess::{bloom::blur, FloatImage},
+    renderers::heatmap::false_color,
+    utilities::math::Vec3,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Differences between two images of the same size, whose colors are display encoded between 0 and 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    pub rmse: f64,
+    /// Peak signal to noise ratio in decibels, infinite for equal images
+    pub psnr: f64,
+    pub ssim: f64,
+    /// Mean of the [`flip`] errors
+    pub flip: f64,
+}
+
+impl Comparison {
+    pub fn new(a: &FloatImage, b: &FloatImage) -> Self {
+        let errors = flip(a, b);
+        Self {
+            rmse: rmse(a, b),
+            psnr: psnr(a, b, 1.0),
+            ssim: ssim(a, b),
+            flip: errors.pixels.iter().map(|error| error.x()).sum::<f64>()
+                / errors.pixels.len().max(1) as f64,
+        }
+    }
+}
+
+fn assert_same_size(a: &FloatImage, b: &FloatImage) {
+    assert!(
+        a.width == b.width && a.height == b.height,
+        "Images of different sizes can't be compared"
+    );
+}
+
+/// Root mean square difference of the channels
+pub fn rmse(a: &FloatImage, b: &FloatImage) -> f64 {
+    assert_same_size(a, b);
+    let sum: f64 = a
+        .pixels
+        .iter()
+        .zip(&b.pixels)
+        .map(|(a, b)| {
+            let difference = *a - *b;
+            difference.dot(difference)
+        })
+        .sum();
+    (sum / (a.pixels.len() * 3).max(1) as f64).sqrt()
+}
+
+/// Peak signal to noise ratio in decibels, for channels between 0 and `peak`
+pub fn psnr(a: &FloatImage, b: &FloatImage, peak: f64) -> f64 {
+    let rmse = rmse(a, b);
+    if rmse == 0.0 {
+        return f64::INFINITY;
+    }
+    20.0 * (peak / rmse).log10()
+}
+
+/// Mean structural similarity of the channels, 1 for equal images.
+/// Statistics are gathered in gaussian windows with a standard deviation of 1.5 pixels.
+pub fn ssim(a: &FloatImage, b: &FloatImage) -> f64 {
+    assert_same_size(a, b);
+    const SIGMA: f64 = 1.5;
+    const C1: f64 = 0.01 * 0.01;
+    const C2: f64 = 0.03 * 0.03;
+    let product = |x: &FloatImage, y: &FloatImage| FloatImage {
+        width: x.width,
+        height: x.height,
+        pixels: x
+            .pixels
+            .iter()
+            .zip(&y.pixels)
+            .map(|(x, y)| *x * *y)
+            .collect(),
+    };
+    let mean_a = blur(a, SIGMA);
+    let mean_b = blur(b, SIGMA);
+    let mean_aa = blur(&product(a, a), SIGMA);
+    let mean_bb = blur(&product(b, b), SIGMA);
+    let mean_ab = blur(&product(a, b), SIGMA);
+    let mut sum = 0.0;
+    for i in 0..a.pixels.len() {
+        let (mu_a, mu_b) = (mean_a.pixels[i], mean_b.pixels[i]);
+        for c in 0..3 {
+            let (mu_a, mu_b) = (mu_a[c], mu_b[c]);
+            let variance_a = mean_aa.pixels[i][c] - mu_a * mu_a;
+            let variance_b = mean_bb.pixels[i][c] - mu_b * mu_b;
+            let covariance = mean_ab.pixels[i][c] - mu_a * mu_b;
+            sum += ((2.0 * mu_a * mu_b + C1) * (2.0 * covariance + C2))
+                / ((mu_a * mu_a + mu_b * mu_b + C1) * (variance_a + variance_b + C2));
+        }
+    }
+    sum / (a.pixels.len() * 3).max(1) as f64
+}
+
+/// Length of the luminance gradient of each pixel
+fn edges(image: &FloatImage) -> Vec<f64> {
+    let luma = |x: isize, y: isize| {
+        let x = x.clamp(0, image.width as isize - 1) as usize;
+        let y = y.clamp(0, image.height as isize - 1) as usize;
+        image.get(x, y).dot(Vec3::new(0.2126, 0.7152, 0.0722))
+    };
+    (0..image.width * image.height)
+        .map(|i| {
+            let (x, y) = ((i % image.width) as isize, (i / image.width) as isize);
+            let dx = (luma(x + 1, y) - luma(x - 1, y)) * 0.5;
+            let dy = (luma(x, y + 1) - luma(x, y - 1)) * 0.5;
+            (dx * dx + dy * dy).sqrt()
+        })
+        .collect()
+}
+
+/// Perceived error of each pixel between 0 and 1, in every channel of the returned image.
+/// Like FLIP, the difference of the slightly blurred colors is raised to a power lowered by differences of edges,
+/// so errors along edges stand out more than noise in flat regions.
+pub fn flip(a: &FloatImage, b: &FloatImage) -> FloatImage {
+    assert_same_size(a, b);
+    let (blurred_a, blurred_b) = (blur(a, 1.0), blur(b, 1.0));
+    let (edges_a, edges_b) = (edges(&blurred_a), edges(&blurred_b));
+    let pixels = (0..a.pixels.len())
+        .map(|i| {
+            let color =
+                ((blurred_a.pixels[i] - blurred_b.pixels[i]).length() / 3f64.sqrt()).min(1.0);
+            let feature = ((edges_a[i] - edges_b[i]).abs() * 2.0).min(1.0);
+            Vec3::from_single(color.powf(1.0 - feature))
+        })
+        .collect();
+    FloatImage {
+        width: a.width,
+        height: a.height,
+        pixels,
+    }
+}
+
+/// Colors errors between 0 and 1 from black through blue, green and yellow to red, see [`false_color`]
+pub fn heatmap(errors: &FloatImage) -> FloatImage {
+    FloatImage {
+        width: errors.width,
+        height: errors.height,
+        pixels: errors
+            .pixels
+            .iter()
+            .map(|error| false_color(error.x()))
+            .collect(),
+    }
+}
@@ -0,0 +1,241 @@
+mod metrics {
+    use crate::{
+        image_diff::{flip, heatmap, psnr, rmse, ssim, Comparison},
+        postprocess::FloatImage,
+        utilities::math::Vec3,
+    };
+
+    /// Checkerboard of 4 pixel squares
+    fn checkerboard(width: usize, height: usize) -> FloatImage {
+        FloatImage::new(width, height).map(|pos| {
+            let square = (pos.x() as usize / 4 + pos.y() as usize / 4) % 2;
+            Vec3::from_single(0.2 + 0.6 * square as f64)
+        })
+    }
+
+    #[test]
+    fn equal() {
+        let image = checkerboard(16, 16);
+        let comparison = Comparison::new(&image, &image);
+        assert_eq!(comparison.rmse, 0.0);
+        assert_eq!(comparison.psnr, f64::INFINITY);
+        assert!((comparison.ssim - 1.0).abs() < 1e-9);
+        assert_eq!(comparison.flip, 0.0);
+    }
+    #[test]
+    fn offset() {
+        let image = checkerboard(16, 16);
+        let brighter = image.map(|pos| image.sample(pos) + Vec3::from_single(0.1));
+        assert!((rmse(&image, &brighter) - 0.1).abs() < 1e-9);
+        assert!((psnr(&image, &brighter, 1.0) - 20.0).abs() < 1e-6);
+        assert!(
+            (psnr(&image, &brighter, 2.0) - psnr(&image, &brighter, 1.0) - 6.0206).abs() < 1e-3
+        );
+        //Brightness changes keep the structure
+        assert!(ssim(&image, &brighter) > 0.9);
+    }
+    #[test]
+    fn structure() {
+        let image = checkerboard(16, 16);
+        let flat = image.map(|_| Vec3::from_single(0.5));
+        let shifted = image.map(|pos| image.get((pos.x() as usize + 2) % 16, pos.y() as usize));
+        assert!(ssim(&image, &flat) < 0.1);
+        assert!(ssim(&image, &shifted) < ssim(&image, &image.map(|pos| image.sample(pos) * 0.9)));
+        //Errors are found where the images differ
+        let mut spot = image.clone();
+        spot.pixels[8 * 16 + 8] = Vec3::from_single(1.0);
+        let errors = flip(&image, &spot);
+        assert!(errors
+            .pixels
+            .iter()
+            .all(|error| (0.0..=1.0).contains(&error.x())));
+        assert!(errors.get(8, 8).x() > 0.0);
+        assert_eq!(errors.get(0, 0).x(), 0.0);
+        assert!(errors.get(8, 8).x() > errors.get(10, 8).x());
+    }
+    #[test]
+    fn heatmap_colors() {
+        let errors = FloatImage::from_raw(2, 1, &[0.0, 0.0, 0.0, 1.0, 1.0, 1.0]);
+        let colors = heatmap(&errors);
+        assert_eq!(colors.get(0, 0), Vec3::default());
+        assert_eq!(colors.get(1, 0), Vec3::new(1.0, 0.0, 0.0));
+    }
+}
+
+mod golden {
+    use std::path::{Path, PathBuf};
+
+    use image::{Rgb, RgbImage};
+
+    use crate::{
+        api::fragment_collector::FragmentCollector,
+        cameras::jitter::JitterCamera,
+        collector::raw_collector::RawCollector,
+        color_space::{ColorSpace, ColorTransform},
+        image_diff::{flip, heatmap, Comparison},
+        intersectables::area_light::AreaLights,
+        loaders::{
+            ascn::{amdl_textures::AMDLTextureType, ASCNLoader},
+            Loader,
+        },
+        postprocess::FloatImage,
+        renderers::{
+            integrator::{IntegratorRenderer, NamedIntegrator, Scene},
+            media::{Atmosphere, Sun},
+            sampling::{SamplingRenderer, SeededRenderer},
+        },
+        textures::{texture::Texture, texture_repo::TextureRepository, TextureID},
+        utilities::math::Vec3,
+    };
+
+    const SEED: u64 = 1;
+    const WIDTH: usize = 48;
+    const HEIGHT: usize = 36;
+    const SAMPLES: usize = 8;
+    /// Scenes of the golden directory and the integrators rendering them
+    const CASES: [(&str, &str); 5] = [
+        ("room", "path"),
+        ("room", "ao"),
+        ("room", "whitted"),
+        ("room", "bdpt"),
+        ("pillars", "path"),
+    ];
+    /// Flat colors of the textures of the scenes, by their IDs starting from 1
+    const PALETTE: [[f64; 3]; 4] = [
+        [0.8, 0.8, 0.8],
+        [0.7, 0.1, 0.1],
+        [0.1, 0.6, 0.1],
+        [0.2, 0.3, 0.7],
+    ];
+    /// Color of the uniform skybox, the only light of the bidirectional path tracer besides emitters
+    const SKY: [f64; 3] = [0.6, 0.7, 0.9];
+    /// Tolerances of renders compared to their golden images
+    const MIN_PSNR: f64 = 40.0;
+    const MIN_SSIM: f64 = 0.98;
+    const MAX_FLIP: f64 = 0.02;
+
+    fn golden_path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("golden")
+            .join(name)
+    }
+
+    /// Renders a scene of the golden directory, display encoded and quantized like its golden image
+    fn render(scene: &str, integrator: &str) -> FloatImage {
+        let mut loader = ASCNLoader::from_path(golden_path(&format!("{}.ascn", scene))).unwrap();
+        let mut repo = TextureRepository::new();
+        for (id, color) in PALETTE.iter().enumerate() {
+            let mut texture = Texture::new(1, 1);
+            texture.set(0, Vec3::new(color[0], color[1], color[2]));
+            repo.insert(AMDLTextureType::diffuse(id as u32 + 1), texture);
+        }
+        let skybox = TextureID::new(&"skybox");
+        let mut sky = Texture::new(1, 1);
+        sky.set(0, Vec3::new(SKY[0], SKY[1], SKY[2]));
+        repo.insert(skybox, sky);
+        loader.load_materials(&repo);
+        let scene = Scene {
+            object: loader.get_triangles().clone(),
+            skybox: Some(skybox),
+            portals: loader.get_portals().clone(),
+            lights: AreaLights::default(),
+            anisotropy: 1,
+            media: Atmosphere::default().media(loader.get_volumes()),
+            sun: Some(Sun::default()),
+        };
+        let integrator = NamedIntegrator::from_name(integrator).unwrap();
+        let camera = JitterCamera::new(loader.get_camera(), WIDTH, HEIGHT);
+        let renderer = SeededRenderer {
+            inner: SamplingRenderer {
+                inner: IntegratorRenderer {
+                    camera: &camera,
+                    scene,
+                    integrator,
+                },
+                samples: SAMPLES,
+            },
+            seed: SEED,
+        };
+        let raw = RawCollector {}.collect(renderer, &repo, WIDTH, HEIGHT);
+        let transform = ColorTransform::from_working(ColorSpace::Srgb);
+        let image = FloatImage::from_raw(WIDTH, HEIGHT, &raw);
+        FloatImage {
+            pixels: image
+                .pixels
+                .iter()
+                .map(|color| {
+                    let color = transform.apply(*color).max(Vec3::default());
+                    let [r, g, b] = color.inner.map(|c| (c.min(1.0) * 255.0).round() / 255.0);
+                    Vec3::new(r, g, b)
+                })
+                .collect(),
+            ..image
+        }
+    }
+
+    fn load(path: &Path) -> FloatImage {
+        let png = image::open(path).unwrap().to_rgb8();
+        let (width, height) = (png.width() as usize, png.height() as usize);
+        let raw: Vec<f32> = png
+            .pixels()
+            .flat_map(|pixel| pixel.0.map(|c| c as f32 / 255.0))
+            .collect();
+        FloatImage::from_raw(width, height, &raw)
+    }
+
+    fn save(image: &FloatImage, path: &Path) {
+        let mut png = RgbImage::new(image.width as u32, image.height as u32);
+        for (x, y, pixel) in png.enumerate_pixels_mut() {
+            let color = image.get(x as usize, y as usize).inner;
+            *pixel = Rgb(color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+        }
+        png.save(path).unwrap();
+    }
+
+    #[test]
+    fn deterministic() {
+        let (scene, integrator) = CASES[0];
+        assert_eq!(render(scene, integrator), render(scene, integrator));
+    }
+    /// Set `UPDATE_GOLDEN` to replace the golden images with the current renders
+    #[test]
+    fn regression() {
+        let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+        let mut failures = Vec::new();
+        for (scene, integrator) in CASES {
+            let name = format!("{}_{}", scene, integrator);
+            let image = render(scene, integrator);
+            let path = golden_path(&format!("{}.png", name));
+            if update {
+                save(&image, &path);
+                continue;
+            }
+            let golden = load(&path);
+            let comparison = Comparison::new(&image, &golden);
+            if comparison.psnr < MIN_PSNR
+                || comparison.ssim < MIN_SSIM
+                || comparison.flip > MAX_FLIP
+            {
+                //Keep the render and where it differs for inspection
+                let directory = std::env::temp_dir().join("archyrt-golden");
+                std::fs::create_dir_all(&directory).unwrap();
+                save(&image, &directory.join(format!("{}.png", name)));
+                save(
+                    &heatmap(&flip(&image, &golden)),
+                    &directory.join(format!("{}_diff.png", name)),
+                );
+                failures.push(format!(
+                    "{}: {:?}, see {}",
+                    name,
+                    comparison,
+                    directory.display()
+                ));
+            }
+        }
+        assert!(
+            failures.is_empty(),
+            "Renders differ from their golden images\n{}",
+            failures.join("\n")
+        );
+    }
+}
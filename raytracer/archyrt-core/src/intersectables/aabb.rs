@@ -1,3 +1,7 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
 use crate::utilities::math::Axis3;
 use crate::vector;
 use crate::{
@@ -8,7 +12,7 @@ use crate::{
     },
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct AABB {
     pub min: Vec3,
     pub max: Vec3,
@@ -30,6 +34,37 @@ impl AABB {
     pub fn max_axis(self) -> Axis3 {
         (self.max - self.min).max_axis()
     }
+    pub fn surface_area(self) -> f64 {
+        let d = self.max - self.min;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+    pub fn centroid(self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+    pub fn corners(self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            vector![a.x(), a.y(), a.z()],
+            vector![b.x(), a.y(), a.z()],
+            vector![a.x(), b.y(), a.z()],
+            vector![b.x(), b.y(), a.z()],
+            vector![a.x(), a.y(), b.z()],
+            vector![b.x(), a.y(), b.z()],
+            vector![a.x(), b.y(), b.z()],
+            vector![b.x(), b.y(), b.z()],
+        ]
+    }
+}
+
+/// Objects with known bounds, which can be put in a [`TopLevelBVH`](super::instances::TopLevelBVH)
+pub trait Bounded {
+    fn bounds(&self) -> AABB;
+}
+
+impl<T: Bounded> Bounded for Arc<T> {
+    fn bounds(&self) -> AABB {
+        self.as_ref().bounds()
+    }
 }
 
 impl AABB {
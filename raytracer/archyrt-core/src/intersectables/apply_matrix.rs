@@ -1,5 +1,7 @@
 use crate::utilities::{ray::{Intersectable, Ray, Intersection}, math::Matrix3x3};
 
+use super::aabb::{Bounded, AABB};
+
 pub struct ApplyMatrix<T: Intersectable>{
     pub inner: T,
     pub matrix: Matrix3x3,
@@ -18,4 +20,14 @@ impl<T: Intersectable> Intersectable for ApplyMatrix<T>{
         Some(result.build())
         
     }
-}
\ No newline at end of file
+}
+
+impl<T: Intersectable + Bounded> Bounded for ApplyMatrix<T>{
+    fn bounds(&self) -> AABB {
+        //Rays are transformed by the matrix, so the object is transformed by its inverse
+        let corners = self.inner.bounds().corners().map(|corner| self.inverse_matrix*corner);
+        corners.iter().fold(AABB::new(corners[0], corners[0]), |aabb, corner| {
+            aabb.union(AABB::new(*corner, *corner))
+        })
+    }
+}
@@ -0,0 +1,125 @@
+use rand::Rng;
+
+use crate::{matrix, utilities::math::Vec3};
+
+use super::triangle::{Triangle, TriangleColor};
+
+/// Emitting side of an emissive triangle
+#[derive(Debug, Clone)]
+pub struct AreaLight {
+    pub triangle: Triangle,
+    /// Whether light leaves through the back face instead of the front
+    pub back: bool,
+}
+
+/// Point sampled on an area light
+pub struct LightSample {
+    pub pos: Vec3,
+    /// Normal of the emitting side
+    pub normal: Vec3,
+    pub color: TriangleColor,
+}
+
+impl AreaLight {
+    /// Uniformly samples a point of the light, `None` if it lands on a cut out texel
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<LightSample> {
+        let r1: f64 = rng.gen();
+        let r2: f64 = rng.gen();
+        let su = r1.sqrt();
+        let (u, v) = (1.0 - su, r2 * su);
+        //Weights of b, c and a, like the barycentric coordinates of hits
+        self.point(Vec3::new(u, v, 1.0 - u - v))
+    }
+    /// Centroid of the light, `None` if it is cut out
+    pub fn center(&self) -> Option<LightSample> {
+        self.point(Vec3::from_single(1.0 / 3.0))
+    }
+    fn point(&self, barycentric: Vec3) -> Option<LightSample> {
+        let t = &self.triangle;
+        let (u, v) = (barycentric[0], barycentric[1]);
+        let coords = t.uv[1] * u + t.uv[2] * v + t.uv[0] * barycentric[2];
+        if let Some(cutout) = &t.cutout {
+            if !cutout.is_opaque(coords) {
+                return None;
+            }
+        }
+        let pos = t.b * u + t.c * v + t.a * barycentric[2];
+        let normal = (matrix![t.bn, t.cn, t.an] * barycentric).normalized();
+        let normal = if self.back { -normal } else { normal };
+        Some(LightSample {
+            pos,
+            normal,
+            color: t.color(barycentric, normal),
+        })
+    }
+}
+
+/// Emissive triangles of a scene, which light paths are started from
+#[derive(Debug, Clone, Default)]
+pub struct AreaLights {
+    pub lights: Vec<AreaLight>,
+    /// Running total of the areas of the lights
+    cdf: Vec<f64>,
+}
+
+impl AreaLights {
+    /// Lights of the emissive triangles, double sided triangles emit from both of their faces
+    pub fn new<'a>(triangles: impl IntoIterator<Item = &'a Triangle>) -> Self {
+        let mut lights = Vec::new();
+        for triangle in emitters(triangles) {
+            if triangle.double_sided {
+                lights.push(AreaLight {
+                    triangle: triangle.clone(),
+                    back: true,
+                });
+            }
+            lights.push(AreaLight {
+                triangle,
+                back: false,
+            });
+        }
+        let mut total = 0.0;
+        let cdf = lights
+            .iter()
+            .map(|light| {
+                total += light.triangle.area();
+                total
+            })
+            .collect();
+        Self { lights, cdf }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+    pub fn area(&self) -> f64 {
+        self.cdf.last().copied().unwrap_or(0.0)
+    }
+    /// Probability density of sampling any point of the lights, per unit area
+    pub fn pdf(&self) -> f64 {
+        let area = self.area();
+        if area > 0.0 {
+            1.0 / area
+        } else {
+            0.0
+        }
+    }
+    /// Picks a light with a probability proportional to its area
+    pub fn choose<R: Rng>(&self, rng: &mut R) -> Option<&AreaLight> {
+        let area = self.area();
+        if area <= 0.0 {
+            return None;
+        }
+        let target = rng.gen::<f64>() * area;
+        let index = self.cdf.partition_point(|total| *total <= target);
+        self.lights.get(index.min(self.lights.len() - 1))
+    }
+}
+
+/// Copies of the triangles which emit light
+pub fn emitters<'a>(triangles: impl IntoIterator<Item = &'a Triangle>) -> Vec<Triangle> {
+    triangles
+        .into_iter()
+        .filter(|triangle| triangle.material.is_emissive())
+        .cloned()
+        .collect()
+}
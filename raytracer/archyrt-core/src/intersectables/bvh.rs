@@ -1,60 +1,296 @@
-use crate::intersectables::aabb::AABB;
+use crate::intersectables::aabb::{Bounded, AABB};
 use crate::intersectables::triangle::Triangle;
 
+use crate::textures::texture_repo::TextureRepository;
 use crate::utilities::math::Vec3;
 use crate::utilities::ray::{Intersectable, Intersection, Ray};
+use anyhow::Result;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "stats")]
+use std::cell::Cell;
 use std::cmp::Ordering;
 
 use super::triangle::TriangleColor;
 
+#[derive(Serialize, Deserialize)]
 pub enum BVH {
     Branch {
         left: Box<BVH>,
         right: Box<BVH>,
         aabb: AABB,
     },
-    Leaf(Triangle),
+    //Boxed, so branches aren't as large as triangles
+    Leaf(Box<Triangle>),
 }
 
-fn bounding(triangles: &[Triangle]) -> AABB {
-    if triangles.is_empty() {
-        return AABB::new(Vec3::default(), Vec3::default());
+/// Number of buckets candidate splits are evaluated between
+const BINS: usize = 16;
+/// Nodes with fewer triangles are built on a single thread
+const PARALLEL_THRESHOLD: usize = 4096;
+
+/// Work done tracing rays on the current thread, gathered by the traversals of the BVHs.
+/// It is only counted with the `stats` feature, so renders without it don't pay for the counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TraversalStats {
+    /// Bounding volume nodes visited
+    pub nodes: usize,
+    /// Triangles tested for intersection
+    pub triangles: usize,
+    /// Deepest bounce which hit a surface, recorded by the integrators
+    pub depth: usize,
+}
+
+#[cfg(feature = "stats")]
+thread_local! {
+    static STATS: Cell<TraversalStats> = Cell::new(TraversalStats::default());
+}
+
+#[cfg(feature = "stats")]
+fn update(f: impl FnOnce(&mut TraversalStats)) {
+    STATS.with(|stats| {
+        let mut value = stats.get();
+        f(&mut value);
+        stats.set(value);
+    });
+}
+
+#[cfg(not(feature = "stats"))]
+#[inline(always)]
+fn update(_: impl FnOnce(&mut TraversalStats)) {}
+
+#[inline(always)]
+pub(crate) fn count_nodes(nodes: usize) {
+    update(|stats| stats.nodes += nodes);
+}
+
+#[inline(always)]
+pub(crate) fn count_triangles(triangles: usize) {
+    update(|stats| stats.triangles += triangles);
+}
+
+#[inline(always)]
+pub(crate) fn record_depth(depth: usize) {
+    update(|stats| stats.depth = stats.depth.max(depth));
+}
+
+/// Runs a closure and returns the work its traversals did, measurements can be nested
+#[cfg(feature = "stats")]
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, TraversalStats) {
+    let outer = STATS.with(|stats| stats.replace(TraversalStats::default()));
+    let result = f();
+    let inner = STATS.with(|stats| {
+        let inner = stats.get();
+        stats.set(TraversalStats {
+            nodes: outer.nodes + inner.nodes,
+            triangles: outer.triangles + inner.triangles,
+            depth: outer.depth.max(inner.depth),
+        });
+        inner
+    });
+    (result, inner)
+}
+
+/// Triangle referenced by index while building
+#[derive(Clone, Copy)]
+struct Primitive {
+    bounds: AABB,
+    centroid: Vec3,
+    index: usize,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Bin {
+    bounds: Option<AABB>,
+    count: usize,
+}
+
+impl Bin {
+    fn add(&mut self, bounds: AABB) {
+        self.bounds = Some(match self.bounds {
+            Some(b) => b.union(bounds),
+            None => bounds,
+        });
+        self.count += 1;
+    }
+    fn merge(self, other: Bin) -> Bin {
+        let bounds = match (self.bounds, other.bounds) {
+            (Some(a), Some(b)) => Some(a.union(b)),
+            (a, b) => a.or(b),
+        };
+        Bin {
+            bounds,
+            count: self.count + other.count,
+        }
     }
-    let mut bounds = triangles[0].bounds();
-    for triangle in triangles {
-        bounds = bounds.union(triangle.bounds());
+    fn cost(self) -> f64 {
+        self.bounds.map_or(0.0, |b| b.surface_area()) * self.count as f64
+    }
+}
+
+fn bounding(primitives: &[Primitive]) -> (AABB, AABB) {
+    let first = primitives[0];
+    let init = (first.bounds, AABB::new(first.centroid, first.centroid));
+    let merge = |(a, ac): (AABB, AABB), (b, bc): (AABB, AABB)| (a.union(b), ac.union(bc));
+    let single = |p: &Primitive| (p.bounds, AABB::new(p.centroid, p.centroid));
+    if primitives.len() > PARALLEL_THRESHOLD {
+        primitives.par_iter().map(single).reduce(|| init, merge)
+    } else {
+        primitives.iter().map(single).fold(init, merge)
+    }
+}
+
+/// Splits the primitives in place, returns the size of the first part
+fn split(primitives: &mut [Primitive], centroids: AABB) -> usize {
+    let axis = centroids.max_axis();
+    let min = centroids.min.get(axis);
+    let extent = centroids.max.get(axis) - min;
+    let bin_of = |p: &Primitive| {
+        if extent <= 0.0 {
+            0
+        } else {
+            (((p.centroid.get(axis) - min) / extent * BINS as f64) as usize).min(BINS - 1)
+        }
+    };
+    let fill = |mut bins: [Bin; BINS], p: &Primitive| {
+        bins[bin_of(p)].add(p.bounds);
+        bins
+    };
+    let merge = |a: [Bin; BINS], b: [Bin; BINS]| {
+        let mut bins = a;
+        for (bin, other) in bins.iter_mut().zip(b) {
+            *bin = bin.merge(other);
+        }
+        bins
+    };
+    let bins = if primitives.len() > PARALLEL_THRESHOLD {
+        primitives
+            .par_iter()
+            .fold(|| [Bin::default(); BINS], fill)
+            .reduce(|| [Bin::default(); BINS], merge)
+    } else {
+        primitives.iter().fold([Bin::default(); BINS], fill)
+    };
+    //Surface area heuristic, splitting after the bin with the lowest cost
+    let mut best = (f64::INFINITY, 0);
+    for k in 1..BINS {
+        let left = bins[..k].iter().fold(Bin::default(), |a, b| a.merge(*b));
+        let right = bins[k..].iter().fold(Bin::default(), |a, b| a.merge(*b));
+        let cost = left.cost() + right.cost();
+        if left.count > 0 && right.count > 0 && cost < best.0 {
+            best = (cost, k);
+        }
+    }
+    if best.1 == 0 {
+        //Every centroid fell into the same bin, fall back to a median split
+        let index = primitives.len() / 2;
+        primitives.select_nth_unstable_by(index, |a, b| {
+            a.centroid
+                .get(axis)
+                .partial_cmp(&b.centroid.get(axis))
+                .unwrap_or(Ordering::Equal)
+        });
+        return index;
+    }
+    let mut mid = 0;
+    for i in 0..primitives.len() {
+        if bin_of(&primitives[i]) < best.1 {
+            primitives.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+fn build(triangles: &[Triangle], primitives: &mut [Primitive]) -> BVH {
+    if primitives.len() == 1 {
+        return BVH::Leaf(Box::new(triangles[primitives[0].index].clone()));
+    }
+    let (bounds, centroids) = bounding(primitives);
+    let mid = split(primitives, centroids);
+    let (a, b) = primitives.split_at_mut(mid);
+    let (left, right) = if a.len() + b.len() > PARALLEL_THRESHOLD {
+        rayon::join(|| build(triangles, a), || build(triangles, b))
+    } else {
+        (build(triangles, a), build(triangles, b))
+    };
+    BVH::Branch {
+        left: Box::new(left),
+        right: Box::new(right),
+        aabb: bounds,
     }
-    bounds
 }
 
 impl BVH {
+    /// Builds the hierarchy with binned surface area heuristic splits, in parallel for large inputs
     pub fn from_triangles(triangles: &[Triangle]) -> Option<Self> {
         if triangles.is_empty() {
             return None;
         }
-        if triangles.len() == 1 {
-            let triangle = triangles.iter().next().unwrap().clone();
-            return Some(BVH::Leaf(triangle));
-        }
-        let bounds = bounding(triangles);
-        let maxis = bounds.max_axis();
-        let mut along_maxis: Vec<_> = triangles
-            .iter()
-            .map(|t| (t.centroid().get(maxis), t.clone()))
+        let mut primitives: Vec<Primitive> = triangles
+            .par_iter()
+            .enumerate()
+            .map(|(index, triangle)| Primitive {
+                bounds: triangle.bounds(),
+                centroid: triangle.centroid(),
+                index,
+            })
             .collect();
-        let index = along_maxis.len() / 2;
-        along_maxis.select_nth_unstable_by(index, |a, b| {
-            a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal)
-        });
-        let along_maxis: Vec<_> = along_maxis.into_iter().map(|(_, t)| t).collect();
-        let (a, b) = along_maxis.split_at(index);
-        let a = BVH::from_triangles(&a.to_vec())?;
-        let b = BVH::from_triangles(&b.to_vec())?;
-        Some(BVH::Branch {
-            left: Box::new(a),
-            right: Box::new(b),
-            aabb: bounds,
-        })
+        Some(build(triangles, &mut primitives))
+    }
+    /// Encodes the built hierarchy, so it can be cached instead of being rebuilt
+    ///
+    /// Cutouts are not encoded, materials have to be loaded again after decoding
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(data)?)
+    }
+    /// Triangles in the leaves
+    pub fn triangles(&self) -> Vec<&Triangle> {
+        let mut triangles = Vec::new();
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            match node {
+                BVH::Branch { left, right, .. } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                BVH::Leaf(triangle) => triangles.push(&**triangle),
+            }
+        }
+        triangles
+    }
+    /// Triangles in the leaves, to change their materials
+    pub fn triangles_mut(&mut self) -> Vec<&mut Triangle> {
+        let mut triangles = Vec::new();
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            match node {
+                BVH::Branch { left, right, .. } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                BVH::Leaf(triangle) => triangles.push(&mut **triangle),
+            }
+        }
+        triangles
+    }
+    /// Applies the cutouts and sidedness of their materials to the triangles
+    pub fn load_materials(&mut self, repo: &TextureRepository) {
+        for triangle in self.triangles_mut() {
+            triangle.load_material(repo);
+        }
+    }
+}
+
+impl Bounded for BVH {
+    fn bounds(&self) -> AABB {
+        match self {
+            BVH::Branch { aabb, .. } => *aabb,
+            BVH::Leaf(triangle) => triangle.bounds(),
+        }
     }
 }
 
@@ -62,8 +298,12 @@ impl Intersectable for BVH {
     type C = TriangleColor;
     fn intersect(&self, ray: Ray) -> Option<Intersection<Self::C>> {
         match self {
-            BVH::Leaf(triangle) => triangle.intersect(ray),
+            BVH::Leaf(triangle) => {
+                count_triangles(1);
+                triangle.intersect(ray)
+            }
             BVH::Branch { left, right, aabb } => {
+                count_nodes(1);
                 aabb.intersect(ray)?;
                 let a = left.intersect(ray);
                 let b = right.intersect(ray);
@@ -0,0 +1,117 @@
+use crate::{
+    renderers::path_tracer::Material,
+    textures::{
+        color_provider::{ColorProvider, RayCone, SurfaceProperties},
+        texture_repo::TextureRepository,
+        TextureID,
+    },
+    utilities::{
+        math::Vec3,
+        ray::{Intersectable, Intersection, IntersectionBuilder, Ray},
+    },
+};
+
+use super::aabb::{Bounded, AABB};
+
+/// Light grey of clay renders
+pub const CLAY_COLOR: Vec3 = Vec3 {
+    inner: [0.8, 0.8, 0.8],
+};
+
+/// Replaces the textures of every surface with a single rough color, for "white model" renders.
+///
+/// Emissive surfaces keep their colors and the sky is unchanged, so the lighting stays the same.
+pub struct Clay<T: Intersectable> {
+    pub inner: T,
+    pub color: Vec3,
+}
+
+impl<T: Intersectable> Clay<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            color: CLAY_COLOR,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ClayColor<C: ColorProvider> {
+    pub inner: C,
+    pub color: Vec3,
+}
+
+impl<C: ColorProvider> ClayColor<C> {
+    fn is_emissive(&self) -> bool {
+        matches!(self.inner.get_material(), Material::Emissive { .. })
+    }
+}
+
+impl<C: ColorProvider> ColorProvider for ClayColor<C> {
+    fn get_color(&self, repo: &TextureRepository) -> Vec3 {
+        if self.is_emissive() {
+            self.inner.get_color(repo)
+        } else {
+            self.color
+        }
+    }
+
+    fn get_material(&self) -> Material {
+        self.inner.get_material()
+    }
+
+    //Emissive textures are sampled through these
+    fn sample(&self, repo: &TextureRepository, id: TextureID) -> Vec3 {
+        self.inner.sample(repo, id)
+    }
+
+    fn get_color_filtered(&self, repo: &TextureRepository, cone: RayCone) -> Vec3 {
+        if self.is_emissive() {
+            self.inner.get_color_filtered(repo, cone)
+        } else {
+            self.color
+        }
+    }
+
+    fn sample_filtered(&self, repo: &TextureRepository, id: TextureID, cone: RayCone) -> Vec3 {
+        self.inner.sample_filtered(repo, id, cone)
+    }
+
+    //Normal, roughness and metallic maps are left out too
+    fn get_surface(
+        &self,
+        _repo: &TextureRepository,
+        normal: Vec3,
+        _cone: RayCone,
+    ) -> SurfaceProperties {
+        SurfaceProperties::new(normal)
+    }
+}
+
+impl<T: Intersectable> Intersectable for Clay<T> {
+    type C = ClayColor<T::C>;
+
+    fn intersect(&self, ray: Ray) -> Option<Intersection<Self::C>> {
+        let result = self.inner.intersect(ray)?.to_builder();
+        Some(
+            IntersectionBuilder {
+                ray: result.ray,
+                pos: result.pos,
+                distance: result.distance,
+                distance_squared: result.distance_squared,
+                normal: result.normal,
+                color_provider: ClayColor {
+                    inner: result.color_provider,
+                    color: self.color,
+                },
+            }
+            .build(),
+        )
+    }
+}
+
+impl<T: Intersectable + Bounded> Bounded for Clay<T> {
+    fn bounds(&self) -> AABB {
+        self.inner.bounds()
+    }
+}
@@ -0,0 +1,81 @@
+use std::cmp::Ordering;
+
+use crate::utilities::ray::{Intersectable, Intersection, Ray};
+
+use super::{
+    aabb::{Bounded, AABB},
+    bvh::count_nodes,
+};
+
+/// BVH over the bounds of whole objects, such as instances of shared BVHs placed in a scene
+pub enum TopLevelBVH<T: Intersectable + Bounded> {
+    Branch {
+        left: Box<TopLevelBVH<T>>,
+        right: Box<TopLevelBVH<T>>,
+        aabb: AABB,
+    },
+    Leaf(T),
+}
+
+impl<T: Intersectable + Bounded> TopLevelBVH<T> {
+    pub fn from_instances(instances: Vec<T>) -> Option<Self> {
+        let instances = instances
+            .into_iter()
+            .map(|instance| (instance.bounds(), instance))
+            .collect();
+        Self::build(instances)
+    }
+    fn build(mut instances: Vec<(AABB, T)>) -> Option<Self> {
+        if instances.len() <= 1 {
+            return instances.pop().map(|(_, instance)| TopLevelBVH::Leaf(instance));
+        }
+        let bounds = instances
+            .iter()
+            .map(|(aabb, _)| *aabb)
+            .reduce(|a, b| a.union(b))?;
+        let maxis = bounds.max_axis();
+        let index = instances.len() / 2;
+        instances.select_nth_unstable_by(index, |a, b| {
+            let a = a.0.centroid().get(maxis);
+            let b = b.0.centroid().get(maxis);
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        });
+        let right = instances.split_off(index);
+        Some(TopLevelBVH::Branch {
+            left: Box::new(Self::build(instances)?),
+            right: Box::new(Self::build(right)?),
+            aabb: bounds,
+        })
+    }
+}
+
+impl<T: Intersectable + Bounded> Bounded for TopLevelBVH<T> {
+    fn bounds(&self) -> AABB {
+        match self {
+            TopLevelBVH::Branch { aabb, .. } => *aabb,
+            TopLevelBVH::Leaf(instance) => instance.bounds(),
+        }
+    }
+}
+
+impl<T: Intersectable + Bounded> Intersectable for TopLevelBVH<T> {
+    type C = T::C;
+    fn intersect(&self, ray: Ray) -> Option<Intersection<Self::C>> {
+        match self {
+            TopLevelBVH::Leaf(instance) => instance.intersect(ray),
+            TopLevelBVH::Branch { left, right, aabb } => {
+                count_nodes(1);
+                aabb.intersect(ray)?;
+                let a = left.intersect(ray);
+                let b = right.intersect(ray);
+                match (a, b) {
+                    (None, None) => None,
+                    (None, Some(b)) => Some(b),
+                    (Some(a), None) => Some(a),
+                    (Some(a), Some(b)) if a.get_distance() < b.get_distance() => Some(a),
+                    (Some(_), Some(b)) => Some(b),
+                }
+            }
+        }
+    }
+}
@@ -7,4 +7,9 @@ mod tests;
 pub mod triangle;
 pub mod union;
 pub mod apply_matrix;
-pub mod transform;
\ No newline at end of file
+pub mod transform;
+pub mod portal;
+pub mod instances;
+pub mod packed_bvh;
+pub mod area_light;
+pub mod clay;
@@ -0,0 +1,438 @@
+use std::{cmp::Ordering, mem::size_of, sync::Arc};
+
+use smallvec::SmallVec;
+use wide::{f32x4, CmpLe};
+
+use crate::{
+    intersectables::{
+        aabb::{Bounded, AABB},
+        bvh::{count_nodes, count_triangles, BVH},
+        triangle::{Triangle, TriangleColor},
+    },
+    matrix,
+    renderers::path_tracer::Material,
+    textures::{samplers::WrapMode, texture::AlphaMask, TextureID},
+    utilities::{
+        math::Vec3,
+        ray::{Intersectable, Intersection, IntersectionBuilder, Ray},
+    },
+    vector,
+};
+
+/// Number of child boxes or triangles tested at once
+pub const LANES: usize = 4;
+/// One value per lane, change together with [`LANES`] (`f32x8` for 8 wide nodes)
+type Lanes = f32x4;
+
+/// Rounds towards negative infinity, so boxes never shrink in single precision
+fn round_down(value: f64) -> f32 {
+    let rounded = value as f32;
+    if rounded as f64 > value {
+        rounded - rounded.abs().max(f32::MIN_POSITIVE) * f32::EPSILON
+    } else {
+        rounded
+    }
+}
+
+fn round_up(value: f64) -> f32 {
+    -round_down(-value)
+}
+
+fn to_f32(v: Vec3) -> [f32; 3] {
+    [v.x() as f32, v.y() as f32, v.z() as f32]
+}
+
+fn to_f64(v: [f32; 3]) -> Vec3 {
+    Vec3::new(v[0] as f64, v[1] as f64, v[2] as f64)
+}
+
+/// Puts the triangles of a subtree into the free lanes of a packet, `false` if they don't fit.
+/// Stops at the first triangle that doesn't fit, so large subtrees aren't walked through.
+fn fill_packet<'a>(
+    bvh: &'a BVH,
+    packet: &mut [Option<&'a Triangle>; LANES],
+    len: &mut usize,
+) -> bool {
+    match bvh {
+        BVH::Branch { left, right, .. } => {
+            fill_packet(left, packet, len) && fill_packet(right, packet, len)
+        }
+        BVH::Leaf(triangle) => {
+            if *len == LANES {
+                return false;
+            }
+            packet[*len] = Some(triangle);
+            *len += 1;
+            true
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Child {
+    Empty,
+    Node(u32),
+    /// Index of a packet
+    Leaf(u32),
+}
+
+/// Bounds of the children of a node, one lane per child
+struct Node {
+    min: [Lanes; 3],
+    max: [Lanes; 3],
+    children: [Child; LANES],
+}
+
+/// Vertices of up to [`LANES`] triangles, one lane per triangle
+struct TrianglePacket {
+    vertices: [[Lanes; 3]; 3],
+    /// Index of the triangles' shading data, `None` for unused lanes
+    shading: [Option<u32>; LANES],
+}
+
+/// Everything besides the vertices needed to shade a hit
+struct Shading {
+    normals: [[f32; 3]; 3],
+    normal: [f32; 3],
+    tangent: [f32; 3],
+    bitangent: [f32; 3],
+    uv: [[f32; 2]; 3],
+    texture: TextureID,
+    material: Material,
+    wrap: WrapMode,
+    cutout: Option<Arc<AlphaMask>>,
+    double_sided: bool,
+}
+
+/// Ray with everything precomputed which is shared by the nodes and triangles it is tested against
+struct PackedRay {
+    ray: Ray,
+    origin: [Lanes; 3],
+    inverse: [Lanes; 3],
+    /// Axes the ray is sheared along, the ray points along the last one
+    axes: [usize; 3],
+    shear: [f64; 3],
+}
+
+impl PackedRay {
+    fn new(ray: Ray) -> Self {
+        let d = ray.direction;
+        let kz = if d.x().abs() > d.y().abs() {
+            if d.x().abs() > d.z().abs() {
+                0
+            } else {
+                2
+            }
+        } else if d.y().abs() > d.z().abs() {
+            1
+        } else {
+            2
+        };
+        let (kx, ky) = if d[kz] < 0.0 {
+            ((kz + 2) % 3, (kz + 1) % 3)
+        } else {
+            ((kz + 1) % 3, (kz + 2) % 3)
+        };
+        let origin = to_f32(ray.origin).map(Lanes::splat);
+        let inverse = to_f32(d).map(|d| Lanes::splat(1.0 / d));
+        Self {
+            ray,
+            origin,
+            inverse,
+            axes: [kx, ky, kz],
+            shear: [d[kx] / d[kz], d[ky] / d[kz], 1.0 / d[kz]],
+        }
+    }
+}
+
+/// Closest hit found so far
+struct Hit {
+    distance: f64,
+    packet: usize,
+    lane: usize,
+    /// Weights of the vertices
+    weights: [f64; 3],
+    back: bool,
+}
+
+/// BVH with single precision geometry, nodes with [`LANES`] children and leaves with [`LANES`] triangles, each tested in a single pass
+///
+/// The edge tests of a packet run in single precision, and are redone in double precision for triangles with an edge exactly through the ray.
+/// Distances are divided out in double precision from the widened weights.
+pub struct PackedBVH {
+    nodes: Vec<Node>,
+    packets: Vec<TrianglePacket>,
+    shading: Vec<Shading>,
+    root: Child,
+    bounds: AABB,
+}
+
+impl PackedBVH {
+    /// Packs a built BVH, opening up its largest branches into wider nodes
+    pub fn new(bvh: &BVH) -> Self {
+        let mut packed = Self {
+            nodes: Vec::new(),
+            packets: Vec::new(),
+            shading: Vec::new(),
+            root: Child::Empty,
+            bounds: bvh.bounds(),
+        };
+        packed.root = packed.add(bvh);
+        packed
+    }
+    /// Size of the nodes and triangles in bytes
+    pub fn memory(&self) -> usize {
+        self.nodes.len() * size_of::<Node>()
+            + self.packets.len() * size_of::<TrianglePacket>()
+            + self.shading.len() * size_of::<Shading>()
+    }
+    fn add(&mut self, bvh: &BVH) -> Child {
+        let mut packet = [None; LANES];
+        if fill_packet(bvh, &mut packet, &mut 0) {
+            return self.add_packet(&packet);
+        }
+        let mut children = vec![bvh];
+        while children.len() < LANES {
+            let largest = children
+                .iter()
+                .enumerate()
+                .filter(|(_, child)| matches!(child, BVH::Branch { .. }))
+                .max_by(|(_, a), (_, b)| {
+                    let a = a.bounds().surface_area();
+                    let b = b.bounds().surface_area();
+                    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+                })
+                .map(|(i, _)| i);
+            match largest {
+                Some(i) => {
+                    if let BVH::Branch { left, right, .. } = children.swap_remove(i) {
+                        children.push(left);
+                        children.push(right);
+                    }
+                }
+                None => break,
+            }
+        }
+        let mut min = [[f32::INFINITY; LANES]; 3];
+        let mut max = [[f32::NEG_INFINITY; LANES]; 3];
+        let mut nodes = [Child::Empty; LANES];
+        for (lane, child) in children.into_iter().enumerate() {
+            let bounds = child.bounds();
+            for axis in 0..3 {
+                min[axis][lane] = round_down(bounds.min[axis]);
+                max[axis][lane] = round_up(bounds.max[axis]);
+            }
+            nodes[lane] = self.add(child);
+        }
+        self.nodes.push(Node {
+            min: min.map(Lanes::from),
+            max: max.map(Lanes::from),
+            children: nodes,
+        });
+        Child::Node((self.nodes.len() - 1) as u32)
+    }
+    fn add_packet(&mut self, triangles: &[Option<&Triangle>; LANES]) -> Child {
+        let mut vertices = [[[0.0; LANES]; 3]; 3];
+        let mut shading = [None; LANES];
+        for (lane, triangle) in triangles.iter().flatten().enumerate() {
+            for (vertex, position) in [triangle.a, triangle.b, triangle.c].into_iter().enumerate() {
+                for axis in 0..3 {
+                    vertices[vertex][axis][lane] = position[axis] as f32;
+                }
+            }
+            shading[lane] = Some(self.shading.len() as u32);
+            self.shading.push(Shading {
+                normals: [
+                    to_f32(triangle.an),
+                    to_f32(triangle.bn),
+                    to_f32(triangle.cn),
+                ],
+                normal: to_f32(triangle.normal),
+                tangent: to_f32(triangle.tangent),
+                bitangent: to_f32(triangle.bitangent),
+                uv: triangle.uv.map(|uv| [uv.x() as f32, uv.y() as f32]),
+                texture: triangle.texture,
+                material: triangle.material,
+                wrap: triangle.wrap,
+                cutout: triangle.cutout.clone(),
+                double_sided: triangle.double_sided,
+            });
+        }
+        self.packets.push(TrianglePacket {
+            vertices: vertices.map(|vertex| vertex.map(Lanes::from)),
+            shading,
+        });
+        Child::Leaf((self.packets.len() - 1) as u32)
+    }
+    /// Distances to the children's boxes, infinite for the ones missed
+    fn intersect_node(node: &Node, ray: &PackedRay, distance: f64) -> [f32; LANES] {
+        let mut near = Lanes::ZERO;
+        let mut far = Lanes::splat(distance as f32);
+        for axis in 0..3 {
+            let t0 = (node.min[axis] - ray.origin[axis]) * ray.inverse[axis];
+            let t1 = (node.max[axis] - ray.origin[axis]) * ray.inverse[axis];
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        //Slightly enlarged, so rounding never misses a box the ray grazes
+        let hit = near.cmp_le(far * Lanes::splat(1.0 + 4.0 * f32::EPSILON));
+        hit.blend(near, Lanes::splat(f32::INFINITY)).to_array()
+    }
+    /// Closest hit on the packet's triangles nearer than the given distance
+    fn intersect_packet(&self, index: usize, ray: &PackedRay, distance: f64) -> Option<Hit> {
+        let packet = &self.packets[index];
+        let [kx, ky, kz] = ray.axes;
+        let [sx, sy, sz] = ray.shear;
+        //Vertices relative to the ray's origin, sheared so the ray points along the z axis
+        let [[ax, ay, az], [bx, by, bz], [cx, cy, cz]] = packet.vertices.map(|vertex| {
+            let z = vertex[kz] - ray.origin[kz];
+            [
+                vertex[kx] - ray.origin[kx] - Lanes::splat(sx as f32) * z,
+                vertex[ky] - ray.origin[ky] - Lanes::splat(sy as f32) * z,
+                z,
+            ]
+        });
+        let wa = (cx * by - cy * bx).to_array();
+        let wb = (ax * cy - ay * cx).to_array();
+        let wc = (bx * ay - by * ax).to_array();
+        let z = [az.to_array(), bz.to_array(), cz.to_array()];
+        let mut closest: Option<Hit> = None;
+        for lane in 0..LANES {
+            let shading = match packet.shading[lane] {
+                Some(shading) => &self.shading[shading as usize],
+                None => continue,
+            };
+            let back = to_f64(shading.normal).dot(ray.ray.direction) > 0.0;
+            if back && !shading.double_sided {
+                continue;
+            }
+            let mut weights = [wa[lane] as f64, wb[lane] as f64, wc[lane] as f64];
+            let mut z = z.map(|z| z[lane] as f64);
+            if weights.contains(&0.0) {
+                //Edges through the ray need double precision to be decided the same way on both triangles
+                let [a, b, c] = packet.vertices.map(|vertex| {
+                    let p = vertex.map(|axis| axis.to_array()[lane] as f64);
+                    let o = ray.ray.origin;
+                    let z = p[kz] - o[kz];
+                    [p[kx] - o[kx] - sx * z, p[ky] - o[ky] - sy * z, z]
+                });
+                weights = [
+                    c[0] * b[1] - c[1] * b[0],
+                    a[0] * c[1] - a[1] * c[0],
+                    b[0] * a[1] - b[1] * a[0],
+                ];
+                z = [a[2], b[2], c[2]];
+            }
+            if weights.iter().any(|w| *w < 0.0) && weights.iter().any(|w| *w > 0.0) {
+                continue;
+            }
+            let det: f64 = weights.iter().sum();
+            if det == 0.0 {
+                continue;
+            }
+            let t = (weights[0] * z[0] + weights[1] * z[1] + weights[2] * z[2]) * sz / det;
+            let best = closest.as_ref().map_or(distance, |hit| hit.distance);
+            if t < 0.0 || t >= best {
+                continue;
+            }
+            let weights = weights.map(|w| w / det);
+            if let Some(cutout) = &shading.cutout {
+                let uv = shading.uv.map(|[u, v]| vector![u as f64, v as f64]);
+                let coords = uv[0] * weights[0] + uv[1] * weights[1] + uv[2] * weights[2];
+                if !cutout.is_opaque(coords) {
+                    continue;
+                }
+            }
+            closest = Some(Hit {
+                distance: t,
+                packet: index,
+                lane,
+                weights,
+                back,
+            });
+        }
+        closest
+    }
+    /// Shading of a hit, the same as the unpacked triangle's
+    fn color(&self, hit: &Hit, ray: Ray) -> Intersection<TriangleColor> {
+        let packet = &self.packets[hit.packet];
+        let shading = &self.shading[packet.shading[hit.lane].unwrap_or_default() as usize];
+        let [a, b, c] = packet
+            .vertices
+            .map(|vertex| to_f64(vertex.map(|axis| axis.to_array()[hit.lane])));
+        let [an, bn, cn] = shading.normals.map(to_f64);
+        let triangle = Triangle {
+            a,
+            b,
+            c,
+            an,
+            bn,
+            cn,
+            normal: to_f64(shading.normal),
+            tangent: to_f64(shading.tangent),
+            bitangent: to_f64(shading.bitangent),
+            uv: shading.uv.map(|[u, v]| vector![u as f64, v as f64]),
+            texture: shading.texture,
+            material: shading.material,
+            wrap: shading.wrap,
+            cutout: None,
+            double_sided: shading.double_sided,
+        };
+        let [wa, wb, wc] = hit.weights;
+        let barycentric = Vec3::new(wb, wc, wa);
+        let normal = matrix![triangle.bn, triangle.cn, triangle.an] * barycentric;
+        let normal = if hit.back { -normal } else { normal };
+        IntersectionBuilder {
+            ray,
+            distance: Some(hit.distance),
+            normal,
+            color_provider: triangle.color(barycentric, ray.direction),
+            ..Default::default()
+        }
+        .build()
+    }
+}
+
+impl Bounded for PackedBVH {
+    fn bounds(&self) -> AABB {
+        self.bounds
+    }
+}
+
+impl Intersectable for PackedBVH {
+    type C = TriangleColor;
+    fn intersect(&self, ray: Ray) -> Option<Intersection<Self::C>> {
+        let packed = PackedRay::new(ray);
+        let mut closest: Option<Hit> = None;
+        //Inline, so tracing a ray doesn't allocate
+        let mut stack: SmallVec<[Child; 64]> = SmallVec::new();
+        stack.push(self.root);
+        while let Some(child) = stack.pop() {
+            let distance = closest.as_ref().map_or(f64::INFINITY, |hit| hit.distance);
+            match child {
+                Child::Empty => {}
+                Child::Leaf(index) => {
+                    count_triangles(self.packets[index as usize].shading.iter().flatten().count());
+                    if let Some(hit) = self.intersect_packet(index as usize, &packed, distance) {
+                        closest = Some(hit);
+                    }
+                }
+                Child::Node(index) => {
+                    count_nodes(1);
+                    let node = &self.nodes[index as usize];
+                    let near = Self::intersect_node(node, &packed, distance);
+                    let mut order: [usize; LANES] = std::array::from_fn(|lane| lane);
+                    //Nearest children are popped first
+                    order
+                        .sort_by(|a, b| near[*b].partial_cmp(&near[*a]).unwrap_or(Ordering::Equal));
+                    for lane in order {
+                        if near[lane].is_finite() && !matches!(node.children[lane], Child::Empty) {
+                            stack.push(node.children[lane]);
+                        }
+                    }
+                }
+            }
+        }
+        closest.map(|hit| self.color(&hit, ray))
+    }
+}
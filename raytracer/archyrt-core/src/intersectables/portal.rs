@@ -0,0 +1,63 @@
+use rand::Rng;
+
+use crate::utilities::{math::Vec3, ray::Ray};
+
+/// Invisible parallelogram marking an opening (e.g. a window) the sky can be seen through.
+/// Portals are never hit by rays, they only guide environment sampling.
+#[derive(Debug, Clone)]
+pub struct Portal {
+    pub corner: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+}
+
+impl Portal {
+    pub fn new(corner: Vec3, u: Vec3, v: Vec3) -> Self {
+        Self { corner, u, v }
+    }
+    pub fn normal(&self) -> Vec3 {
+        self.u.cross(self.v).normalized()
+    }
+    pub fn area(&self) -> f64 {
+        self.u.cross(self.v).length()
+    }
+    /// Uniformly samples a point on the portal
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Vec3 {
+        let s: f64 = rng.gen();
+        let t: f64 = rng.gen();
+        self.corner + self.u * s + self.v * t
+    }
+    /// Returns the distance along the ray where it passes through the portal
+    pub fn intersect(&self, ray: Ray) -> Option<f64> {
+        let n = self.u.cross(self.v);
+        let denominator = n.dot(ray.direction);
+        if denominator == 0.0 {
+            return None;
+        }
+        let t = n.dot(self.corner - ray.origin) / denominator;
+        if t <= 0.0 {
+            return None;
+        }
+        //Solving p=a*u+b*v for a and b
+        let p = ray.origin + ray.direction * t - self.corner;
+        let n2 = n.length_squared();
+        let a = p.cross(self.v).dot(n) / n2;
+        let b = self.u.cross(p).dot(n) / n2;
+        if !(0.0..=1.0).contains(&a) || !(0.0..=1.0).contains(&b) {
+            return None;
+        }
+        Some(t)
+    }
+    /// Solid angle probability density of sampling the ray's direction through this portal
+    pub fn pdf(&self, ray: Ray) -> f64 {
+        match self.intersect(ray) {
+            Some(t) => {
+                let direction = ray.direction.normalized();
+                let cos = self.normal().dot(direction).abs();
+                let distance = t * ray.direction.length();
+                distance * distance / (self.area() * cos)
+            }
+            None => 0.0,
+        }
+    }
+}
@@ -100,10 +100,19 @@ mod surface {
 }
 
 mod triangle {
+    use std::sync::Arc;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
     use crate::{
         intersectables::triangle::Triangle,
         renderers::path_tracer::Material,
-        textures::TextureID,
+        textures::{
+            color_provider::RayCone,
+            texture::{AlphaMask, Texture},
+            texture_repo::{SurfaceMaps, TextureRepository},
+            TextureID,
+        },
         utilities::{
             math::Vec3,
             ray::{Intersectable, Ray},
@@ -149,6 +158,123 @@ mod triangle {
         let intersection = triangle.intersect(ray);
         assert!(intersection.is_none());
     }
+    fn flat() -> Triangle {
+        Triangle::new(
+            [
+                Vec3::new(0.0, -1.0, 1.0),
+                Vec3::new(1.0, -1.0, -1.0),
+                Vec3::new(-1.0, -1.0, -1.0),
+            ],
+            [vector!(0.0, 0.0), vector!(0.0, 1.0), vector!(1.0, 0.0)],
+            TextureID::new(&0),
+            Material::Diffuse,
+        )
+    }
+    #[test]
+    fn tangent_frame() {
+        let triangle = flat();
+        //u increases towards c, v increases towards b
+        assert_eq!(triangle.tangent, (triangle.c - triangle.a).normalized());
+        assert_eq!(triangle.bitangent, (triangle.a - triangle.b).normalized());
+    }
+    #[test]
+    fn normal_map() {
+        let triangle = flat();
+        let mut repo = TextureRepository::new();
+        let mut texture = Texture::new(1, 1);
+        //Tilted fully towards the tangent
+        texture.set(0, Vec3::new(1.0, 0.5, 0.5));
+        let id = TextureID::new(&1);
+        repo.insert(id, texture);
+        repo.insert_maps(
+            triangle.texture,
+            SurfaceMaps {
+                normal: Some(id),
+                ..Default::default()
+            },
+        );
+        let ray = Ray {
+            origin: Vec3::from_single(0.0),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+        };
+        let intersection = triangle.intersect(ray).unwrap();
+        let surface = intersection.get_surface(&repo, RayCone::default());
+        assert!((surface.normal - triangle.tangent).length() < 1e-9);
+        assert_eq!(surface.roughness, 1.0);
+    }
+    #[test]
+    fn cutout() {
+        let mut triangle = flat();
+        let ray = Ray {
+            origin: Vec3::from_single(0.0),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+        };
+        //The ray hits the left half of the texture
+        triangle.cutout = Some(Arc::new(AlphaMask {
+            width: 2,
+            height: 1,
+            data: vec![0, 255],
+        }));
+        assert!(triangle.intersect(ray).is_none());
+        triangle.cutout = Some(Arc::new(AlphaMask {
+            width: 2,
+            height: 1,
+            data: vec![255, 0],
+        }));
+        assert!(triangle.intersect(ray).is_some());
+    }
+    #[test]
+    fn edge_and_vertex() {
+        let triangle = flat();
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        //Middle of the edge from b to c, and the vertices
+        for origin in [
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, -1.0),
+            Vec3::new(-1.0, 0.0, -1.0),
+        ] {
+            let intersection = triangle.intersect(Ray::new(origin, down)).unwrap();
+            assert_eq!(intersection.get_distance(), 1.0);
+        }
+        //Just outside of the edge
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -1.0 - 1e-12), down);
+        assert!(triangle.intersect(ray).is_none());
+    }
+    #[test]
+    fn shared_edge() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let p = [
+            Vec3::new(-1.3, -1.1, -0.7),
+            Vec3::new(1.1, -0.9, -1.2),
+            Vec3::new(0.9, -1.2, 1.3),
+            Vec3::new(-1.2, -0.8, 1.1),
+        ];
+        let uv = [vector!(0.0, 0.0); 3];
+        let id = TextureID::new(&0);
+        let first = Triangle::new([p[0], p[2], p[1]], uv, id, Material::Diffuse);
+        let second = Triangle::new([p[0], p[3], p[2]], uv, id, Material::Diffuse);
+        for _ in 0..10000 {
+            //Rays aimed exactly at the diagonal never slip between the triangles
+            let origin = Vec3::new(rng.gen_range(-3.0..3.0), 2.0, rng.gen_range(-3.0..3.0));
+            let target = p[0] + (p[2] - p[0]) * rng.gen::<f64>();
+            let ray = Ray::new(origin, (target - origin).normalized());
+            assert!(first.intersect(ray).is_some() || second.intersect(ray).is_some());
+        }
+    }
+    #[test]
+    fn double_sided() {
+        let mut triangle = flat();
+        let ray = Ray::new(Vec3::new(0.0, -2.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(triangle.intersect(ray).is_none());
+        let mut repo = TextureRepository::new();
+        repo.set_double_sided(triangle.texture, true);
+        triangle.load_material(&repo);
+        let intersection = triangle.intersect(ray).unwrap();
+        assert_eq!(intersection.get_distance(), 1.0);
+        //Shaded as seen from below
+        assert!(intersection.get_normal().dot(ray.direction) < 0.0);
+    }
 }
 
 mod aabb {
@@ -174,3 +300,321 @@ mod aabb {
         assert!(aabb.intersect(ray).is_none());
     }
 }
+
+mod portal {
+    use crate::{
+        intersectables::portal::Portal,
+        utilities::{math::Vec3, ray::Ray},
+    };
+
+    fn window() -> Portal {
+        Portal::new(
+            Vec3::new(-1.0, -1.0, 5.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn intersect() {
+        let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(window().intersect(ray), Some(5.0));
+    }
+    #[test]
+    fn intersect_fail() {
+        let ray = Ray::new(Vec3::new(3.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(window().intersect(ray).is_none());
+
+        let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, -1.0));
+        assert!(window().intersect(ray).is_none());
+    }
+    #[test]
+    fn pdf() {
+        let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0));
+        //distance^2/(area*cos)
+        assert_eq!(window().pdf(ray), 25.0 / 4.0);
+        let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(window().pdf(ray), 0.0);
+    }
+}
+
+mod instances {
+    use std::sync::Arc;
+
+    use crate::{
+        intersectables::{
+            apply_matrix::ApplyMatrix, bvh::BVH, instances::TopLevelBVH, transform::Transform,
+            triangle::Triangle,
+        },
+        utilities::{
+            math::{Matrix3x3, Vec3},
+            ray::{Intersectable, Ray},
+        },
+    };
+
+    fn instances() -> Vec<Transform<ApplyMatrix<Arc<BVH>>>> {
+        let bvh = Arc::new(BVH::from_triangles(&[Triangle::default()]).unwrap());
+        (0..10)
+            .map(|i| Transform {
+                inner: ApplyMatrix {
+                    inner: bvh.clone(),
+                    matrix: Matrix3x3::identity(),
+                    inverse_matrix: Matrix3x3::identity(),
+                },
+                transformation: Vec3::new(i as f64 * 3.0, 0.0, i as f64),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_linear_search() {
+        let tlas = TopLevelBVH::from_instances(instances()).unwrap();
+        let linear = instances();
+        for i in 0..10 {
+            let ray = Ray::new(
+                Vec3::new(i as f64 * 3.0, -0.5, -1.0),
+                Vec3::new(0.0, 0.0, 1.0),
+            );
+            let a = tlas.intersect(ray).unwrap();
+            let b = linear.intersect(ray).unwrap();
+            assert_eq!(a.get_pos(), b.get_pos());
+        }
+        let miss = Ray::new(Vec3::new(-5.0, -0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(tlas.intersect(miss).is_none());
+    }
+}
+
+mod bvh {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        intersectables::{
+            bvh::BVH,
+            triangle::Triangle,
+        },
+        renderers::path_tracer::Material,
+        textures::TextureID,
+        utilities::{
+            math::Vec3,
+            ray::{Intersectable, Ray},
+        },
+        vector,
+    };
+
+    pub(super) fn point(rng: &mut StdRng, scale: f64) -> Vec3 {
+        Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>()) * scale
+    }
+
+    pub(super) fn triangles(count: usize) -> Vec<Triangle> {
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..count)
+            .map(|_| {
+                let a = point(&mut rng, 10.0);
+                Triangle::new(
+                    [a, a + point(&mut rng, 0.5), a + point(&mut rng, 0.5)],
+                    [vector![0.0, 0.0]; 3],
+                    TextureID::new(&0),
+                    Material::Diffuse,
+                )
+            })
+            .collect()
+    }
+
+    fn matches_linear_search(count: usize) {
+        let triangles = triangles(count);
+        let bvh = BVH::from_triangles(&triangles).unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let origin = point(&mut rng, 10.0);
+            let direction = (point(&mut rng, 2.0) - Vec3::from_single(1.0)).normalized();
+            let ray = Ray::new(origin, direction);
+            let a = bvh.intersect(ray).map(|i| i.get_distance());
+            let b = triangles.intersect(ray).map(|i| i.get_distance());
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn small() {
+        matches_linear_search(100);
+    }
+    #[test]
+    fn parallel() {
+        matches_linear_search(20000);
+    }
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats() {
+        use crate::intersectables::bvh::measure;
+
+        let triangles = triangles(1000);
+        let bvh = BVH::from_triangles(&triangles).unwrap();
+        let target = (triangles[0].a + triangles[0].b + triangles[0].c) / 3.0;
+        let origin = Vec3::from_single(-1.0);
+        let ray = Ray::new(origin, (target - origin).normalized());
+        let ((hit, inner), outer) = measure(|| measure(|| bvh.intersect(ray).is_some()));
+        assert!(hit);
+        assert!(inner.nodes > 0);
+        assert!(inner.triangles > 0 && inner.triangles < triangles.len());
+        assert_eq!(inner.depth, 0);
+        //Nested measurements are included in the outer ones
+        assert_eq!(inner, outer);
+        let (_, cheaper) = measure(|| bvh.intersect(Ray::new(ray.origin, -ray.direction)));
+        assert_eq!(cheaper.triangles, 0);
+    }
+}
+
+mod packed_bvh {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::bvh::{point, triangles};
+    use crate::{
+        intersectables::{bvh::BVH, packed_bvh::PackedBVH},
+        utilities::{
+            math::Vec3,
+            ray::{Intersectable, Ray},
+        },
+    };
+
+    #[test]
+    fn matches_double_precision() {
+        let bvh = BVH::from_triangles(&triangles(5000)).unwrap();
+        let packed = PackedBVH::new(&bvh);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut mismatches = 0;
+        for _ in 0..1000 {
+            let origin = point(&mut rng, 10.0);
+            let direction = (point(&mut rng, 2.0) - Vec3::from_single(1.0)).normalized();
+            let ray = Ray::new(origin, direction);
+            match (bvh.intersect(ray), packed.intersect(ray)) {
+                (Some(a), Some(b)) => {
+                    assert!((a.get_distance() - b.get_distance()).abs() < 1e-4);
+                    assert!((a.get_normal() - b.get_normal()).length() < 1e-4);
+                }
+                (None, None) => {}
+                //Rays grazing an edge may go either way after rounding
+                _ => mismatches += 1,
+            }
+        }
+        assert!(mismatches <= 2);
+    }
+    #[test]
+    fn single_triangle() {
+        let bvh = BVH::from_triangles(&triangles(1)).unwrap();
+        let packed = PackedBVH::new(&bvh);
+        let triangle = bvh.triangles()[0];
+        let target = (triangle.a + triangle.b + triangle.c) / 3.0;
+        let origin = target + triangle.normal;
+        let ray = Ray::new(origin, -triangle.normal);
+        let hit = packed.intersect(ray).unwrap();
+        assert!((hit.get_distance() - 1.0).abs() < 1e-6);
+        assert!(packed
+            .intersect(Ray::new(origin, triangle.normal))
+            .is_none());
+    }
+}
+
+mod area_light {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        intersectables::{area_light::AreaLights, triangle::Triangle},
+        renderers::path_tracer::Material,
+        textures::TextureID,
+        matrix,
+        utilities::math::Vec3,
+        vector,
+    };
+
+    fn lamp() -> Triangle {
+        let uv = [vector![0.0, 0.0], vector![0.0, 1.0], vector![1.0, 0.0]];
+        Triangle::new(
+            [
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 2.0),
+            ],
+            uv,
+            TextureID::new(&0),
+            Material::Emissive { power: 1.0 },
+        )
+    }
+
+    #[test]
+    fn emitters_only() {
+        let mut double_sided = lamp();
+        double_sided.double_sided = true;
+        let triangles = [lamp(), Triangle::default(), double_sided];
+        let lights = AreaLights::new(&triangles);
+        //Both faces of the double sided triangle emit
+        assert_eq!(lights.lights.len(), 3);
+        assert_eq!(lights.area(), 6.0);
+        assert_eq!(lights.pdf(), 1.0 / 6.0);
+        assert!(AreaLights::new(&[Triangle::default()]).choose(&mut rand::thread_rng()).is_none());
+    }
+    #[test]
+    fn sample() {
+        let lights = AreaLights::new(&[lamp()]);
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let sample = lights.choose(&mut rng).unwrap().sample(&mut rng).unwrap();
+            let p = sample.pos;
+            assert!(p.x() >= 0.0 && p.z() >= 0.0 && p.x() + p.z() <= 2.0 + 1e-9);
+            assert_eq!(p.y(), 0.0);
+            assert_eq!(sample.normal, lamp().normal);
+        }
+    }
+    #[test]
+    fn transformed() {
+        let matrix = matrix![
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            Vec3::new(0.0, 0.0, 2.0)
+        ];
+        let triangle = lamp().transformed(matrix, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(triangle.b, Vec3::new(4.0, 1.0, 0.0));
+        assert_eq!(triangle.area(), lamp().area() * 4.0);
+        assert_eq!(triangle.an, lamp().an);
+    }
+}
+
+mod clay {
+    use crate::{
+        intersectables::{
+            clay::{Clay, CLAY_COLOR},
+            sphere::Sphere,
+        },
+        renderers::path_tracer::Material,
+        textures::{color_provider::RayCone, texture_repo::TextureRepository},
+        utilities::{
+            math::Vec3,
+            ray::{Intersectable, Ray},
+        },
+    };
+
+    fn color(sphere: Sphere) -> Vec3 {
+        let repo = TextureRepository::new();
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let intersection = Clay::new(sphere).intersect(ray).unwrap();
+        assert_eq!(intersection.get_distance(), 4.0);
+        intersection.get_color_filtered(&repo, RayCone::default())
+    }
+
+    #[test]
+    fn neutral() {
+        let sphere = Sphere {
+            color: Vec3::new(1.0, 0.0, 0.0),
+            ..Default::default()
+        };
+        assert_eq!(color(sphere), CLAY_COLOR);
+    }
+    #[test]
+    fn keeps_lights() {
+        let lamp = Sphere {
+            color: Vec3::new(1.0, 0.5, 0.0),
+            material: Material::Emissive { power: 2.0 },
+            ..Default::default()
+        };
+        assert_eq!(color(lamp), Vec3::new(1.0, 0.5, 0.0));
+    }
+}
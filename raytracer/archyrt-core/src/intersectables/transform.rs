@@ -1,5 +1,7 @@
 use crate::utilities::{ray::{Intersectable, Ray, Intersection}, math::{Vec3}};
 
+use super::aabb::{Bounded, AABB};
+
 pub struct Transform<T: Intersectable>{
     pub inner: T,
     pub transformation: Vec3
@@ -19,4 +21,11 @@ impl<T: Intersectable> Intersectable for Transform<T>{
 
         Some(result.build())
     }
-}
\ No newline at end of file
+}
+
+impl<T: Intersectable + Bounded> Bounded for Transform<T>{
+    fn bounds(&self) -> AABB {
+        let aabb = self.inner.bounds();
+        AABB::new(aabb.min + self.transformation, aabb.max + self.transformation)
+    }
+}
@@ -1,25 +1,34 @@
+use crate::loaders::amdl::repo::PropID;
 use crate::renderers::path_tracer::Material;
-use crate::textures::color_provider::ColorProvider;
+use crate::textures::color_provider::{ColorProvider, RayCone, SurfaceProperties};
 
 use crate::textures::samplers::linear::LinearSampler;
 use crate::textures::samplers::nearest::NearestSampler;
-use crate::textures::samplers::TextureSampler;
+use crate::textures::samplers::trilinear::{Footprint, TrilinearSampler};
+use crate::textures::samplers::{TextureSampler, WrapMode};
+use crate::textures::texture::AlphaMask;
 use crate::textures::texture_repo::TextureRepository;
 use crate::textures::TextureID;
-use crate::utilities::math::{Axis3, Vec2};
+use crate::utilities::math::{Axis3, Matrix3x3, Vec2};
 use crate::vector;
 use crate::{
     matrix,
     utilities::{
-        math::{Matrix3x3, Vec3},
+        math::Vec3,
         ray::{Intersectable, Intersection, IntersectionBuilder, Ray},
     },
 };
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
 use std::cmp::Ordering;
+use std::sync::Arc;
 
 use super::aabb::AABB;
 
-#[derive(Debug, Clone)]
+/// Slope of bump mapped surfaces per unit of height difference between neighbouring texels
+const BUMP_STRENGTH: f64 = 4.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Triangle {
     pub a: Vec3,
     pub b: Vec3,
@@ -28,9 +37,20 @@ pub struct Triangle {
     pub bn: Vec3,
     pub cn: Vec3,
     pub normal: Vec3,
+    /// Direction of increasing u
+    pub tangent: Vec3,
+    /// Direction towards the top of the texture, which is decreasing v
+    pub bitangent: Vec3,
     pub uv: [Vec2; 3],
     pub texture: TextureID,
     pub material: Material,
+    /// How the texture continues past the edges of its uv range
+    pub wrap: WrapMode,
+    /// Opacity of the texture, hits on transparent texels are skipped
+    #[serde(skip)]
+    pub cutout: Option<Arc<AlphaMask>>,
+    /// Whether the back face is hit too, instead of being culled
+    pub double_sided: bool,
 }
 
 impl Triangle {
@@ -42,6 +62,7 @@ impl Triangle {
     pub fn with_normals(vertices: [Vec3; 3], uv: [Vec2; 3], normals: [Vec3; 3], texture: TextureID, material: Material) -> Self{
         let [a, b, c] = vertices;
         let normal = (b - a).cross(c - a).normalized();
+        let (tangent, bitangent) = tangent_frame(vertices, uv, normal);
         Self {
             a,
             b,
@@ -50,11 +71,39 @@ impl Triangle {
             bn: normals[1],
             cn: normals[2],
             normal,
+            tangent,
+            bitangent,
             uv,
             texture,
             material,
+            wrap: WrapMode::default(),
+            cutout: None,
+            double_sided: false,
+        }
+    }
+    /// Cuts out the transparent parts of the triangle's texture, and makes it double sided if its material is
+    pub fn load_material(&mut self, repo: &TextureRepository) {
+        self.cutout = repo.get_cutout(self.texture);
+        self.double_sided = repo.is_double_sided(self.texture);
+    }
+    /// Puts the light of an emissive texture in the light group of the prop
+    pub fn set_prop(&mut self, id: PropID) {
+        if let Material::DiffuseAndEmissive { prop, .. } = &mut self.material {
+            *prop = Some(id);
         }
     }
+    /// Triangle multiplied by a matrix then moved, the way props are placed
+    pub fn transformed(&self, matrix: Matrix3x3, translation: Vec3) -> Self {
+        let vertices = [self.a, self.b, self.c].map(|v| matrix * v + translation);
+        let normals = [self.an, self.bn, self.cn].map(|n| (matrix * n).normalized());
+        let mut triangle = Self::with_normals(vertices, self.uv, normals, self.texture, self.material);
+        triangle.cutout = self.cutout.clone();
+        triangle.double_sided = self.double_sided;
+        triangle
+    }
+    pub fn area(&self) -> f64 {
+        (self.b - self.a).cross(self.c - self.a).length() / 2.0
+    }
     pub fn bounds(&self) -> AABB {
         let min = self.a.min(self.b).min(self.c);
         let max = self.a.max(self.b).max(self.c);
@@ -65,6 +114,28 @@ impl Triangle {
         (self.a + self.b + self.c) / 3.0
     }
 
+    /// uv-space footprint of a ray cone with unit width hitting the triangle from the given direction
+    pub fn footprint(&self, direction: Vec3) -> Footprint {
+        footprint([self.a, self.b, self.c], self.uv, self.normal, direction)
+    }
+    /// Color of a point of the triangle hit from a direction.
+    /// The footprint of the ray cone is only computed once a texture is filtered, so missed candidates don't pay for it.
+    pub fn color(&self, barycentric: Vec3, direction: Vec3) -> TriangleColor {
+        TriangleColor {
+            uv: self.uv,
+            barycentric,
+            texture: self.texture,
+            material: self.material,
+            wrap: self.wrap,
+            vertices: [self.a, self.b, self.c],
+            normal: self.normal,
+            direction,
+            footprint: OnceCell::new(),
+            tangent: self.tangent,
+            bitangent: self.bitangent,
+        }
+    }
+
     pub fn side(&self, a: Axis3, divider: f64) -> Ordering {
         let o = self.a.get(a) >= divider;
         for p in [self.b, self.c].iter() {
@@ -80,6 +151,60 @@ impl Triangle {
     }
 }
 
+/// uv-space footprint of a ray cone with unit width hitting a triangle from the given direction
+fn footprint(vertices: [Vec3; 3], uv: [Vec2; 3], normal: Vec3, direction: Vec3) -> Footprint {
+    let [a, b, c] = vertices;
+    let e1 = b - a;
+    let e2 = c - a;
+    let t1 = uv[1] - uv[0];
+    let t2 = uv[2] - uv[0];
+    let world_area = e1.cross(e2).length();
+    let uv_area = (t1.x() * t2.y() - t1.y() * t2.x()).abs();
+    let direction = direction.normalized();
+    let cos = normal.dot(direction).abs();
+    //The cone is stretched along the direction projected onto the triangle's plane
+    let along = direction - normal * normal.dot(direction);
+    let major = if along.length_squared() > 0.0 && cos > 0.0 {
+        //Solving along=s*e1+t*e2 for s and t
+        let along = along.normalized();
+        let (a, b, c) = (e1.dot(e1), e1.dot(e2), e2.dot(e2));
+        let (d1, d2) = (e1.dot(along), e2.dot(along));
+        let det = a * c - b * b;
+        let s = (c * d1 - b * d2) / det;
+        let t = (a * d2 - b * d1) / det;
+        (t1 * s + t2 * t) / cos
+    } else {
+        Vec2::default()
+    };
+    Footprint {
+        width: (uv_area / world_area).sqrt(),
+        major,
+    }
+}
+
+/// World space directions of the texture's axes on a triangle
+fn tangent_frame(vertices: [Vec3; 3], uv: [Vec2; 3], normal: Vec3) -> (Vec3, Vec3) {
+    let [a, b, c] = vertices;
+    let e1 = b - a;
+    let e2 = c - a;
+    let t1 = uv[1] - uv[0];
+    let t2 = uv[2] - uv[0];
+    let det = t1.x() * t2.y() - t2.x() * t1.y();
+    if det == 0.0 || !det.is_finite() {
+        //Degenerate uvs, any frame around the normal will do
+        let tangent = if normal.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let tangent = tangent.cross(normal).normalized();
+        return (tangent, normal.cross(tangent));
+    }
+    let tangent = (e1 * t2.y() - e2 * t1.y()) / det;
+    let bitangent = (e2 * t1.x() - e1 * t2.x()) / det;
+    (tangent.normalized(), -bitangent.normalized())
+}
+
 impl Default for Triangle {
     fn default() -> Self {
         let a = Vec3::new(0.0, 0.0, 3.0);
@@ -96,6 +221,30 @@ pub struct TriangleColor {
     pub barycentric: Vec3,
     pub texture: TextureID,
     pub material: Material,
+    pub wrap: WrapMode,
+    /// Corners and geometric normal of the triangle, which the footprint is computed from
+    pub vertices: [Vec3; 3],
+    pub normal: Vec3,
+    /// Direction the triangle was hit from
+    pub direction: Vec3,
+    /// Footprint of a unit wide ray cone, computed when first needed
+    footprint: OnceCell<Footprint>,
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+}
+
+impl TriangleColor {
+    /// Footprint of a unit wide ray cone
+    pub fn footprint(&self) -> Footprint {
+        *self
+            .footprint
+            .get_or_init(|| footprint(self.vertices, self.uv, self.normal, self.direction))
+    }
+    fn coords(&self) -> Vec2 {
+        self.uv[1] * self.barycentric[0]
+            + self.uv[2] * self.barycentric[1]
+            + self.uv[0] * self.barycentric[2]
+    }
 }
 
 impl ColorProvider for TriangleColor {
@@ -108,42 +257,132 @@ impl ColorProvider for TriangleColor {
     }
 
     fn sample(&self, repo: &TextureRepository, id: TextureID) -> Vec3 {
-        let sampler = LinearSampler {};
-        let coords = self.uv[1] * self.barycentric[0]
-            + self.uv[2] * self.barycentric[1]
-            + self.uv[0] * self.barycentric[2];
-        sampler.sample_or_default(repo.get(id), coords)
+        let sampler = LinearSampler { wrap: self.wrap };
+        sampler.sample_or_default(repo.get(id), self.coords())
+    }
+
+    fn get_color_filtered(&self, repo: &TextureRepository, cone: RayCone) -> Vec3 {
+        self.sample_filtered(repo, self.texture, cone)
+    }
+
+    fn sample_filtered(&self, repo: &TextureRepository, id: TextureID, cone: RayCone) -> Vec3 {
+        let sampler = TrilinearSampler {
+            anisotropy: cone.anisotropy,
+            wrap: self.wrap,
+        };
+        match repo.get_mipmap(id) {
+            Some(mipmap) => {
+                sampler.sample_footprint(mipmap, self.coords(), self.footprint().scale(cone.width))
+            }
+            None => Vec3::default(),
+        }
+    }
+
+    fn get_surface(&self, repo: &TextureRepository, normal: Vec3, cone: RayCone) -> SurfaceProperties {
+        let mut surface = SurfaceProperties::new(normal);
+        let maps = match repo.get_maps(self.texture) {
+            Some(maps) => *maps,
+            None => return surface,
+        };
+        //Tangent frame orthogonalized against the interpolated normal
+        let tangent = (self.tangent - normal * normal.dot(self.tangent)).normalized();
+        let handedness = if normal.cross(tangent).dot(self.bitangent) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let bitangent = normal.cross(tangent) * handedness;
+        if let Some(id) = maps.normal {
+            let n = self.sample_filtered(repo, id, cone) * 2.0 - Vec3::from_single(1.0);
+            let n = tangent * n.x() + bitangent * n.y() + normal * n.z();
+            if n.length_squared() > 0.0 {
+                surface.normal = n.normalized();
+            }
+        } else if let Some(texture) = maps.bump.and_then(|id| repo.get(id)) {
+            //Height differences to the neighbouring texels
+            let sampler = LinearSampler { wrap: self.wrap };
+            let coords = self.coords();
+            let du = vector![1.0 / texture.width() as f64, 0.0];
+            let dv = vector![0.0, 1.0 / texture.height() as f64];
+            let height = sampler.sample(texture, coords).x();
+            let dhdu = sampler.sample(texture, coords + du).x() - height;
+            //The bitangent points towards decreasing v
+            let dhdv = height - sampler.sample(texture, coords + dv).x();
+            surface.normal = (normal - (tangent * dhdu + bitangent * dhdv) * BUMP_STRENGTH).normalized();
+        }
+        if let Some(id) = maps.roughness {
+            surface.roughness = self.sample_filtered(repo, id, cone).x().clamp(0.0, 1.0);
+        }
+        if let Some(id) = maps.metallic {
+            surface.metallic = self.sample_filtered(repo, id, cone).x().clamp(0.0, 1.0);
+        }
+        if let Some(id) = maps.occlusion {
+            surface.occlusion = self.sample_filtered(repo, id, cone).x().clamp(0.0, 1.0);
+        }
+        surface
     }
 }
 
 impl Intersectable for Triangle {
     type C = TriangleColor;
     fn intersect(&self, ray: Ray) -> Option<Intersection<Self::C>> {
-        //Based on https://www.scratchapixel.com/lessons/3d-basic-rendering/ray-tracing-rendering-a-triangle/moller-trumbore-ray-triangle-intersection
-        //Backface culling
-        if self.normal.dot(ray.direction) > 0.0 {
+        //Watertight intersection, based on https://jcgt.org/published/0002/01/05/
+        let back = self.normal.dot(ray.direction) > 0.0;
+        if back && !self.double_sided {
             return None;
         }
-        let mat: Matrix3x3 = matrix!(-ray.direction, self.b - self.a, self.c - self.a);
-        let v = ray.origin - self.a;
-        let solution = mat.cramer(v)?;
-        let [t, u, v] = solution.inner;
-        if t < 0.0 || u < 0.0 || v < 0.0 || u + v > 1.0 {
+        //Shear the vertices so the ray points along the z axis, the largest component of its direction
+        let d = ray.direction;
+        let kz = if d.x().abs() > d.y().abs() {
+            if d.x().abs() > d.z().abs() { 0 } else { 2 }
+        } else if d.y().abs() > d.z().abs() {
+            1
+        } else {
+            2
+        };
+        let (kx, ky) = if d[kz] < 0.0 {
+            ((kz + 2) % 3, (kz + 1) % 3)
+        } else {
+            ((kz + 1) % 3, (kz + 2) % 3)
+        };
+        let sx = d[kx] / d[kz];
+        let sy = d[ky] / d[kz];
+        let sz = 1.0 / d[kz];
+        let [a, b, c] = [self.a, self.b, self.c].map(|p| p - ray.origin);
+        let shear = |p: Vec3| (p[kx] - sx * p[kz], p[ky] - sy * p[kz]);
+        let ((ax, ay), (bx, by), (cx, cy)) = (shear(a), shear(b), shear(c));
+        //Edges shared by two triangles give exactly opposite values, so rays can't leak between them
+        let wa = cx * by - cy * bx;
+        let wb = ax * cy - ay * cx;
+        let wc = bx * ay - by * ax;
+        if (wa < 0.0 || wb < 0.0 || wc < 0.0) && (wa > 0.0 || wb > 0.0 || wc > 0.0) {
             return None;
         }
-        let barycentric = Vec3::new(u, v, 1.0 - u - v);
+        let det = wa + wb + wc;
+        if det == 0.0 {
+            return None;
+        }
+        let t = (wa * a[kz] + wb * b[kz] + wc * c[kz]) * sz / det;
+        if t < 0.0 {
+            return None;
+        }
+        let (u, v) = (wb / det, wc / det);
+        let barycentric = Vec3::new(u, v, wa / det);
+        if let Some(cutout) = &self.cutout {
+            let coords = self.uv[1] * u + self.uv[2] * v + self.uv[0] * barycentric[2];
+            if !cutout.is_opaque(coords) {
+                return None;
+            }
+        }
         let normal = matrix![self.bn, self.cn, self.an] * barycentric;
+        //Back faces are shaded as seen from behind
+        let normal = if back { -normal } else { normal };
         Some(
             IntersectionBuilder {
                 ray,
                 distance: Some(t),
                 normal,
-                color_provider: TriangleColor {
-                    uv: self.uv,
-                    barycentric,
-                    texture: self.texture,
-                    material: self.material,
-                },
+                color_provider: self.color(barycentric, ray.direction),
                 ..Default::default()
             }
             .build(),
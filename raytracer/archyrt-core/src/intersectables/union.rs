@@ -1,6 +1,9 @@
 use crate::{
     renderers::path_tracer::Material,
-    textures::{color_provider::ColorProvider, texture_repo::TextureRepository},
+    textures::{
+        color_provider::{ColorProvider, RayCone, SurfaceProperties},
+        texture_repo::TextureRepository,
+    },
     utilities::{
         math::Vec3,
         ray::{Intersectable, Intersection, Ray},
@@ -37,6 +40,37 @@ impl<A: ColorProvider + Clone, B: ColorProvider + Clone> ColorProvider
             UnionColorProvider::B(b) => b.sample(repo, id),
         }
     }
+
+    fn get_color_filtered(&self, repo: &TextureRepository, cone: RayCone) -> Vec3 {
+        match self {
+            UnionColorProvider::A(a) => a.get_color_filtered(repo, cone),
+            UnionColorProvider::B(b) => b.get_color_filtered(repo, cone),
+        }
+    }
+
+    fn sample_filtered(
+        &self,
+        repo: &TextureRepository,
+        id: crate::textures::TextureID,
+        cone: RayCone,
+    ) -> Vec3 {
+        match self {
+            UnionColorProvider::A(a) => a.sample_filtered(repo, id, cone),
+            UnionColorProvider::B(b) => b.sample_filtered(repo, id, cone),
+        }
+    }
+
+    fn get_surface(
+        &self,
+        repo: &TextureRepository,
+        normal: Vec3,
+        cone: RayCone,
+    ) -> SurfaceProperties {
+        match self {
+            UnionColorProvider::A(a) => a.get_surface(repo, normal, cone),
+            UnionColorProvider::B(b) => b.get_surface(repo, normal, cone),
+        }
+    }
 }
 
 impl<A: Intersectable, B: Intersectable> Intersectable for UnionIntersector<A, B>
@@ -1,9 +1,14 @@
 pub mod api;
 pub mod cameras;
+pub mod checkpoint;
 pub mod collector;
+pub mod color_space;
+pub mod image_diff;
 pub mod intersectables;
+pub mod lightmap;
 pub mod loaders;
 pub mod renderers;
 pub mod textures;
 pub mod utilities;
+pub mod postprocess;
 pub mod tonemapping;
\ No newline at end of file
@@ -0,0 +1,200 @@
+use anyhow::{anyhow, Result};
+use asset::{encode_rgbm, scene::{self, FaceKind, SolidKind}, Lightmap, LightmapFace};
+use cgmath::vec2;
+use rayon::prelude::*;
+
+use crate::{
+    api::fragment_render::FragmentContext,
+    color_space::{ColorSpace, ColorTransform},
+    loaders::ascn::point_position,
+    postprocess::FloatImage,
+    renderers::{integrator::Scene, path_tracer::PathTracer},
+    textures::texture_repo::TextureRepository,
+    utilities::{math::Vec3, ray::Intersectable},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Texels around each face repeating its edges, so filtering doesn't blend neighbouring faces
+pub const PADDING: u32 = 1;
+/// Texels per meter of the lightmaps baked for the editor
+pub const DENSITY: f64 = 8.0;
+/// Most texels along an edge of a face in the lightmaps baked for the editor
+pub const MAX_SIZE: u32 = 128;
+
+/// Face of a solid placed in an atlas
+#[derive(Debug, Clone)]
+pub struct AtlasFace {
+    pub face: LightmapFace,
+    /// Corners in the coordinates of the raytracer
+    pub corners: [Vec3; 4],
+    /// Side the face is seen and lit from
+    pub normal: Vec3,
+}
+
+impl AtlasFace {
+    /// Position of the center of a texel, counted from the first texel of the face
+    pub fn position(&self, x: u32, y: u32) -> Vec3 {
+        let s = (x as f64 + 0.5) / self.face.size.x as f64;
+        let t = (y as f64 + 0.5) / self.face.size.y as f64;
+        let [a, b, c, d] = self.corners;
+        (a * (1.0 - s) + b * s) * (1.0 - t) + (d * (1.0 - s) + c * s) * t
+    }
+}
+
+/// Rendered faces of a solid, with `density` texels per meter and at most `max_size` texels along an edge
+fn solid_faces(solid: &scene::Solid, density: f64, max_size: u32) -> Vec<AtlasFace> {
+    //Fog volumes aren't rendered
+    if solid.kind == SolidKind::Fog {
+        return Vec::new();
+    }
+    solid
+        .faces
+        .iter()
+        .filter(|face| face.texture.0 != 0 && face.kind == FaceKind::Surface)
+        .filter_map(|face| {
+            let points = face.indices.map(|id| &solid.points[id as usize]);
+            let corners = points.map(point_position);
+            let [a, b, c, d] = corners;
+            //Same winding as the triangles of the loader
+            let normal = (c - a).cross(b - a);
+            if normal.length() < 1e-9 {
+                return None;
+            }
+            let texels = |edge: Vec3| ((edge.length() * density).ceil() as u32).clamp(1, max_size);
+            Some(AtlasFace {
+                face: LightmapFace {
+                    corners: points.map(|point| point.position),
+                    offset: vec2(0, 0),
+                    size: vec2(texels(b - a), texels(d - a)),
+                },
+                corners,
+                normal: normal.normalized(),
+            })
+        })
+        .collect()
+}
+
+/// Layout of the rendered faces of a scene's solids in a lightmap
+#[derive(Debug, Clone)]
+pub struct LightmapAtlas {
+    pub width: u32,
+    pub height: u32,
+    pub faces: Vec<AtlasFace>,
+}
+
+impl LightmapAtlas {
+    pub fn from_bytes(data: &[u8], density: f64, max_size: u32) -> Result<Self> {
+        let scene = scene::Scene::decode(data).ok_or_else(|| anyhow!("Could not decode scene"))?;
+        Ok(Self::new(&scene, density, max_size))
+    }
+    /// Gives faces `density` texels per meter, at most `max_size` along an edge
+    pub fn new(scene: &scene::Scene, density: f64, max_size: u32) -> Self {
+        let mut faces: Vec<AtlasFace> = scene
+            .world
+            .solids
+            .iter()
+            .flat_map(|solid| solid_faces(solid, density, max_size))
+            .collect();
+        let padded = |face: &AtlasFace| face.face.size + vec2(PADDING * 2, PADDING * 2);
+        let area: u64 = faces
+            .iter()
+            .map(|face| {
+                let size = padded(face);
+                size.x as u64 * size.y as u64
+            })
+            .sum();
+        let widest = faces.iter().map(|face| padded(face).x).max().unwrap_or(1);
+        let width = ((area as f64).sqrt().ceil() as u32)
+            .max(widest)
+            .next_power_of_two();
+        //Shelves of faces from left to right, tallest faces first
+        faces.sort_by_key(|face| std::cmp::Reverse(face.face.size.y));
+        let (mut x, mut y, mut shelf) = (0, 0, 0);
+        for face in &mut faces {
+            let size = padded(face);
+            if x + size.x > width {
+                x = 0;
+                y += shelf;
+                shelf = 0;
+            }
+            face.face.offset = vec2(x + PADDING, y + PADDING);
+            x += size.x;
+            shelf = shelf.max(size.y);
+        }
+        Self {
+            width,
+            height: (y + shelf).max(1),
+            faces,
+        }
+    }
+    /// Light reflected by white diffuse surfaces at the texels, in the working space, averaged over `samples` paths.
+    /// It still has to be denoised, texels outside the faces are black.
+    pub fn bake<K: Intersectable + Sync>(
+        &self,
+        scene: &Scene<K>,
+        tracer: &PathTracer,
+        repo: &TextureRepository,
+        samples: usize,
+    ) -> FloatImage {
+        let ctx = FragmentContext {
+            width: self.width as f64,
+            height: self.height as f64,
+            repo,
+        };
+        let mut image = FloatImage::new(self.width as usize, self.height as usize);
+        for face in &self.faces {
+            let (width, height) = (face.face.size.x, face.face.size.y);
+            let texels: Vec<Vec3> = (0..width * height)
+                .into_par_iter()
+                .map(|i| {
+                    let pos = face.position(i % width, i / width);
+                    let sum = (0..samples).fold(Vec3::default(), |a, _| {
+                        a + tracer.gather(scene, &ctx, pos, face.normal).beauty()
+                    });
+                    sum / samples as f64
+                })
+                .collect();
+            for (i, texel) in texels.into_iter().enumerate() {
+                let x = face.face.offset.x + i as u32 % width;
+                let y = face.face.offset.y + i as u32 / width;
+                image.pixels[y as usize * image.width + x as usize] = texel;
+            }
+        }
+        self.dilate(&mut image);
+        image
+    }
+    /// Fills the padding around each face with its nearest texels
+    pub fn dilate(&self, image: &mut FloatImage) {
+        for face in &self.faces {
+            let LightmapFace { offset, size, .. } = face.face;
+            for y in offset.y - PADDING..offset.y + size.y + PADDING {
+                for x in offset.x - PADDING..offset.x + size.x + PADDING {
+                    let inner_x = x.clamp(offset.x, offset.x + size.x - 1);
+                    let inner_y = y.clamp(offset.y, offset.y + size.y - 1);
+                    image.pixels[y as usize * image.width + x as usize] =
+                        image.get(inner_x as usize, inner_y as usize);
+                }
+            }
+        }
+    }
+    /// Lightmap asset of baked texels, converted for the sRGB textures of the editor
+    pub fn encode(&self, image: &FloatImage) -> Lightmap {
+        let transform = ColorTransform::from_working(ColorSpace::LinearSrgb);
+        let rgbm8 = image
+            .pixels
+            .iter()
+            .flat_map(|texel| {
+                let color = transform.linear(*texel).max(Vec3::default());
+                encode_rgbm(color.inner.map(|c| c as f32))
+            })
+            .collect();
+        Lightmap {
+            width: self.width,
+            height: self.height,
+            rgbm8,
+            faces: self.faces.iter().map(|face| face.face).collect(),
+        }
+    }
+}
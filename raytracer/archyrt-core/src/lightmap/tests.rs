@@ -0,0 +1,204 @@
+mod atlas {
+    use asset::{
+        decode_rgbm,
+        scene::{Camera, Face, FaceKind, Point, Scene, Solid, SolidKind, TextureTransform, World},
+        TextureID,
+    };
+    use cgmath::{vec2, vec3, Vector3};
+
+    use crate::{
+        intersectables::area_light::AreaLights,
+        lightmap::{LightmapAtlas, PADDING},
+        loaders::{ascn::ASCNLoader, Loader},
+        renderers::{
+            integrator::{Scene as RenderScene, HEMISPHERE_PDF},
+            media::{Media, Sun},
+            path_tracer::PathTracer,
+        },
+        textures::texture_repo::TextureRepository,
+        utilities::math::Vec3,
+    };
+
+    /// Box of the editor from `origin` with the size `extent`
+    fn solid(origin: Vector3<i32>, extent: Vector3<i32>) -> Solid {
+        let points = [
+            vec3(0, 0, 0),
+            vec3(1, 0, 0),
+            vec3(1, 0, 1),
+            vec3(0, 0, 1),
+            vec3(0, 1, 0),
+            vec3(1, 1, 0),
+            vec3(1, 1, 1),
+            vec3(0, 1, 1),
+        ]
+        .map(|point| Point {
+            position: origin + vec3(point.x * extent.x, point.y * extent.y, point.z * extent.z),
+        });
+        let faces = [
+            [1, 5, 6, 2],
+            [4, 0, 3, 7],
+            [5, 4, 7, 6],
+            [0, 1, 2, 3],
+            [3, 2, 6, 7],
+            [1, 0, 4, 5],
+        ]
+        .map(|indices| Face {
+            texture: TextureID(1),
+            indices,
+            transform: TextureTransform::default(),
+            kind: FaceKind::Surface,
+        });
+        Solid {
+            points,
+            faces,
+            kind: SolidKind::Solid,
+        }
+    }
+    fn scene(solids: Vec<Solid>) -> Scene {
+        Scene {
+            camera: Camera {
+                position: vec3(0.0, 0.0, 0.0),
+                rotation: vec2(0.0, 0.0),
+            },
+            world: World {
+                solids,
+                props: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn layout() {
+        //2 meter cube and a flat 1 meter wide slab
+        let scene = scene(vec![
+            solid(vec3(0, 0, 0), vec3(256, 256, 256)),
+            solid(vec3(512, 0, 0), vec3(128, 32, 128)),
+        ]);
+        let atlas = LightmapAtlas::new(&scene, 4.0, 64);
+        assert_eq!(atlas.faces.len(), 12);
+        assert!(atlas.faces.iter().any(|face| face.face.size == vec2(8, 8)));
+        assert!(atlas.faces.iter().any(|face| face.face.size == vec2(4, 4)));
+        //Padded faces don't overlap and stay inside the atlas
+        let rect = |i: usize| {
+            let face = atlas.faces[i].face;
+            let min = face.offset - vec2(PADDING, PADDING);
+            let max = face.offset + face.size + vec2(PADDING, PADDING);
+            (min, max)
+        };
+        for i in 0..atlas.faces.len() {
+            let (min, max) = rect(i);
+            assert!(max.x <= atlas.width && max.y <= atlas.height);
+            for j in 0..i {
+                let (other_min, other_max) = rect(j);
+                let apart = max.x <= other_min.x
+                    || other_max.x <= min.x
+                    || max.y <= other_min.y
+                    || other_max.y <= min.y;
+                assert!(apart);
+            }
+        }
+    }
+    #[test]
+    fn faces() {
+        let atlas = LightmapAtlas::new(
+            &scene(vec![solid(vec3(0, 0, 0), vec3(128, 128, 128))]),
+            2.0,
+            64,
+        );
+        //Faces point away from the cube
+        for face in &atlas.faces {
+            let center = face.position(1, 1) * 0.5 + face.position(0, 0) * 0.5;
+            let inside = Vec3::new(0.5, 0.5, -0.5);
+            assert!(face.normal.dot(center - inside) > 0.0);
+        }
+        //Texel centers span the face
+        let face = &atlas.faces[0];
+        assert!((face.position(0, 0) - face.corners[0]).length() < 0.4);
+        assert!((face.position(1, 1) - face.corners[2]).length() < 0.4);
+        //Invisible faces aren't baked
+        let mut hidden = solid(vec3(0, 0, 0), vec3(128, 128, 128));
+        hidden.faces[0].texture = TextureID(0);
+        assert_eq!(
+            LightmapAtlas::new(&scene(vec![hidden]), 2.0, 64)
+                .faces
+                .len(),
+            5
+        );
+        //Portals aren't baked either, they are loaded as portals
+        let mut window = solid(vec3(0, 0, 0), vec3(128, 128, 128));
+        window.faces[0].kind = FaceKind::Portal;
+        window.faces[1].kind = FaceKind::Portal;
+        let window = scene(vec![window]);
+        assert_eq!(LightmapAtlas::new(&window, 2.0, 64).faces.len(), 4);
+        let loader = ASCNLoader::from_scene(window).unwrap();
+        assert_eq!(loader.get_portals().len(), 2);
+        assert_eq!(loader.get_triangles().len(), 8);
+        //Portals without a texture are openings all the same
+        let mut opening = solid(vec3(0, 0, 0), vec3(128, 128, 128));
+        opening.faces[0].kind = FaceKind::Portal;
+        opening.faces[0].texture = TextureID(0);
+        let loader = ASCNLoader::from_scene(scene(vec![opening])).unwrap();
+        assert_eq!(loader.get_portals().len(), 1);
+        assert_eq!(loader.get_triangles().len(), 10);
+    }
+    #[test]
+    fn bake() {
+        let ascn = scene(vec![solid(vec3(0, 0, 0), vec3(128, 128, 128))]);
+        let atlas = LightmapAtlas::new(&ascn, 2.0, 64);
+        let loader = ASCNLoader::from_scene(ascn).unwrap();
+        let color = Vec3::from_single(2.0);
+        let scene = RenderScene {
+            object: loader.get_triangles().clone(),
+            skybox: None,
+            portals: Vec::new(),
+            lights: AreaLights::default(),
+            anisotropy: 1,
+            media: Media::default(),
+            sun: Some(Sun {
+                direction: Vec3::new(0.0, 1.0, 0.0),
+                color,
+            }),
+        };
+        let repo = TextureRepository::new();
+        let image = atlas.bake(&scene, &PathTracer::default(), &repo, 1);
+        for face in &atlas.faces {
+            let texel = image.get(face.face.offset.x as usize, face.face.offset.y as usize);
+            //Only the top is lit by the sun, nothing else is there to reflect it
+            let expected = if face.normal.y() > 0.5 {
+                color * HEMISPHERE_PDF
+            } else {
+                Vec3::default()
+            };
+            assert!((texel - expected).length() < 1e-9);
+            //The padding repeats the edges
+            let padding = image.get(
+                (face.face.offset.x - PADDING) as usize,
+                (face.face.offset.y - PADDING) as usize,
+            );
+            assert_eq!(padding, texel);
+        }
+        let lightmap = atlas.encode(&image);
+        assert_eq!(
+            lightmap.rgbm8.len(),
+            (atlas.width * atlas.height * 4) as usize
+        );
+        let texcoords = lightmap.texcoords();
+        for face in &lightmap.faces {
+            let corners = texcoords[&face.corners.map(Into::into)];
+            assert!(corners
+                .iter()
+                .all(|uv| (0.0..=1.0).contains(&uv.x) && (0.0..=1.0).contains(&uv.y)));
+            //Grey light stays grey in sRGB
+            let i = (face.offset.y * lightmap.width + face.offset.x) as usize * 4;
+            let [r, g, b] = decode_rgbm(lightmap.rgbm8[i..i + 4].try_into().unwrap());
+            let expected = image
+                .get(face.offset.x as usize, face.offset.y as usize)
+                .x() as f32;
+            assert!(
+                (r - expected).abs() < 0.01
+                    && (g - expected).abs() < 0.01
+                    && (b - expected).abs() < 0.01
+            );
+        }
+    }
+}
@@ -1,12 +1,20 @@
-use std::{path::Path, fs::File, io::Read};
+use std::{path::Path, fs::File, io::Read, collections::HashSet};
 
-use crate::{intersectables::triangle::Triangle, renderers::path_tracer::Material, textures::texture_repo::TextureRepository};
+use crate::{intersectables::triangle::Triangle, renderers::path_tracer::Material, textures::{texture_repo::TextureRepository, TextureID}};
 use anyhow::{anyhow, Result};
 use asset::Prop;
 
-use super::ascn::amdl_textures::AMDLTextureType;
+use super::{ascn::amdl_textures::AMDLTextureType, textures_of};
 pub mod repo;
 
+/// Texture ID of each mesh of the prop and whether it has an emissive texture, which decides the materials of its triangles
+pub fn materials(prop: &Prop, textures: &TextureRepository) -> Vec<(u32, bool)> {
+    prop.meshes
+        .iter()
+        .map(|mesh| (mesh.texture.0, textures.exists(AMDLTextureType::emissive(mesh.texture.0))))
+        .collect()
+}
+
 pub struct AMDLLoader{
     pub triangles: Vec<Triangle>
 }
@@ -25,17 +33,17 @@ impl AMDLLoader{
 
     pub fn from_prop(scene: Prop, textures: &TextureRepository) -> Result<Self> {
         let mut triangles = Vec::new();
-        for mesh in scene.meshes{
+        let materials = materials(&scene, textures);
+        for (mesh, (_, has_emission)) in scene.meshes.into_iter().zip(materials){
             let texture = AMDLTextureType::diffuse(mesh.texture.0);
             let emissive = AMDLTextureType::emissive(mesh.texture.0);
-            let has_emission = textures.exists(emissive);
             for triangle in mesh.triangles{
                 let triangle: Vec<&asset::PropVertex> = triangle.iter().map(|index|&mesh.vertices[(*index) as usize]).collect();
                 let v1 = triangle[0];
                 let v2 = triangle[1];
                 let v3 = triangle[2];
                 let material = if has_emission{
-                    Material::DiffuseAndEmissive{emissive_texture: emissive}
+                    Material::DiffuseAndEmissive{emissive_texture: emissive, prop: None}
                 }else{
                     Material::Diffuse
                 };
@@ -65,5 +73,13 @@ impl AMDLLoader{
             triangles,
         })
     }
-
-}
\ No newline at end of file
+    pub fn get_textures(&self) -> HashSet<TextureID> {
+        textures_of(&self.triangles)
+    }
+    /// Applies the cutouts and sidedness of their materials to the triangles
+    pub fn load_materials(&mut self, textures: &TextureRepository) {
+        for triangle in &mut self.triangles {
+            triangle.load_material(textures);
+        }
+    }
+}
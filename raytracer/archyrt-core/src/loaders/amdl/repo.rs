@@ -1,13 +1,15 @@
-use std::{collections::{hash_map::DefaultHasher, HashMap}, hash::{Hash, Hasher}, path::Path, fs::File};
+use std::{collections::{hash_map::DefaultHasher, HashMap, HashSet}, fmt, hash::{Hash, Hasher}, path::{Path, PathBuf}, fs::{self, File}, sync::Arc};
 
 use anyhow::{Result, anyhow};
 use serde::{Serialize, Deserialize};
 
-use crate::{intersectables::{bvh::{self, BVH}, apply_matrix::ApplyMatrix, transform::Transform}, utilities::math::{Vec3, Matrix3x3}, textures::texture_repo::TextureRepository};
+use crate::{intersectables::{area_light::emitters, bvh::{self, BVH}, packed_bvh::PackedBVH, triangle::Triangle, apply_matrix::ApplyMatrix, transform::Transform, instances::TopLevelBVH}, utilities::{math::{Vec3, Matrix3x3}, lru::LruBudget}, textures::{texture_repo::TextureRepository, TextureID}};
 
-use super::AMDLLoader;
+use super::{materials, AMDLLoader};
+use crate::loaders::{bvh_cache::{material_key, BVHCache}, textures_of};
+use asset::Prop;
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PropID(u64);
 
 #[derive(Hash, Copy, Clone)]
@@ -29,32 +31,137 @@ impl PropID {
     }
 }
 
+impl fmt::Display for PropID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
 impl Default for PropID {
     fn default() -> Self {
         Self::new(&0u32)
     }
 }
 
+/// Placement of a cached prop in the scene
+pub type PropInstance = Transform<ApplyMatrix<Arc<PackedBVH>>>;
+
+/// Prop with its BVH built once, shared by all of its instances
+pub struct CachedProp {
+    /// `None` for props without any triangles
+    pub bvh: Option<Arc<PackedBVH>>,
+    /// Textures the prop is drawn with
+    pub textures: HashSet<TextureID>,
+    /// Emissive triangles, which light paths can start from
+    pub emitters: Vec<Triangle>,
+}
+
+impl CachedProp {
+    pub fn new(object: AMDLLoader) -> Self {
+        Self {
+            bvh: BVH::from_triangles(&object.triangles).map(|bvh| Arc::new(PackedBVH::new(&bvh))),
+            textures: object.get_textures(),
+            emitters: emitters(&object.triangles),
+        }
+    }
+}
+
+/// Resident props, which registered props are loaded into when required
 pub struct PropRepository {
-    pub objects: HashMap<PropID, AMDLLoader>,
+    pub objects: HashMap<PropID, CachedProp>,
+    sources: HashMap<PropID, PathBuf>,
+    /// Props loaded from sources, inserted props are never evicted
+    lru: LruBudget<PropID>,
+    /// BVHs built from sources, keyed by the contents of the `.amdl` files and the materials of their meshes
+    cache: Option<BVHCache>,
 }
 
 impl PropRepository {
     pub fn new() -> Self {
         let t = HashMap::new();
-        Self { objects: t }
+        Self {
+            objects: t,
+            sources: HashMap::new(),
+            lru: LruBudget::new(None),
+            cache: None,
+        }
+    }
+    /// Caches the BVHs of props loaded from sources in the directory
+    pub fn set_cache(&mut self, cache: Option<BVHCache>) {
+        self.cache = cache;
     }
-    pub fn get(&self, id: PropID) -> Option<&AMDLLoader> {
+    /// Limits the memory of props loaded from sources, in bytes
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.lru.budget = budget;
+    }
+    pub fn get(&self, id: PropID) -> Option<&CachedProp> {
         let object = self.objects.get(&id)?;
         Some(object)
     }
-    pub fn insert(&mut self, id: PropID, object: AMDLLoader) {
-        self.objects.insert(id, object);
+    /// Inserts the prop and builds its BVH
+    pub fn insert(&mut self, id: PropID, mut object: AMDLLoader) {
+        for triangle in &mut object.triangles {
+            triangle.set_prop(id);
+        }
+        self.objects.insert(id, CachedProp::new(object));
+    }
+    /// Registers a prop to be loaded when it is first required
+    pub fn register<P: AsRef<Path>>(&mut self, id: PropID, path: P) {
+        self.sources.insert(id, path.as_ref().to_path_buf());
+    }
+    /// Makes the requested props and their textures resident, loading them from their sources if needed
+    pub fn require(&mut self, requests: &[PropRequest], textures: &mut TextureRepository) -> Result<()> {
+        let ids: HashSet<PropID> = requests.iter().map(|req| req.prop).collect();
+        for id in ids {
+            if !self.lru.touch(id) && !self.objects.contains_key(&id) {
+                let path = self.sources.get(&id).ok_or(anyhow!("Invalid prop id"))?;
+                let data = fs::read(path)?;
+                let prop = Prop::decode(&data).ok_or_else(||anyhow!("Could not decode prop"))?;
+                //The materials are chosen while building, so they are part of the key
+                let key = material_key(&data, &materials(&prop, textures));
+                let build = || Ok(BVH::from_triangles(&AMDLLoader::from_prop(prop, textures)?.triangles));
+                let mut bvh = match &self.cache {
+                    Some(cache) => cache.get_or_build(&key, build)?,
+                    None => build()?,
+                };
+                let object_textures = match &bvh {
+                    Some(bvh) => textures_of(bvh.triangles()),
+                    None => HashSet::new(),
+                };
+                textures.require(object_textures.iter().copied())?;
+                if let Some(bvh) = &mut bvh {
+                    bvh.load_materials(textures);
+                    for triangle in bvh.triangles_mut() {
+                        triangle.set_prop(id);
+                    }
+                }
+                let object_emitters = match &bvh {
+                    Some(bvh) => emitters(bvh.triangles()),
+                    None => Vec::new(),
+                };
+                let bvh = bvh.map(|bvh| PackedBVH::new(&bvh));
+                self.lru.insert(id, bvh.as_ref().map_or(0, PackedBVH::memory));
+                self.objects.insert(id, CachedProp {
+                    bvh: bvh.map(Arc::new),
+                    textures: object_textures,
+                    emitters: object_emitters,
+                });
+            } else if let Some(object) = self.objects.get(&id) {
+                textures.require(object.textures.iter().copied())?;
+            }
+        }
+        Ok(())
+    }
+    /// Unloads the least recently used props not required since the last eviction, until the rest fit in the budget
+    pub fn evict(&mut self) {
+        for id in self.lru.evict() {
+            self.objects.remove(&id);
+        }
     }
-    pub fn fulfill(&self, req: &PropRequest) -> Result<Transform<ApplyMatrix<BVH>>>{
+    /// Places an instance of the prop's shared BVH
+    pub fn fulfill(&self, req: &PropRequest) -> Result<PropInstance>{
         let object = self.get(req.prop).ok_or(anyhow!("Invalid prop id"))?;
-        let object = &object.triangles;
-        let object = BVH::from_triangles(object).ok_or(anyhow!("Invalid geometry"))?;
+        let object = object.bvh.clone().ok_or(anyhow!("Invalid geometry"))?;
         let object = ApplyMatrix{
             inner: object, 
             matrix: req.matrix,
@@ -66,7 +173,7 @@ impl PropRepository {
         };
         Ok(object)
     }
-    pub fn fulfill_all(&self, requests: &[PropRequest]) -> Result<Vec<Transform<ApplyMatrix<BVH>>>>{
+    pub fn fulfill_all(&self, requests: &[PropRequest]) -> Result<Vec<PropInstance>>{
         let mut output = Vec::with_capacity(requests.len());
         for req in requests{
             let prop = self.fulfill(req)?;
@@ -74,6 +181,31 @@ impl PropRepository {
         }
         Ok(output)
     }
+    /// Emissive triangles of every placed prop, in scene space
+    pub fn emitters(&self, requests: &[PropRequest]) -> Result<Vec<Triangle>> {
+        let mut output = Vec::new();
+        for req in requests {
+            let object = self.get(req.prop).ok_or(anyhow!("Invalid prop id"))?;
+            //Rays are transformed by the matrix, so the prop is transformed by its inverse
+            output.extend(object.emitters.iter().map(|triangle| triangle.transformed(req.inverse_matrix, req.position)));
+        }
+        Ok(output)
+    }
+    /// Places the props and the scene's own geometry under a single top level BVH
+    pub fn build_scene(&self, solids: Option<Arc<PackedBVH>>, requests: &[PropRequest]) -> Result<Option<TopLevelBVH<PropInstance>>>{
+        let mut instances = self.fulfill_all(requests)?;
+        if let Some(solids) = solids {
+            instances.push(Transform{
+                inner: ApplyMatrix{
+                    inner: solids,
+                    matrix: Matrix3x3::identity(),
+                    inverse_matrix: Matrix3x3::identity(),
+                },
+                transformation: Vec3::default(),
+            });
+        }
+        Ok(TopLevelBVH::from_instances(instances))
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -87,16 +219,14 @@ struct Repo{
     pub props: Vec<PropInfo>
 }
 
-pub fn load_into(repo: &mut PropRepository, textures: &TextureRepository, directory: &str) -> Result<()> {
+/// Registers every prop of the repository, they are loaded once required
+pub fn load_into(repo: &mut PropRepository, directory: &str) -> Result<()> {
     let propsjson = Path::new(directory).join("repo.json");
     let propsjson = File::open(propsjson)?;
     let json: Repo = serde_json::from_reader(propsjson)?;
     for prop in json.props {
         let path = Path::new(directory).join("props").join(prop.name).with_extension("amdl");
-        repo.insert(
-            PropType::default(prop.id),
-            AMDLLoader::from_path(path, textures)?
-        );
+        repo.register(PropType::default(prop.id), path);
     }
     Ok(())
 }
@@ -2,15 +2,23 @@ use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
 use std::{fs::File, path::Path};
 
-use crate::textures::{
-    texture_repo::{png, TextureRepository},
-    TextureID,
+use crate::{
+    color_space::ColorSpace,
+    textures::{
+        texture_repo::{png, SurfaceMaps, TextureRepository},
+        TextureID,
+    },
 };
 
 #[derive(Hash)]
 pub enum AMDLTextureType {
     Diffuse(u32),
     Emissive(u32),
+    Normal(u32),
+    Bump(u32),
+    Roughness(u32),
+    Metallic(u32),
+    Occlusion(u32),
 }
 
 impl AMDLTextureType {
@@ -20,6 +28,21 @@ impl AMDLTextureType {
     pub fn emissive(id: u32) -> TextureID {
         TextureID::new(&Self::Emissive(id))
     }
+    pub fn normal(id: u32) -> TextureID {
+        TextureID::new(&Self::Normal(id))
+    }
+    pub fn bump(id: u32) -> TextureID {
+        TextureID::new(&Self::Bump(id))
+    }
+    pub fn roughness(id: u32) -> TextureID {
+        TextureID::new(&Self::Roughness(id))
+    }
+    pub fn metallic(id: u32) -> TextureID {
+        TextureID::new(&Self::Metallic(id))
+    }
+    pub fn occlusion(id: u32) -> TextureID {
+        TextureID::new(&Self::Occlusion(id))
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -31,9 +54,49 @@ struct Repo {
 struct Texture{
     pub id: u32,
     pub name: String,
-    pub emissive: Option<String>
+    pub emissive: Option<String>,
+    #[serde(default)]
+    pub normal: Option<String>,
+    #[serde(default)]
+    pub bump: Option<String>,
+    #[serde(default)]
+    pub roughness: Option<String>,
+    #[serde(default)]
+    pub metallic: Option<String>,
+    #[serde(default)]
+    pub occlusion: Option<String>,
+    /// Thin surfaces such as leaves or cloth, which are visible from both sides
+    #[serde(default)]
+    pub double_sided: bool,
+    /// Color space of the diffuse and emissive images, sRGB if it is missing
+    #[serde(default)]
+    pub color_space: Option<ColorSpace>,
+}
+
+/// Registers a png to be loaded when it is required, colors are converted from their color space
+/// while data maps without one are kept as they are
+fn register_png(repo: &mut TextureRepository, directory: &str, name: &str, id: TextureID, space: Option<ColorSpace>) {
+    let directory = directory.to_string();
+    let name = name.to_string();
+    repo.register(id, move || match space {
+        Some(space) => png::load_in(&directory, &name, space),
+        None => png::load_linear(&directory, &name),
+    });
+}
+
+/// Registers an optional data map, returning the id it is stored under
+fn register_map(
+    repo: &mut TextureRepository,
+    directory: &str,
+    name: &Option<String>,
+    id: TextureID,
+) -> Option<TextureID> {
+    let name = name.as_ref()?;
+    register_png(repo, directory, name, id, None);
+    Some(id)
 }
 
+/// Registers every texture of the repository, they are loaded once required
 pub fn load_into(repo: &mut TextureRepository, directory: &str) -> Result<()> {
     let texturesjson = Path::new(directory).join("repo.json");
     let texturesjson = File::open(texturesjson)?;
@@ -41,16 +104,20 @@ pub fn load_into(repo: &mut TextureRepository, directory: &str) -> Result<()> {
     for tex in json.textures {
         let textures_directory = Path::new(directory).join("textures");
         let textures_directory = textures_directory.to_str().ok_or(anyhow!("Unable to decode path string"))?;
+        let space = Some(tex.color_space.unwrap_or_default());
         if let Some(emissive) = tex.emissive{
-            repo.insert(
-                AMDLTextureType::emissive(tex.id),
-                png::load(textures_directory, &emissive)?,
-            );
+            register_png(repo, textures_directory, &emissive, AMDLTextureType::emissive(tex.id), space);
         }
-        repo.insert(
-            AMDLTextureType::diffuse(tex.id),
-            png::load(textures_directory, &tex.name)?,
-        );
+        register_png(repo, textures_directory, &tex.name, AMDLTextureType::diffuse(tex.id), space);
+        let maps = SurfaceMaps {
+            normal: register_map(repo, textures_directory, &tex.normal, AMDLTextureType::normal(tex.id)),
+            bump: register_map(repo, textures_directory, &tex.bump, AMDLTextureType::bump(tex.id)),
+            roughness: register_map(repo, textures_directory, &tex.roughness, AMDLTextureType::roughness(tex.id)),
+            metallic: register_map(repo, textures_directory, &tex.metallic, AMDLTextureType::metallic(tex.id)),
+            occlusion: register_map(repo, textures_directory, &tex.occlusion, AMDLTextureType::occlusion(tex.id)),
+        };
+        repo.insert_maps(AMDLTextureType::diffuse(tex.id), maps);
+        repo.set_double_sided(AMDLTextureType::diffuse(tex.id), tex.double_sided);
     }
     Ok(())
 }
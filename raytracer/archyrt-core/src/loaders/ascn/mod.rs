@@ -1,16 +1,21 @@
 pub mod amdl_textures;
+use crate::intersectables::portal::Portal;
 use crate::intersectables::triangle::Triangle;
-use crate::loaders::Loader;
+use crate::loaders::{textures_of, Loader};
 
+use crate::renderers::media::Convex;
 use crate::renderers::path_tracer::Material;
+use crate::textures::samplers::WrapMode;
+use crate::textures::texture_repo::TextureRepository;
+use crate::textures::TextureID;
 
 use crate::utilities::math::{Vec2, Vec3, Matrix3x3};
 use crate::{cameras::perspective::PerspectiveCamera, vector};
 use anyhow::{anyhow, Result};
-use asset::scene::{Scene, Point};
-use cgmath::{Rotation, Matrix3, Matrix, SquareMatrix};
+use asset::scene::{FaceKind, Scene, Point, Solid, SolidKind, TextureTransform, TextureWrap};
+use cgmath::{vec2, Rotation, Matrix3, Matrix, SquareMatrix};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Read;
 
@@ -21,13 +26,73 @@ use self::amdl_textures::AMDLTextureType;
 use super::amdl::repo::{PropRequest, PropType};
 
 
+/// Focal distance of scene cameras, giving an 80 degree vertical field of view
+pub const FOCAL_DISTANCE: f64 = 0.595877;
+
 pub struct ASCNLoader {
     triangles: Vec<Triangle>,
     camera: PerspectiveCamera,
-    prop_requests: Vec<PropRequest>
+    prop_requests: Vec<PropRequest>,
+    portals: Vec<Portal>,
+    volumes: Vec<Convex>,
+}
+
+/// Position of a point of a solid in the coordinates of the raytracer
+pub(crate) fn point_position(point: &Point) -> Vec3 {
+    let mut position: Vec3 = Vec3::from(point.position) / 128.0;
+    position[2] = -position[2];
+    position
 }
-fn texcoord(position: Vec3, normal: Vec3) -> Vec2 {
-    if normal.x().abs() > normal.y().abs() {
+
+/// Camera at a position of the editor, in meters, with the pitch and yaw of the editor, in degrees
+pub fn editor_camera(position: Vec3, rotation: Vec2, focal_distance: f64) -> PerspectiveCamera {
+    let mut position = position;
+    position[2] = -position[2];
+    let mut camera = PerspectiveCamera::from_euler(
+        position,
+        vector![rotation.x(), rotation.y(), 0.0] / 180.0 * std::f64::consts::PI,
+        focal_distance,
+    );
+    camera.matrix = camera.matrix.transpose();
+    camera
+}
+
+/// Region enclosed by the faces of a solid
+fn solid_volume(solid: &Solid) -> Convex {
+    let center = solid
+        .points
+        .iter()
+        .fold(Vec3::default(), |a, point| a + point_position(point))
+        / (solid.points.len() as f64);
+    let planes = solid
+        .faces
+        .iter()
+        .filter_map(|face| {
+            let points: Vec<Vec3> = face
+                .indices
+                .iter()
+                .map(|id| point_position(&solid.points[(*id) as usize]))
+                .collect();
+            let normal = (points[1] - points[0]).cross(points[3] - points[0]);
+            //Faces of collapsed solids can have no area
+            if normal.length() < 1e-9 {
+                return None;
+            }
+            let normal = normal.normalized();
+            let offset = normal.dot(points[0]);
+            //Normals point away from the center
+            if normal.dot(center) > offset {
+                Some((-normal, -offset))
+            } else {
+                Some((normal, offset))
+            }
+        })
+        .collect();
+    Convex { planes }
+}
+
+fn texcoord(position: Vec3, normal: Vec3, transform: &TextureTransform) -> Vec2 {
+    let projected = if normal.x().abs() > normal.y().abs() {
         if normal.x().abs() > normal.z().abs() {
           vector!(position.z(), position.y())
         } else {
@@ -37,7 +102,18 @@ fn texcoord(position: Vec3, normal: Vec3) -> Vec2 {
         vector!(position.x(), position.z())
       } else {
           vector!(position.x(), position.y())
-      }
+      };
+    transform
+        .apply(vec2(projected.x() as f32, projected.y() as f32))
+        .into()
+}
+
+fn wrap_mode(wrap: TextureWrap) -> WrapMode {
+    match wrap {
+        TextureWrap::Repeat => WrapMode::Repeat,
+        TextureWrap::Clamp => WrapMode::Clamp,
+        TextureWrap::Mirror => WrapMode::Mirror,
+    }
 }
 
 impl ASCNLoader {
@@ -54,73 +130,59 @@ impl ASCNLoader {
 
     pub fn from_scene(scene: Scene) -> Result<Self> {
         let mut triangles: Vec<Triangle> = Vec::new();
-        let focal_distance = 0.595877;
-        let mut camera_pos: Vec3 = scene.camera.position.into();
-        camera_pos.inner[2] = -camera_pos[2];
-        let rotation: Vec2 = scene.camera.rotation.into();
-        let mut camera = PerspectiveCamera::from_euler(
-            camera_pos,
-            vector![rotation.x(), rotation.y(), 0.0] / 180.0 * std::f64::consts::PI,
-            focal_distance,
+        let mut portals: Vec<Portal> = Vec::new();
+        let mut volumes: Vec<Convex> = Vec::new();
+        let camera = editor_camera(
+            scene.camera.position.into(),
+            scene.camera.rotation.into(),
+            FOCAL_DISTANCE,
         );
-        camera.matrix = camera.matrix.transpose();
         for solid in &scene.world.solids {
+            if solid.kind == SolidKind::Fog {
+                volumes.push(solid_volume(solid));
+                continue;
+            }
             for face in &solid.faces {
+                //Counterclockwise
+                let [point0, point1, point2, point3] =
+                    face.indices.map(|id| point_position(&solid.points[id as usize]));
+                //Portals are invisible, whatever their texture
+                if face.kind == FaceKind::Portal {
+                    portals.push(Portal::new(point0, point1 - point0, point3 - point0));
+                    continue;
+                }
                 if face.texture.0 == 0 {
                     continue;
                 }
-                //Counterclockwise
-                let points: Vec<&Point> = face
+                //Texture coordinates are projected in the coordinates of the editor
+                let point_positions: Vec<Vec3> = face
                     .indices
                     .iter()
-                    .map(|id| &solid.points[(*id) as usize])
-                    .collect();
-                let point_positions: Vec<Vec3> = points
-                    .iter()
-                    .map(|point| (point.position).into())
-                    .map(|point: Vec3| point/128.0)
+                    .map(|id| Vec3::from(solid.points[*id as usize].position) / 128.0)
                     .collect();
                 let edge0 = point_positions[1] - point_positions[0];
                 let edge1 = point_positions[3] - point_positions[0];
                 let normal = edge0.cross(edge1).normalized();
 
-                let uv0 = texcoord(point_positions[0], normal);
-                let uv1 = texcoord(point_positions[1], normal);
-                let uv2 = texcoord(point_positions[2], normal);
-                let uv3 = texcoord(point_positions[3], normal);
-
-                let point0 = {
-                    let mut a = point_positions[0];
-                    a[2] = -a[2];
-                    a
-                };
-                let point1 = {
-                    let mut a = point_positions[1];
-                    a[2] = -a[2];
-                    a
-                };
-                let point2 = {
-                    let mut a = point_positions[2];
-                    a[2] = -a[2];
-                    a
-                };
-                let point3 = {
-                    let mut a = point_positions[3];
-                    a[2] = -a[2];
-                    a
-                };
-                let triangle1 = Triangle::new(
+                let uv0 = texcoord(point_positions[0], normal, &face.transform);
+                let uv1 = texcoord(point_positions[1], normal, &face.transform);
+                let uv2 = texcoord(point_positions[2], normal, &face.transform);
+                let uv3 = texcoord(point_positions[3], normal, &face.transform);
+
+                let mut triangle1 = Triangle::new(
                     [point0, point2, point1],
                     [uv0, uv2, uv1],
                     AMDLTextureType::diffuse(face.texture.0),
                     Material::Diffuse,
                 );
-                let triangle2 = Triangle::new(
+                let mut triangle2 = Triangle::new(
                     [point0, point3, point2],
                     [uv0, uv3, uv2],
                     AMDLTextureType::diffuse(face.texture.0),
                     Material::Diffuse,
                 );
+                triangle1.wrap = wrap_mode(face.transform.wrap);
+                triangle2.wrap = wrap_mode(face.transform.wrap);
                 triangles.push(triangle1);
                 triangles.push(triangle2);
             }
@@ -142,11 +204,27 @@ impl ASCNLoader {
                 inverse_matrix: inverse_matrix.into()
             }
         }).collect();
-        Ok(Self { camera, triangles, prop_requests })
+        Ok(Self { camera, triangles, prop_requests, portals, volumes })
+    }
+    pub fn get_textures(&self) -> HashSet<TextureID> {
+        textures_of(&self.triangles)
+    }
+    /// Applies the cutouts and sidedness of their materials to the solids
+    pub fn load_materials(&mut self, repo: &TextureRepository) {
+        for triangle in &mut self.triangles {
+            triangle.load_material(repo);
+        }
     }
     pub fn get_prop_requests(&self) -> &Vec<PropRequest>{
         &self.prop_requests
     }
+    pub fn get_portals(&self) -> &Vec<Portal>{
+        &self.portals
+    }
+    /// Regions of the fog solids, see [`SolidKind::Fog`]
+    pub fn get_volumes(&self) -> &Vec<Convex>{
+        &self.volumes
+    }
 }
 
 impl Loader for ASCNLoader {
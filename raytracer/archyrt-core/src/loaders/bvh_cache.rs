@@ -0,0 +1,77 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use crate::{intersectables::bvh::BVH, utilities::files::write_atomic};
+
+/// Changes whenever BVHs are encoded or built differently, so stale entries are never loaded
+const FORMAT_VERSION: u32 = 5;
+
+fn key(hasher: Sha256) -> String {
+    let hash: String = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("v{}-{}", FORMAT_VERSION, hash)
+}
+
+/// Key of the BVH built from the contents of an `.ascn` file
+pub fn content_key(data: &[u8]) -> String {
+    key(Sha256::new_with_prefix(data))
+}
+
+/// Key of the BVH built from the contents of an `.amdl` file, whose meshes got their materials from `materials`:
+/// the texture ID of each mesh and whether it has an emissive texture
+pub fn material_key(data: &[u8], materials: &[(u32, bool)]) -> String {
+    let mut hasher = Sha256::new_with_prefix(data);
+    for (texture, emissive) in materials {
+        hasher.update(texture.to_le_bytes());
+        hasher.update([*emissive as u8]);
+    }
+    key(hasher)
+}
+
+/// Built BVHs stored in a local directory
+pub struct BVHCache {
+    directory: PathBuf,
+}
+
+impl BVHCache {
+    pub fn new<P: AsRef<Path>>(directory: P) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+        }
+    }
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(key).with_extension("bvh")
+    }
+    /// Cached BVH, `None` if it is missing or can't be decoded
+    pub fn load(&self, key: &str) -> Option<BVH> {
+        let data = fs::read(self.path(key)).ok()?;
+        BVH::decode(&data).ok()
+    }
+    /// Stores an encoded BVH, through a temporary file so that other workers never read a partial entry
+    pub fn store(&self, key: &str, data: &[u8]) -> Result<()> {
+        write_atomic(self.path(key), data)
+    }
+    /// Loads the cached BVH, or builds and stores it if it is missing
+    pub fn get_or_build<F>(&self, key: &str, build: F) -> Result<Option<BVH>>
+    where
+        F: FnOnce() -> Result<Option<BVH>>,
+    {
+        if let Some(bvh) = self.load(key) {
+            return Ok(Some(bvh));
+        }
+        let bvh = build()?;
+        if let Some(bvh) = &bvh {
+            //Failing to store it only means building it again next time
+            bvh.encode().and_then(|data| self.store(key, &data)).ok();
+        }
+        Ok(bvh)
+    }
+}
@@ -1,11 +1,31 @@
-use crate::{api::camera::Camera, intersectables::triangle::Triangle};
+use std::collections::HashSet;
+
+use crate::{
+    api::camera::Camera, intersectables::triangle::Triangle,
+    renderers::path_tracer::Material, textures::TextureID,
+};
 
 pub mod ascn;
 pub mod gltf;
 pub mod amdl;
+pub mod bvh_cache;
+#[cfg(test)]
+mod tests;
 
 pub trait Loader {
     type C: Camera;
     fn get_triangles(&self) -> &Vec<Triangle>;
     fn get_camera(&self) -> &Self::C;
 }
+
+/// Textures the triangles are drawn with
+pub fn textures_of<'a>(triangles: impl IntoIterator<Item = &'a Triangle>) -> HashSet<TextureID> {
+    let mut textures = HashSet::new();
+    for triangle in triangles {
+        textures.insert(triangle.texture);
+        if let Material::DiffuseAndEmissive { emissive_texture, .. } = triangle.material {
+            textures.insert(emissive_texture);
+        }
+    }
+    textures
+}
@@ -0,0 +1,243 @@
+mod prop_repo {
+    use std::sync::Arc;
+
+    use crate::{
+        intersectables::triangle::Triangle,
+        loaders::amdl::{
+            repo::{PropRepository, PropRequest, PropType},
+            AMDLLoader,
+        },
+        utilities::math::{Matrix3x3, Vec3},
+    };
+
+    fn request(position: Vec3) -> PropRequest {
+        PropRequest {
+            prop: PropType::default(0),
+            position,
+            matrix: Matrix3x3::identity(),
+            inverse_matrix: Matrix3x3::identity(),
+        }
+    }
+
+    #[test]
+    fn instances_share_bvh() {
+        let mut repo = PropRepository::new();
+        repo.insert(
+            PropType::default(0),
+            AMDLLoader {
+                triangles: vec![Triangle::default()],
+            },
+        );
+        let a = repo.fulfill(&request(Vec3::default())).unwrap();
+        let b = repo.fulfill(&request(Vec3::new(1.0, 0.0, 0.0))).unwrap();
+        assert!(Arc::ptr_eq(&a.inner.inner, &b.inner.inner));
+    }
+}
+
+mod bvh_cache {
+    use std::env;
+
+    use asset::{BoundingBox, Prop, PropMesh, PropVertex, TextureID};
+    use cgmath::{vec2, vec3};
+
+    use crate::{
+        intersectables::{bvh::BVH, triangle::Triangle},
+        loaders::{
+            amdl::repo::{PropRepository, PropRequest, PropType},
+            ascn::amdl_textures::AMDLTextureType,
+            bvh_cache::{content_key, material_key, BVHCache},
+        },
+        renderers::path_tracer::Material,
+        textures::{texture::Texture, texture_repo::TextureRepository},
+        utilities::{
+            math::{Matrix3x3, Vec3},
+            ray::{Intersectable, Ray},
+        },
+    };
+
+    fn bvh() -> Option<BVH> {
+        let triangles = [-1.0, 0.0, 1.0].map(|x| {
+            let mut triangle = Triangle::default();
+            triangle.a += Vec3::new(x, 0.0, x);
+            triangle.b += Vec3::new(x, 0.0, x);
+            triangle.c += Vec3::new(x, 0.0, x);
+            triangle
+        });
+        BVH::from_triangles(&triangles)
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(content_key(b"scene"), content_key(b"scene"));
+        assert_ne!(content_key(b"scene"), content_key(b"scene2"));
+    }
+    #[test]
+    fn round_trip() {
+        let bvh = bvh().unwrap();
+        let decoded = BVH::decode(&bvh.encode().unwrap()).unwrap();
+        assert_eq!(decoded.triangles().len(), 3);
+        let ray = Ray::new(Vec3::new(0.0, -0.5, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let a = bvh.intersect(ray).map(|i| i.get_distance());
+        let b = decoded.intersect(ray).map(|i| i.get_distance());
+        assert!(a.is_some());
+        assert_eq!(a, b);
+    }
+    #[test]
+    fn builds_once() {
+        let directory = env::temp_dir().join(format!("archyrt-bvh-cache-{}", std::process::id()));
+        let cache = BVHCache::new(&directory);
+        let key = content_key(b"scene");
+        let built = cache.get_or_build(&key, || Ok(bvh())).unwrap();
+        assert!(built.is_some());
+        let loaded = cache
+            .get_or_build(&key, || panic!("The BVH should have been cached"))
+            .unwrap();
+        assert_eq!(loaded.unwrap().triangles().len(), 3);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+    #[test]
+    fn material_keys() {
+        assert_eq!(
+            material_key(b"prop", &[(1, false)]),
+            material_key(b"prop", &[(1, false)])
+        );
+        assert_ne!(
+            material_key(b"prop", &[(1, false)]),
+            material_key(b"prop", &[(1, true)])
+        );
+        assert_ne!(material_key(b"prop", &[]), content_key(b"prop2"));
+    }
+    #[test]
+    fn materials_change() {
+        let directory = env::temp_dir().join(format!("archyrt-prop-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let vertex = |x: f32| PropVertex {
+            position: vec3(x, 0.0, x),
+            normal: vec3(0.0, 1.0, 0.0),
+            texcoord: vec2(0.0, 0.0),
+        };
+        let prop = Prop {
+            bounds: BoundingBox {
+                min: vec3(0.0, 0.0, 0.0),
+                max: vec3(1.0, 0.0, 1.0),
+            },
+            meshes: vec![PropMesh {
+                texture: TextureID(1),
+                vertices: vec![vertex(0.0), vertex(1.0), vertex(0.5)],
+                triangles: vec![[0, 1, 2]],
+            }],
+        };
+        let path = directory.join("lamp.amdl");
+        std::fs::write(&path, prop.encode().unwrap()).unwrap();
+        //Materials of the emissive triangles of the prop loaded through the cache
+        let emitters = |textures: &mut TextureRepository| {
+            let mut props = PropRepository::new();
+            props.register(PropType::default(0), &path);
+            props.set_cache(Some(BVHCache::new(directory.join("cache"))));
+            let request = PropRequest {
+                prop: PropType::default(0),
+                position: Vec3::default(),
+                matrix: Matrix3x3::identity(),
+                inverse_matrix: Matrix3x3::identity(),
+            };
+            props.require(&[request], textures).unwrap();
+            let prop = props.get(PropType::default(0)).unwrap();
+            prop.emitters.iter().map(|triangle| triangle.material).collect::<Vec<_>>()
+        };
+        let mut textures = TextureRepository::new();
+        assert!(emitters(&mut textures).is_empty());
+        assert!(emitters(&mut textures).is_empty());
+        //Adding an emissive texture misses the cached BVH of the diffuse prop
+        textures.insert(AMDLTextureType::emissive(1), Texture::new(1, 1));
+        let materials = emitters(&mut textures);
+        assert_eq!(materials.len(), 1);
+        //Its light is in the group of the prop
+        assert!(matches!(
+            materials[0],
+            Material::DiffuseAndEmissive { prop: Some(prop), .. } if prop == PropType::default(0)
+        ));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
+
+mod ascn {
+    use asset::{
+        scene::{
+            Camera, Face, FaceKind, Point, Scene, Solid, SolidKind, TextureTransform, TextureWrap,
+            World,
+        },
+        TextureID,
+    };
+    use cgmath::{vec2, vec3};
+
+    use crate::{
+        loaders::{ascn::ASCNLoader, Loader},
+        textures::samplers::WrapMode,
+    };
+
+    /// Scene of a one meter cube
+    fn cube(kind: SolidKind, wrap: TextureWrap) -> ASCNLoader {
+        let points = [
+            vec3(0, 0, 0),
+            vec3(1, 0, 0),
+            vec3(1, 0, 1),
+            vec3(0, 0, 1),
+            vec3(0, 1, 0),
+            vec3(1, 1, 0),
+            vec3(1, 1, 1),
+            vec3(0, 1, 1),
+        ]
+        .map(|point| Point {
+            position: point * 128,
+        });
+        let faces = [
+            [1, 5, 6, 2],
+            [4, 0, 3, 7],
+            [5, 4, 7, 6],
+            [0, 1, 2, 3],
+            [3, 2, 6, 7],
+            [1, 0, 4, 5],
+        ]
+        .map(|indices| Face {
+            texture: TextureID(1),
+            indices,
+            transform: TextureTransform {
+                wrap,
+                ..Default::default()
+            },
+            kind: FaceKind::Surface,
+        });
+        let scene = Scene {
+            camera: Camera {
+                position: vec3(0.0, 0.0, 0.0),
+                rotation: vec2(0.0, 0.0),
+            },
+            world: World {
+                solids: vec![Solid {
+                    points,
+                    faces,
+                    kind,
+                }],
+                props: Vec::new(),
+            },
+        };
+        ASCNLoader::from_bytes(&scene.encode().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let loader = cube(SolidKind::Solid, TextureWrap::Mirror);
+        let triangles = loader.get_triangles();
+        assert_eq!(triangles.len(), 12);
+        assert!(triangles.iter().all(|triangle| triangle.wrap == WrapMode::Mirror));
+        assert!(loader.get_volumes().is_empty());
+    }
+
+    #[test]
+    fn fog() {
+        //Fog solids are volumes instead of surfaces
+        let loader = cube(SolidKind::Fog, TextureWrap::Repeat);
+        assert!(loader.get_triangles().is_empty());
+        assert_eq!(loader.get_volumes().len(), 1);
+    }
+}
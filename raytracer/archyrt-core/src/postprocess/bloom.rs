@@ -0,0 +1,147 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    utilities::math::{Vec2, Vec3},
+    vector,
+};
+
+use super::{luminance, Effect, FloatImage};
+
+/// Parts of the image brighter than a threshold, with their colors
+fn highlights(image: &FloatImage, threshold: f64) -> FloatImage {
+    let mut bright = image.clone();
+    for pixel in &mut bright.pixels {
+        let luminance = luminance(*pixel);
+        *pixel = if luminance > threshold {
+            *pixel * ((luminance - threshold) / luminance)
+        } else {
+            Vec3::default()
+        };
+    }
+    bright
+}
+
+/// Separable gaussian blur, `sigma` is in pixels
+pub fn blur(image: &FloatImage, sigma: f64) -> FloatImage {
+    if sigma <= 0.0 {
+        return image.clone();
+    }
+    let radius = (sigma * 3.0).ceil() as isize;
+    let kernel: Vec<f64> = (-radius..=radius)
+        .map(|i| (-((i * i) as f64) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f64 = kernel.iter().sum();
+    let kernel: Vec<f64> = kernel.into_iter().map(|w| w / total).collect();
+    let pass = |image: &FloatImage, (dx, dy): (isize, isize)| {
+        let (w, h) = (image.width as isize, image.height as isize);
+        image.map(|pos| {
+            let (x, y) = (pos.x() as isize, pos.y() as isize);
+            let mut sum = Vec3::default();
+            for (weight, i) in kernel.iter().zip(-radius..=radius) {
+                //Edges are extended
+                let sx = (x + i * dx).clamp(0, w - 1);
+                let sy = (y + i * dy).clamp(0, h - 1);
+                sum += image.get(sx as usize, sy as usize) * *weight;
+            }
+            sum
+        })
+    };
+    pass(&pass(image, (1, 0)), (0, 1))
+}
+
+/// Soft glow around highlights, like light scattered in the lens
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bloom {
+    /// Luminance above which pixels glow
+    pub threshold: f64,
+    pub intensity: f64,
+    /// Size of the glow relative to the image's width
+    pub radius: f64,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            intensity: 0.1,
+            radius: 0.02,
+        }
+    }
+}
+
+impl Effect for Bloom {
+    fn apply(&self, image: &mut FloatImage) {
+        let bright = highlights(image, self.threshold);
+        let glow = blur(&bright, self.radius * image.width as f64);
+        for (pixel, glow) in image.pixels.iter_mut().zip(glow.pixels) {
+            *pixel += glow * self.intensity;
+        }
+    }
+}
+
+/// Star shaped streaks from highlights, like the diffraction of an aperture's blades
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Glare {
+    /// Luminance above which pixels streak
+    pub threshold: f64,
+    pub intensity: f64,
+    /// Length of the streaks relative to the image's width
+    pub length: f64,
+    /// Number of streaks, spread evenly around highlights
+    pub streaks: usize,
+    /// Rotation of the first streak, in degrees
+    pub angle: f64,
+}
+
+impl Default for Glare {
+    fn default() -> Self {
+        Self {
+            threshold: 2.0,
+            intensity: 0.05,
+            length: 0.1,
+            streaks: 6,
+            angle: 15.0,
+        }
+    }
+}
+
+/// Samples taken along each streak
+const GLARE_STEPS: usize = 32;
+
+impl Effect for Glare {
+    fn apply(&self, image: &mut FloatImage) {
+        if self.streaks == 0 {
+            return;
+        }
+        let bright = highlights(image, self.threshold);
+        let length = self.length * image.width as f64;
+        let directions: Vec<Vec2> = (0..self.streaks)
+            .map(|i| {
+                let angle = self.angle.to_radians() + 2.0 * PI * i as f64 / self.streaks as f64;
+                vector![angle.cos(), angle.sin()]
+            })
+            .collect();
+        //Weights fall off exponentially along the streaks
+        let weights: Vec<f64> = (1..=GLARE_STEPS)
+            .map(|step| (-4.0 * step as f64 / GLARE_STEPS as f64).exp())
+            .collect();
+        let total = weights.iter().sum::<f64>() * self.streaks as f64;
+        let streaks = bright.map(|pos| {
+            let mut sum = Vec3::default();
+            for direction in &directions {
+                for (step, weight) in weights.iter().enumerate() {
+                    let distance = length * (step + 1) as f64 / GLARE_STEPS as f64;
+                    sum += bright.sample(pos - *direction * distance) * *weight;
+                }
+            }
+            sum / total
+        });
+        for (pixel, streak) in image.pixels.iter_mut().zip(streaks.pixels) {
+            *pixel += streak * self.intensity * self.streaks as f64;
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utilities::math::Vec3;
+
+use super::{luminance, Effect, FloatImage};
+
+/// Luminance contrast is pivoted around
+const MIDDLE_GREY: f64 = 0.18;
+
+/// Scales contrast around middle grey, working on logarithmic exposure so highlights keep their detail
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Contrast {
+    /// 1 leaves the image unchanged
+    pub amount: f64,
+}
+
+impl Default for Contrast {
+    fn default() -> Self {
+        Self { amount: 1.1 }
+    }
+}
+
+impl Effect for Contrast {
+    fn apply(&self, image: &mut FloatImage) {
+        for pixel in &mut image.pixels {
+            let luminance = luminance(*pixel);
+            if luminance > 0.0 {
+                let graded = MIDDLE_GREY * (luminance / MIDDLE_GREY).powf(self.amount);
+                *pixel *= graded / luminance;
+            }
+        }
+    }
+}
+
+/// Moves colors away from or towards grey of the same luminance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Saturation {
+    /// 0 is greyscale and 1 leaves the image unchanged
+    pub amount: f64,
+}
+
+impl Default for Saturation {
+    fn default() -> Self {
+        Self { amount: 1.1 }
+    }
+}
+
+impl Effect for Saturation {
+    fn apply(&self, image: &mut FloatImage) {
+        for pixel in &mut image.pixels {
+            let grey = Vec3::from_single(luminance(*pixel));
+            *pixel = (grey + (*pixel - grey) * self.amount).max(Vec3::default());
+        }
+    }
+}
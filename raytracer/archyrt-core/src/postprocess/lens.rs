@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utilities::math::{Vec2, Vec3};
+
+use super::{Effect, FloatImage};
+
+/// Position a pixel is read from after radially scaling its offset from the center.
+/// `k` is the change in scale at the corners, positive values pull the image inwards.
+fn distort(image: &FloatImage, pos: Vec2, k: f64) -> Vec2 {
+    let center = image.center();
+    let offset = pos - center;
+    let r = offset.length() / image.half_diagonal();
+    center + offset * (1.0 + k * r * r)
+}
+
+/// Darkens the image towards its corners
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Vignette {
+    /// Darkening at the corners, 1 makes them black
+    pub strength: f64,
+    /// How quickly the darkening falls off towards the center
+    pub falloff: f64,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            strength: 0.3,
+            falloff: 2.0,
+        }
+    }
+}
+
+impl Effect for Vignette {
+    fn apply(&self, image: &mut FloatImage) {
+        let center = image.center();
+        let half_diagonal = image.half_diagonal();
+        *image = image.map(|pos| {
+            let r = (pos - center).length() / half_diagonal;
+            let factor = (1.0 - self.strength * r.powf(self.falloff)).max(0.0);
+            image.sample(pos) * factor
+        });
+    }
+}
+
+/// Barrel or pincushion distortion of the lens
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LensDistortion {
+    /// Positive values bulge the image like a wide angle lens, negative ones pinch it
+    pub amount: f64,
+}
+
+impl Default for LensDistortion {
+    fn default() -> Self {
+        Self { amount: 0.05 }
+    }
+}
+
+impl Effect for LensDistortion {
+    fn apply(&self, image: &mut FloatImage) {
+        *image = image.map(|pos| image.sample(distort(image, pos, self.amount)));
+    }
+}
+
+/// Color fringes towards the edges, from red and blue being magnified differently
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChromaticAberration {
+    /// Difference in the magnification of red and blue at the corners
+    pub strength: f64,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self { strength: 0.005 }
+    }
+}
+
+impl Effect for ChromaticAberration {
+    fn apply(&self, image: &mut FloatImage) {
+        *image = image.map(|pos| {
+            let red = image.sample(distort(image, pos, -self.strength / 2.0));
+            let green = image.sample(pos);
+            let blue = image.sample(distort(image, pos, self.strength / 2.0));
+            Vec3::new(red.x(), green.y(), blue.z())
+        });
+    }
+}
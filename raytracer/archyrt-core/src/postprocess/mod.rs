@@ -0,0 +1,157 @@
+use anyhow::Result;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    utilities::math::{Vec2, Vec3},
+    vector,
+};
+
+pub mod bloom;
+pub mod grading;
+pub mod lens;
+#[cfg(test)]
+mod tests;
+
+use self::{
+    bloom::{Bloom, Glare},
+    grading::{Contrast, Saturation},
+    lens::{ChromaticAberration, LensDistortion, Vignette},
+};
+
+/// Linear HDR image, stored row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Vec3>,
+}
+
+impl FloatImage {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Vec3::default(); width * height],
+        }
+    }
+    /// Image from three floats per pixel, as returned by the raw collector
+    pub fn from_raw(width: usize, height: usize, data: &[f32]) -> Self {
+        let pixels = data
+            .chunks(3)
+            .map(|c| Vec3::new(c[0] as f64, c[1] as f64, c[2] as f64))
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+    pub fn to_raw(&self) -> Vec<f32> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| pixel.inner.map(|c| c as f32))
+            .collect()
+    }
+    pub fn get(&self, x: usize, y: usize) -> Vec3 {
+        self.pixels[y * self.width + x]
+    }
+    /// Bilinearly filtered color at a position in pixels, edges are extended
+    pub fn sample(&self, pos: Vec2) -> Vec3 {
+        if self.pixels.is_empty() {
+            return Vec3::default();
+        }
+        let x = (pos.x() - 0.5).clamp(0.0, (self.width - 1) as f64);
+        let y = (pos.y() - 0.5).clamp(0.0, (self.height - 1) as f64);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+        let top = self.get(x0, y0) * (1.0 - fx) + self.get(x1, y0) * fx;
+        let bottom = self.get(x0, y1) * (1.0 - fx) + self.get(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+    pub fn center(&self) -> Vec2 {
+        vector![self.width as f64 / 2.0, self.height as f64 / 2.0]
+    }
+    /// Distance from the center to the corners, in pixels
+    pub fn half_diagonal(&self) -> f64 {
+        self.center().length()
+    }
+    /// New image from a function of each pixel's center, in pixels, evaluated in parallel
+    pub fn map(&self, f: impl Fn(Vec2) -> Vec3 + Sync) -> Self {
+        let width = self.width;
+        let pixels = (0..self.width * self.height)
+            .into_par_iter()
+            .map(|i| f(vector![(i % width) as f64 + 0.5, (i / width) as f64 + 0.5]))
+            .collect();
+        Self {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+/// Relative luminance of a color of the working space, ACEScg
+pub fn luminance(color: Vec3) -> f64 {
+    color.dot(Vec3::new(0.2722287168, 0.6740817658, 0.0536895174))
+}
+
+/// Step of the post-processing chain
+pub trait Effect {
+    fn apply(&self, image: &mut FloatImage);
+}
+
+/// Effects which can be listed in a render job's chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PostEffect {
+    Bloom(Bloom),
+    Glare(Glare),
+    Vignette(Vignette),
+    LensDistortion(LensDistortion),
+    ChromaticAberration(ChromaticAberration),
+    Contrast(Contrast),
+    Saturation(Saturation),
+}
+
+impl Effect for PostEffect {
+    fn apply(&self, image: &mut FloatImage) {
+        match self {
+            Self::Bloom(effect) => effect.apply(image),
+            Self::Glare(effect) => effect.apply(image),
+            Self::Vignette(effect) => effect.apply(image),
+            Self::LensDistortion(effect) => effect.apply(image),
+            Self::ChromaticAberration(effect) => effect.apply(image),
+            Self::Contrast(effect) => effect.apply(image),
+            Self::Saturation(effect) => effect.apply(image),
+        }
+    }
+}
+
+/// Effects applied in order to the float buffer, before tonemapping.
+///
+/// Described as a JSON list such as `[{"type": "bloom", "intensity": 0.1}, {"type": "vignette"}]`,
+/// settings which are left out take their default values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PostProcess {
+    pub effects: Vec<PostEffect>,
+}
+
+impl PostProcess {
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+impl Effect for PostProcess {
+    fn apply(&self, image: &mut FloatImage) {
+        for effect in &self.effects {
+            effect.apply(image);
+        }
+    }
+}
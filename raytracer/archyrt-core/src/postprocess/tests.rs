@@ -0,0 +1,170 @@
+mod chain {
+    use crate::postprocess::{
+        bloom::Bloom, lens::Vignette, Effect, FloatImage, PostEffect, PostProcess,
+    };
+
+    #[test]
+    fn from_json() {
+        let chain = PostProcess::from_json(
+            r#"[{"type": "bloom", "intensity": 0.5}, {"type": "vignette"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            chain.effects,
+            vec![
+                PostEffect::Bloom(Bloom {
+                    intensity: 0.5,
+                    ..Default::default()
+                }),
+                PostEffect::Vignette(Vignette::default()),
+            ]
+        );
+        assert_eq!(
+            PostProcess::from_json(&chain.to_json().unwrap()).unwrap(),
+            chain
+        );
+        assert!(PostProcess::from_json(r#"[{"type": "sepia"}]"#).is_err());
+    }
+    #[test]
+    fn empty() {
+        let mut image = FloatImage::from_raw(2, 1, &[0.5, 1.0, 2.0, 0.0, 0.1, 0.2]);
+        let original = image.clone();
+        PostProcess::default().apply(&mut image);
+        assert_eq!(image, original);
+        assert_eq!(image.to_raw(), vec![0.5, 1.0, 2.0, 0.0, 0.1, 0.2]);
+    }
+}
+
+mod bloom {
+    use crate::{
+        postprocess::{
+            bloom::{blur, Bloom, Glare},
+            Effect, FloatImage,
+        },
+        utilities::math::Vec3,
+    };
+
+    /// Dark image with a single bright pixel in the middle
+    fn lamp() -> FloatImage {
+        let mut image = FloatImage::new(21, 21);
+        image.pixels[10 * 21 + 10] = Vec3::from_single(100.0);
+        image
+    }
+
+    #[test]
+    fn blur_keeps_energy() {
+        let image = lamp();
+        let blurred = blur(&image, 2.0);
+        let sum = |image: &FloatImage| image.pixels.iter().map(|p| p.x()).sum::<f64>();
+        assert!((sum(&blurred) - sum(&image)).abs() < 1e-6);
+        assert!(blurred.get(10, 10).x() < 100.0);
+        assert!(blurred.get(12, 10).x() > 0.0);
+    }
+    #[test]
+    fn spreads_highlights() {
+        let mut image = lamp();
+        image.pixels[0] = Vec3::from_single(0.5);
+        Bloom {
+            threshold: 1.0,
+            intensity: 1.0,
+            radius: 0.1,
+        }
+        .apply(&mut image);
+        assert!(image.get(12, 10).x() > 0.0);
+        //Pixels under the threshold don't glow
+        assert_eq!(image.get(0, 1), Vec3::default());
+    }
+    #[test]
+    fn glare_streaks() {
+        let mut image = lamp();
+        Glare {
+            streaks: 4,
+            angle: 0.0,
+            length: 0.5,
+            ..Default::default()
+        }
+        .apply(&mut image);
+        //Along the streaks, but not between them
+        assert!(image.get(15, 10).x() > 0.0);
+        assert!(image.get(10, 15).x() > 0.0);
+        assert_eq!(image.get(15, 15), Vec3::default());
+    }
+}
+
+mod lens {
+    use crate::{
+        postprocess::{
+            lens::{ChromaticAberration, LensDistortion, Vignette},
+            Effect, FloatImage,
+        },
+        utilities::math::Vec3,
+    };
+
+    fn grey() -> FloatImage {
+        let mut image = FloatImage::new(9, 9);
+        image.pixels.fill(Vec3::from_single(1.0));
+        image
+    }
+
+    #[test]
+    fn vignette() {
+        let mut image = grey();
+        Vignette {
+            strength: 1.0,
+            falloff: 2.0,
+        }
+        .apply(&mut image);
+        assert_eq!(image.get(4, 4), Vec3::from_single(1.0));
+        assert!(image.get(0, 0).x() < 0.3);
+        assert!(image.get(0, 4).x() > image.get(0, 0).x());
+    }
+    #[test]
+    fn distortion_keeps_center() {
+        let mut image = FloatImage::new(9, 9);
+        for (i, pixel) in image.pixels.iter_mut().enumerate() {
+            *pixel = Vec3::from_single(i as f64);
+        }
+        let original = image.clone();
+        LensDistortion { amount: -0.2 }.apply(&mut image);
+        assert_eq!(image.get(4, 4), original.get(4, 4));
+        assert_ne!(image.get(0, 4), original.get(0, 4));
+    }
+    #[test]
+    fn aberration_separates_channels() {
+        let mut image = FloatImage::new(9, 1);
+        image.pixels[7] = Vec3::from_single(1.0);
+        ChromaticAberration { strength: 0.5 }.apply(&mut image);
+        let pixel = image.get(7, 0);
+        assert_eq!(pixel.y(), 1.0);
+        assert_ne!(pixel.x(), pixel.z());
+    }
+}
+
+mod grading {
+    use crate::{
+        postprocess::{
+            grading::{Contrast, Saturation},
+            luminance, Effect, FloatImage,
+        },
+        utilities::math::Vec3,
+    };
+
+    #[test]
+    fn contrast() {
+        let mut image =
+            FloatImage::from_raw(3, 1, &[0.18, 0.18, 0.18, 0.01, 0.01, 0.01, 2.0, 2.0, 2.0]);
+        Contrast { amount: 1.5 }.apply(&mut image);
+        assert!((image.get(0, 0).x() - 0.18).abs() < 1e-6);
+        assert!(image.get(1, 0).x() < 0.01);
+        assert!(image.get(2, 0).x() > 2.0);
+    }
+    #[test]
+    fn saturation() {
+        let color = Vec3::new(0.8, 0.4, 0.1);
+        let mut image = FloatImage::from_raw(1, 1, &[0.8, 0.4, 0.1]);
+        Saturation { amount: 0.0 }.apply(&mut image);
+        let grey = image.get(0, 0);
+        assert!((grey.x() - grey.z()).abs() < 1e-6);
+        assert!((luminance(grey) - luminance(color)).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,87 @@
+use rand_distr::{Distribution, UnitSphere};
+
+use crate::{
+    api::{
+        fragment_render::FragmentContext,
+        light_layers::{LightGroup, LightLayers},
+    },
+    utilities::{
+        math::Vec3,
+        random,
+        ray::{Intersectable, Ray},
+    },
+};
+
+use super::integrator::{Integrator, Scene, EPSILON};
+
+/// Shades surfaces by how much of the hemisphere above them is unoccluded nearby, as if lit by a uniform white sky
+pub struct AmbientOcclusion {
+    /// Rays cast per fragment
+    pub samples: usize,
+    /// Occluders further away than this are ignored
+    pub distance: f64,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        Self {
+            samples: 16,
+            distance: 1.0,
+        }
+    }
+}
+
+/// Fraction of cosine weighted rays from a point which aren't blocked within a distance
+pub fn visibility<K: Intersectable>(
+    object: &K,
+    pos: Vec3,
+    normal: Vec3,
+    samples: usize,
+    distance: f64,
+) -> f64 {
+    let origin = pos + normal * EPSILON;
+    let mut rng = random::rng();
+    let mut visible = 0;
+    for _ in 0..samples {
+        //Cosine weighted direction
+        let p: [f64; 3] = UnitSphere.sample(&mut rng);
+        let direction = normal + Vec3::new(p[0], p[1], p[2]);
+        if direction.length_squared() < EPSILON {
+            continue;
+        }
+        let ray = Ray::new(origin, direction.normalized());
+        match object.intersect(ray) {
+            Some(occluder) if occluder.get_distance() < distance => {}
+            _ => visible += 1,
+        }
+    }
+    visible as f64 / samples.max(1) as f64
+}
+
+impl Integrator for AmbientOcclusion {
+    fn integrate<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        ray: Ray,
+        _spread: f64,
+    ) -> LightLayers {
+        let mut layers = LightLayers::new();
+        let intersection = match scene.object.intersect(ray) {
+            Some(intersection) => intersection,
+            None => {
+                layers.add(LightGroup::Sky, scene.sky_color(ctx, ray.direction, 0));
+                return layers;
+            }
+        };
+        let visibility = visibility(
+            &scene.object,
+            intersection.get_pos(),
+            intersection.get_normal(),
+            self.samples,
+            self.distance,
+        );
+        layers.add(LightGroup::Sky, Vec3::from_single(visibility));
+        layers
+    }
+}
@@ -0,0 +1,525 @@
+use std::f64::consts::PI;
+
+use rand_distr::{Distribution, UnitSphere};
+
+use crate::{
+    api::{
+        fragment_render::FragmentContext,
+        light_layers::{LightGroup, LightLayers},
+    },
+    intersectables::{area_light::AreaLights, bvh::record_depth},
+    textures::color_provider::{Lobe, RayCone},
+    utilities::{
+        math::Vec3,
+        random,
+        ray::{Intersectable, Ray},
+    },
+};
+
+use super::{
+    integrator::{Integrator, Scene, EPSILON, HEMISPHERE_PDF},
+    path_tracer::Material,
+};
+
+/// Bidirectional path tracing with multiple importance sampling, for scenes lit through indirect bounces.
+///
+/// Paths are traced from the camera and from a point on [`Scene::lights`], and every pair of their vertices is
+/// connected. Each connection is weighted by the balance heuristic against the other ways the same path could have been
+/// sampled. The sky isn't part of light paths, it is sampled through portals like in [`super::path_tracer::PathTracer`].
+/// Specular reflections can't be connected to, so light through them is only found by extending paths.
+/// [`Scene::media`] and [`Scene::sun`] are ignored, see [`super::integrator::NamedIntegrator::renders_atmosphere`].
+pub struct BidirectionalPathTracer {
+    /// Maximum number of surfaces between the camera and a light
+    pub bounces: usize,
+}
+
+impl Default for BidirectionalPathTracer {
+    fn default() -> Self {
+        Self { bounces: 5 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+#[derive(Debug, Clone)]
+struct Vertex {
+    kind: VertexKind,
+    pos: Vec3,
+    /// Normal of the side facing the previous vertex, or of the emitting side of lights
+    normal: Vec3,
+    /// Normal used for shading, after normal and bump mapping
+    shading: Vec3,
+    /// Contribution of the path up to the vertex, divided by the probability of sampling it
+    beta: Vec3,
+    /// Diffuse reflectance, weighted by the probability of picking the diffuse lobe
+    diffuse: Vec3,
+    diffuse_chance: f64,
+    /// Whether the path was reflected specularly at the vertex, which can't be connected to
+    delta: bool,
+    /// Probability density of sampling the vertex from the previous one, per unit area
+    pdf_fwd: f64,
+    /// Probability density of sampling the vertex from the next one, per unit area
+    pdf_rev: f64,
+    /// Light emitted by the surface, and its source
+    emission: Option<(LightGroup, Vec3)>,
+}
+
+impl Vertex {
+    fn new(kind: VertexKind, pos: Vec3, normal: Vec3, beta: Vec3) -> Self {
+        Self {
+            kind,
+            pos,
+            normal,
+            shading: normal,
+            beta,
+            diffuse: Vec3::default(),
+            diffuse_chance: 0.0,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            emission: None,
+        }
+    }
+    fn is_connectable(&self) -> bool {
+        self.kind != VertexKind::Camera && !self.delta
+    }
+    /// Reflectance towards a direction, without the cosine
+    fn f(&self, direction: Vec3) -> Vec3 {
+        if self.kind != VertexKind::Surface
+            || direction.dot(self.normal) <= 0.0
+            || direction.dot(self.shading) <= 0.0
+        {
+            return Vec3::default();
+        }
+        self.diffuse * HEMISPHERE_PDF
+    }
+    /// Light emitted towards a direction
+    fn emitted(&self, direction: Vec3) -> Option<(LightGroup, Vec3)> {
+        if direction.dot(self.normal) <= 0.0 {
+            return None;
+        }
+        self.emission
+    }
+    /// Solid angle probability density of continuing the path towards a direction
+    fn pdf_direction(&self, direction: Vec3) -> f64 {
+        match self.kind {
+            VertexKind::Surface => {
+                if direction.dot(self.normal) > 0.0 && direction.dot(self.shading) > 0.0 {
+                    self.diffuse_chance * HEMISPHERE_PDF
+                } else {
+                    0.0
+                }
+            }
+            VertexKind::Light => self.pdf_emission(direction),
+            VertexKind::Camera => 0.0,
+        }
+    }
+    /// Solid angle probability density of emitting light towards a direction, if the vertex was on a light
+    fn pdf_emission(&self, direction: Vec3) -> f64 {
+        direction.dot(self.normal).max(0.0) / PI
+    }
+    /// Converts a solid angle density of the direction towards the next vertex to a density per unit area
+    fn to_area(&self, pdf: f64, next: &Vertex) -> f64 {
+        let d = next.pos - self.pos;
+        let distance_squared = d.length_squared();
+        if distance_squared == 0.0 {
+            return 0.0;
+        }
+        let pdf = pdf / distance_squared;
+        match next.kind {
+            VertexKind::Camera => pdf,
+            _ => pdf * next.normal.dot(d / distance_squared.sqrt()).abs(),
+        }
+    }
+    /// Probability density per unit area of sampling the next vertex from this one
+    fn pdf(&self, next: &Vertex) -> f64 {
+        let direction = (next.pos - self.pos).normalized();
+        self.to_area(self.pdf_direction(direction), next)
+    }
+    /// Probability density per unit area of sampling the next vertex by emitting light from this one
+    fn pdf_light(&self, next: &Vertex) -> f64 {
+        let direction = (next.pos - self.pos).normalized();
+        self.to_area(self.pdf_emission(direction), next)
+    }
+}
+
+/// Ray extending a path
+struct Segment {
+    ray: Ray,
+    /// Throughput of the path, including the ray's direction
+    beta: Vec3,
+    /// Solid angle probability density of the ray's direction
+    pdf: f64,
+}
+
+/// Zero densities mark specular vertices, which drop out of the weights
+fn remap0(pdf: f64) -> f64 {
+    if pdf != 0.0 {
+        pdf
+    } else {
+        1.0
+    }
+}
+
+/// Cosine weighted direction around the normal, and its probability density
+fn cosine_direction(normal: Vec3) -> Option<(Vec3, f64)> {
+    let p: [f64; 3] = UnitSphere.sample(&mut random::rng());
+    let direction = normal + Vec3::new(p[0], p[1], p[2]);
+    if direction.length_squared() < EPSILON {
+        return None;
+    }
+    let direction = direction.normalized();
+    let pdf = direction.dot(normal) / PI;
+    if pdf <= 0.0 {
+        return None;
+    }
+    Some((direction, pdf))
+}
+
+impl BidirectionalPathTracer {
+    /// Extends a path by reflecting it off surfaces. Paths from the camera sample the sky through portals and where
+    /// they escape.
+    fn walk<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        segment: Segment,
+        spread: f64,
+        path: &mut Vec<Vertex>,
+        sky: &mut LightLayers,
+    ) {
+        let Segment {
+            mut ray,
+            mut beta,
+            mut pdf,
+        } = segment;
+        let camera = path[0].kind == VertexKind::Camera;
+        let bounces = if camera {
+            self.bounces
+        } else {
+            //Connections need at least one surface on the camera path, so light paths can be one bounce shorter
+            self.bounces.saturating_sub(2)
+        };
+        let mut cone_width = 0.0;
+        //Whether the last bounce has already sampled the sky through the portals
+        let mut sampled_portals = false;
+        for bounce in 0..bounces {
+            let intersection = match scene.object.intersect(ray) {
+                Some(intersection) => intersection,
+                None => {
+                    if camera {
+                        let sky_color = scene.sky_color(ctx, ray.direction, bounce);
+                        let weight = if sampled_portals {
+                            let portal_pdf = scene.portal_pdf(ray);
+                            HEMISPHERE_PDF / (portal_pdf + HEMISPHERE_PDF)
+                        } else {
+                            1.0
+                        };
+                        sky.add(LightGroup::Sky, beta * sky_color * weight);
+                    }
+                    break;
+                }
+            };
+            if camera {
+                record_depth(bounce + 1);
+            }
+            cone_width += spread * intersection.get_distance();
+            let cone = RayCone {
+                width: cone_width,
+                anisotropy: scene.anisotropy,
+            };
+            let material = intersection.get_material();
+            let surface = intersection.get_surface(ctx.repo, cone);
+            let color = intersection.get_color_filtered(ctx.repo, cone);
+            let mut vertex = Vertex::new(
+                VertexKind::Surface,
+                intersection.get_pos(),
+                intersection.get_normal().normalized(),
+                beta,
+            );
+            vertex.shading = surface.normal;
+            vertex.emission = material.emission(intersection.ref_color_provider(), ctx.repo, cone);
+            let previous = path.last().unwrap();
+            vertex.pdf_fwd = previous.to_area(pdf, &vertex);
+            if matches!(material, Material::Emissive { .. }) {
+                path.push(vertex);
+                break;
+            }
+            vertex.diffuse_chance = 1.0 - surface.specular_chance();
+            vertex.diffuse = color * surface.occlusion * vertex.diffuse_chance;
+            let lobe = surface.choose_lobe(&mut random::rng());
+            sampled_portals = camera
+                && scene.skybox.is_some()
+                && !scene.portals.is_empty()
+                && lobe == Lobe::Diffuse;
+            if sampled_portals {
+                if let Some((light, portal_pdf)) =
+                    scene.sample_portals(ctx, vertex.pos, surface.normal, bounce)
+                {
+                    //Balance heuristic against the uniform hemisphere sampling of diffuse bounces
+                    let weight = portal_pdf / (portal_pdf + HEMISPHERE_PDF);
+                    sky.add(
+                        LightGroup::Sky,
+                        beta * color * surface.occlusion * light * weight,
+                    );
+                }
+            }
+            let next = match material.reflect(&intersection, &surface, lobe) {
+                Some(next) => next,
+                None => {
+                    path.push(vertex);
+                    break;
+                }
+            };
+            let pdf_rev = match lobe {
+                Lobe::Diffuse => {
+                    beta *= color * surface.occlusion * next.direction.dot(surface.normal).max(0.0);
+                    pdf = vertex.pdf_direction(next.direction);
+                    vertex.pdf_direction(-ray.direction.normalized())
+                }
+                Lobe::Specular => {
+                    beta *= surface.specular_tint(color);
+                    vertex.delta = true;
+                    pdf = 0.0;
+                    0.0
+                }
+            };
+            let previous = path.last_mut().unwrap();
+            previous.pdf_rev = vertex.to_area(pdf_rev, previous);
+            ray = Ray::new(
+                next.origin + intersection.get_normal() * EPSILON,
+                next.direction,
+            );
+            path.push(vertex);
+            if beta == Vec3::default() {
+                break;
+            }
+        }
+    }
+    /// Path starting on a random point of the lights
+    fn light_path<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        path: &mut Vec<Vertex>,
+    ) {
+        if self.light_vertex(scene, ctx, path).is_none() {
+            return;
+        }
+        let vertex = &path[0];
+        let (direction, pdf) = match cosine_direction(vertex.normal) {
+            Some(direction) => direction,
+            None => return,
+        };
+        let segment = Segment {
+            ray: Ray::new(vertex.pos + vertex.normal * EPSILON, direction),
+            beta: vertex.beta * direction.dot(vertex.normal) / pdf,
+            pdf,
+        };
+        let mut sky = LightLayers::new();
+        self.walk(scene, ctx, segment, 0.0, path, &mut sky);
+    }
+    /// Samples a point of the lights as the first vertex of a light path, returns `None` if it doesn't emit
+    fn light_vertex<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        path: &mut Vec<Vertex>,
+    ) -> Option<()> {
+        let mut rng = random::rng();
+        let sample = scene.lights.choose(&mut rng)?.sample(&mut rng)?;
+        let cone = RayCone {
+            width: 0.0,
+            anisotropy: scene.anisotropy,
+        };
+        let emission = sample
+            .color
+            .material
+            .emission(&sample.color, ctx.repo, cone)?;
+        let pdf = scene.lights.pdf();
+        let mut vertex = Vertex::new(
+            VertexKind::Light,
+            sample.pos,
+            sample.normal,
+            emission.1 / pdf,
+        );
+        vertex.emission = Some(emission);
+        vertex.pdf_fwd = pdf;
+        path.push(vertex);
+        Some(())
+    }
+    /// Whether nothing blocks the segment between two vertices
+    fn visible<K: Intersectable>(scene: &Scene<K>, a: &Vertex, b: &Vertex) -> bool {
+        let d = b.pos - a.pos;
+        let distance = d.length();
+        let direction = d / distance;
+        let offset = if a.normal.dot(direction) < 0.0 {
+            -a.normal
+        } else {
+            a.normal
+        };
+        let ray = Ray::new(a.pos + offset * EPSILON, direction);
+        match scene.object.intersect(ray) {
+            Some(intersection) => {
+                intersection.get_distance() >= distance * (1.0 - 1e-6) - 2.0 * EPSILON
+            }
+            None => true,
+        }
+    }
+    /// Light of the path made of the first `s` vertices of the light path and the first `t` of the camera path,
+    /// weighted against the other strategies. For `s` = 1 a new point of the lights is sampled.
+    fn connect<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        light: &[Vertex],
+        camera: &[Vertex],
+        s: usize,
+        t: usize,
+    ) -> Option<(LightGroup, Vec3)> {
+        let pt = &camera[t - 1];
+        //Point of the lights sampled for this connection
+        let mut sampled = Vec::new();
+        let (group, color) = if s == 0 {
+            //The camera path hit a light
+            let (group, emission) = pt.emitted(camera[t - 2].pos - pt.pos)?;
+            (group, pt.beta * emission)
+        } else {
+            if !pt.is_connectable() {
+                return None;
+            }
+            let qs = if s == 1 {
+                self.light_vertex(scene, ctx, &mut sampled)?;
+                &sampled[0]
+            } else {
+                &light[s - 1]
+            };
+            if !qs.is_connectable() {
+                return None;
+            }
+            let d = pt.pos - qs.pos;
+            let distance_squared = d.length_squared();
+            if distance_squared == 0.0 {
+                return None;
+            }
+            let direction = d / distance_squared.sqrt();
+            //Reflectance, or the emitted light of the light vertex, and the cosine towards the other end
+            let (qs_color, qs_cos) = match qs.kind {
+                VertexKind::Light => {
+                    qs.emitted(direction)?;
+                    (qs.beta, direction.dot(qs.normal))
+                }
+                _ => (qs.beta * qs.f(direction), direction.dot(qs.shading)),
+            };
+            let pt_color = pt.beta * pt.f(-direction);
+            let g = qs_cos.abs() * direction.dot(pt.shading).abs() / distance_squared;
+            let color = qs_color * pt_color * g;
+            if color == Vec3::default() || !Self::visible(scene, qs, pt) {
+                return None;
+            }
+            let origin = if s == 1 { qs } else { &light[0] };
+            let (group, _) = origin.emission?;
+            (group, color)
+        };
+        let light = if s == 1 { &sampled[..] } else { &light[..s] };
+        let weight = Self::mis_weight(&scene.lights, light, &camera[..t]);
+        Some((group, color * weight))
+    }
+    /// Balance heuristic weight of connecting the last vertices of the two subpaths
+    fn mis_weight(lights: &AreaLights, light: &[Vertex], camera: &[Vertex]) -> f64 {
+        let (s, t) = (light.len(), camera.len());
+        if s == 0 && lights.is_empty() {
+            //Light paths can't start on the hit emitter
+            return 1.0;
+        }
+        //Densities and whether the vertices are specular, as if the connected path had been sampled
+        let mut light_pdfs: Vec<(f64, f64, bool)> = light
+            .iter()
+            .map(|v| (v.pdf_fwd, v.pdf_rev, v.delta))
+            .collect();
+        let mut camera_pdfs: Vec<(f64, f64, bool)> = camera
+            .iter()
+            .map(|v| (v.pdf_fwd, v.pdf_rev, v.delta))
+            .collect();
+        let pt = &camera[t - 1];
+        let pt_minus = &camera[t - 2];
+        if s > 0 {
+            let qs = &light[s - 1];
+            camera_pdfs[t - 1].1 = qs.pdf(pt);
+            camera_pdfs[t - 2].1 = pt.pdf(pt_minus);
+            light_pdfs[s - 1].1 = pt.pdf(qs);
+            if s > 1 {
+                light_pdfs[s - 2].1 = qs.pdf(&light[s - 2]);
+            }
+            light_pdfs[s - 1].2 = false;
+        } else {
+            camera_pdfs[t - 1].1 = lights.pdf();
+            camera_pdfs[t - 2].1 = pt.pdf_light(pt_minus);
+        }
+        camera_pdfs[t - 1].2 = false;
+        let mut sum = 0.0;
+        //Fewer camera vertices, camera paths without any surfaces can't be connected
+        let mut ratio = 1.0;
+        for i in (2..t).rev() {
+            ratio *= remap0(camera_pdfs[i].1) / remap0(camera_pdfs[i].0);
+            if !camera_pdfs[i].2 && !camera_pdfs[i - 1].2 {
+                sum += ratio;
+            }
+        }
+        //Fewer light vertices
+        let mut ratio = 1.0;
+        for i in (0..s).rev() {
+            ratio *= remap0(light_pdfs[i].1) / remap0(light_pdfs[i].0);
+            let delta_previous = i > 0 && light_pdfs[i - 1].2;
+            if !light_pdfs[i].2 && !delta_previous {
+                sum += ratio;
+            }
+        }
+        1.0 / (1.0 + sum)
+    }
+}
+
+impl Integrator for BidirectionalPathTracer {
+    fn integrate<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        ray: Ray,
+        spread: f64,
+    ) -> LightLayers {
+        let mut layers = LightLayers::new();
+        if self.bounces == 0 {
+            return layers;
+        }
+        let mut camera = Vec::with_capacity(self.bounces + 1);
+        camera.push(Vertex::new(
+            VertexKind::Camera,
+            ray.origin,
+            ray.direction.normalized(),
+            Vec3::from_single(1.0),
+        ));
+        let segment = Segment {
+            ray,
+            beta: Vec3::from_single(1.0),
+            pdf: 1.0,
+        };
+        self.walk(scene, ctx, segment, spread, &mut camera, &mut layers);
+        let mut light = Vec::with_capacity(self.bounces);
+        self.light_path(scene, ctx, &mut light);
+        for t in 2..=camera.len() {
+            for s in 0..=light.len().min(self.bounces + 1 - t) {
+                if let Some((group, color)) = self.connect(scene, ctx, &light, &camera, s, t) {
+                    if color.inner.iter().all(|c| c.is_finite()) {
+                        layers.add(group, color);
+                    }
+                }
+            }
+        }
+        layers
+    }
+}
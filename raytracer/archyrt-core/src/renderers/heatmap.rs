@@ -0,0 +1,115 @@
+#[cfg(feature = "stats")]
+use std::time::Instant;
+
+#[cfg(feature = "stats")]
+use crate::{
+    api::fragment_render::{FragmentContext, FragmentRender},
+    intersectables::bvh::measure,
+    utilities::math::Vec2,
+};
+use crate::utilities::math::Vec3;
+
+/// Cost a [`HeatmapRenderer`] colors pixels by
+#[cfg(feature = "stats")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeatmapMetric {
+    /// Bounding volume nodes visited
+    Nodes,
+    /// Triangles tested for intersection
+    Triangles,
+    /// Deepest bounce which hit a surface
+    Depth,
+    /// Time spent, in microseconds
+    Time,
+}
+
+#[cfg(feature = "stats")]
+impl HeatmapMetric {
+    /// Names accepted by [`HeatmapMetric::from_name`]
+    pub const NAMES: [&'static str; 4] = ["nodes", "triangles", "depth", "time"];
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nodes" => Some(Self::Nodes),
+            "triangles" => Some(Self::Triangles),
+            "depth" => Some(Self::Depth),
+            "time" => Some(Self::Time),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Nodes => "nodes",
+            Self::Triangles => "triangles",
+            Self::Depth => "depth",
+            Self::Time => "time",
+        }
+    }
+    /// Value shown as the hottest color by default
+    pub fn default_scale(self) -> f64 {
+        match self {
+            Self::Nodes => 500.0,
+            Self::Triangles => 200.0,
+            Self::Depth => 5.0,
+            Self::Time => 1000.0,
+        }
+    }
+}
+
+/// Colors of [`false_color`], from cheap to expensive
+const GRADIENT: [[f64; 3]; 6] = [
+    [0.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, 1.0, 0.0],
+    [1.0, 1.0, 0.0],
+    [1.0, 0.0, 0.0],
+];
+
+/// Maps 0 to black and 1 to red through blue, cyan, green and yellow, larger values stay red
+pub fn false_color(t: f64) -> Vec3 {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    let position = t * (GRADIENT.len() - 1) as f64;
+    let index = (position as usize).min(GRADIENT.len() - 2);
+    let fraction = position - index as f64;
+    let [a, b] = [GRADIENT[index], GRADIENT[index + 1]].map(|[r, g, b]| Vec3::new(r, g, b));
+    a * (1.0 - fraction) + b * fraction
+}
+
+/// Renders the cost of each fragment of another renderer in false color, instead of its image.
+/// Counting the work of traversals needs the `stats` feature.
+#[cfg(feature = "stats")]
+pub struct HeatmapRenderer<T: FragmentRender> {
+    pub inner: T,
+    pub metric: HeatmapMetric,
+    /// Value shown as the hottest color
+    pub scale: f64,
+}
+
+#[cfg(feature = "stats")]
+impl<T: FragmentRender> HeatmapRenderer<T> {
+    pub fn new(inner: T, metric: HeatmapMetric) -> Self {
+        Self {
+            inner,
+            metric,
+            scale: metric.default_scale(),
+        }
+    }
+    /// Cost of rendering a fragment, in the unit of the metric
+    pub fn cost(&self, ctx: &FragmentContext, pos: Vec2) -> f64 {
+        let start = Instant::now();
+        let (_, stats) = measure(|| self.inner.render_fragment(ctx, pos));
+        match self.metric {
+            HeatmapMetric::Nodes => stats.nodes as f64,
+            HeatmapMetric::Triangles => stats.triangles as f64,
+            HeatmapMetric::Depth => stats.depth as f64,
+            HeatmapMetric::Time => start.elapsed().as_secs_f64() * 1e6,
+        }
+    }
+}
+
+#[cfg(feature = "stats")]
+impl<T: FragmentRender> FragmentRender for HeatmapRenderer<T> {
+    fn render_fragment(&self, ctx: &FragmentContext, pos: Vec2) -> Vec3 {
+        false_color(self.cost(ctx, pos) / self.scale)
+    }
+}
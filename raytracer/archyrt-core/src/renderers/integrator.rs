@@ -0,0 +1,264 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::{
+    api::{
+        camera::Camera,
+        fragment_render::{FragmentContext, FragmentRender},
+        light_layers::{LayeredFragmentRender, LightLayers},
+    },
+    intersectables::{area_light::AreaLights, clay::Clay, portal::Portal},
+    textures::{
+        samplers::{nearest::NearestSampler, TextureSampler, WrapMode},
+        TextureID,
+    },
+    utilities::{
+        math::{Vec2, Vec3},
+        random,
+        ray::{Intersectable, Ray},
+    },
+    vector,
+};
+
+use super::{
+    ambient_occlusion::AmbientOcclusion,
+    bidirectional::BidirectionalPathTracer,
+    media::{Media, Sun},
+    path_tracer::PathTracer,
+    whitted::Whitted,
+};
+
+/// Offset of secondary rays from the surface they start on
+pub(crate) const EPSILON: f64 = 0.00001;
+/// Probability density of a uniformly sampled direction of a hemisphere
+pub(crate) const HEMISPHERE_PDF: f64 = 1.0 / (2.0 * PI);
+
+/// Geometry and lighting shared by every integrator
+pub struct Scene<K: Intersectable> {
+    pub object: K,
+    pub skybox: Option<TextureID>,
+    /// Openings the skybox is sampled through, see [`Portal`]
+    pub portals: Vec<Portal>,
+    /// Emissive triangles light paths start from. Emitters missing from it are only found by rays hitting them,
+    /// so the bidirectional path tracer underestimates their light.
+    pub lights: AreaLights,
+    /// Maximum number of texture taps along stretched footprints, 1 disables anisotropic filtering
+    pub anisotropy: usize,
+    /// Fog and smoke light is scattered by. Only the path tracer scatters light in media, Whitted attenuates the
+    /// light of its shadow rays by them.
+    pub media: Media,
+    /// Lights the surfaces of the path tracer and Whitted
+    pub sun: Option<Sun>,
+}
+
+impl<K: Intersectable> Scene<K> {
+    /// Same scene with every surface replaced by clay, see [`Clay`]
+    pub fn into_clay(self) -> Scene<Clay<K>> {
+        Scene {
+            object: Clay::new(self.object),
+            skybox: self.skybox,
+            portals: self.portals,
+            lights: self.lights,
+            anisotropy: self.anisotropy,
+            media: self.media,
+            sun: self.sun,
+        }
+    }
+    /// Same scene borrowing its geometry, so it can be rendered several times
+    pub fn by_ref(&self) -> Scene<&K> {
+        Scene {
+            object: &self.object,
+            skybox: self.skybox,
+            portals: self.portals.clone(),
+            lights: self.lights.clone(),
+            anisotropy: self.anisotropy,
+            media: self.media.clone(),
+            sun: self.sun,
+        }
+    }
+    pub fn sky_color(&self, ctx: &FragmentContext, direction: Vec3, bounce: usize) -> Vec3 {
+        match self.skybox {
+            //Skybox color
+            Some(skybox) => {
+                //The longitude wraps around, the latitude ends at the poles
+                let sampler = NearestSampler {
+                    wrap: WrapMode::Clamp,
+                };
+                let texture = ctx.repo.get(skybox).unwrap();
+                let longitude = direction.x().atan2(direction.z());
+                let latitude = -(direction.y() / direction.length()).asin();
+                let longitude = WrapMode::Repeat.apply((longitude / PI + 1.0) * 0.5);
+                let latitude = (latitude / (PI / 2.0) + 1.0) * 0.5;
+                let res = sampler.sample(texture, vector![longitude, latitude]);
+                if bounce == 0 {
+                    res
+                } else {
+                    res * 3.0
+                }
+            }
+            //Default skybox color
+            _ => Vec3::default(),
+        }
+    }
+    /// Probability density of picking the ray's direction by sampling a random portal
+    pub fn portal_pdf(&self, ray: Ray) -> f64 {
+        let sum: f64 = self.portals.iter().map(|portal| portal.pdf(ray)).sum();
+        sum / (self.portals.len() as f64)
+    }
+    /// Skylight arriving at a point through a random portal, attenuated by the media, with its direction and the
+    /// probability density of the direction. `None` if it is blocked or there are no portals.
+    pub fn sample_sky(
+        &self,
+        ctx: &FragmentContext,
+        origin: Vec3,
+        bounce: usize,
+    ) -> Option<(Vec3, Vec3, f64)> {
+        if self.portals.is_empty() {
+            return None;
+        }
+        let mut rng = random::rng();
+        let portal = &self.portals[rng.gen_range(0..self.portals.len())];
+        let direction = (portal.sample(&mut rng) - origin).normalized();
+        let ray = Ray::new(origin, direction);
+        if self.object.intersect(ray).is_some() {
+            return None;
+        }
+        let portal_pdf = self.portal_pdf(ray);
+        if portal_pdf <= 0.0 || !portal_pdf.is_finite() {
+            return None;
+        }
+        let light = self.sky_color(ctx, direction, bounce + 1)
+            * self.media.transmittance(ray, f64::INFINITY);
+        Some((direction, light, portal_pdf))
+    }
+    /// Skylight arriving through a random portal, scaled like a uniformly sampled hemisphere direction,
+    /// and the probability density of its direction. `None` if it is blocked or there are no portals.
+    pub fn sample_portals(
+        &self,
+        ctx: &FragmentContext,
+        pos: Vec3,
+        normal: Vec3,
+        bounce: usize,
+    ) -> Option<(Vec3, f64)> {
+        let (direction, light, portal_pdf) =
+            self.sample_sky(ctx, pos + normal * EPSILON, bounce)?;
+        let cos = direction.dot(normal);
+        if cos <= 0.0 {
+            return None;
+        }
+        Some((light * cos * HEMISPHERE_PDF / portal_pdf, portal_pdf))
+    }
+    /// Direction towards the sun and its light arriving at a point, attenuated by the media. `None` if it is blocked
+    /// or there is no sun.
+    pub fn sunlight(&self, origin: Vec3) -> Option<(Vec3, Vec3)> {
+        let sun = self.sun?;
+        let direction = sun.direction.normalized();
+        let ray = Ray::new(origin, direction);
+        if self.object.intersect(ray).is_some() {
+            return None;
+        }
+        Some((
+            direction,
+            sun.color * self.media.transmittance(ray, f64::INFINITY),
+        ))
+    }
+}
+
+/// Estimates the light arriving along a ray
+pub trait Integrator {
+    /// `spread` is the angle between the rays of neighbouring pixels, used to filter textures
+    fn integrate<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        ray: Ray,
+        spread: f64,
+    ) -> LightLayers;
+}
+
+impl<T: Integrator> Integrator for &T {
+    fn integrate<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        ray: Ray,
+        spread: f64,
+    ) -> LightLayers {
+        (*self).integrate(scene, ctx, ray, spread)
+    }
+}
+
+/// Integrators which can be picked by name, with their default settings
+pub enum NamedIntegrator {
+    AmbientOcclusion(AmbientOcclusion),
+    Whitted(Whitted),
+    PathTracer(PathTracer),
+    Bidirectional(BidirectionalPathTracer),
+}
+
+impl NamedIntegrator {
+    /// Names accepted by [`NamedIntegrator::from_name`]
+    pub const NAMES: [&'static str; 4] = ["ao", "whitted", "path", "bdpt"];
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ao" => Some(Self::AmbientOcclusion(Default::default())),
+            "whitted" => Some(Self::Whitted(Default::default())),
+            "path" => Some(Self::PathTracer(Default::default())),
+            "bdpt" => Some(Self::Bidirectional(Default::default())),
+            _ => None,
+        }
+    }
+    /// Whether the integrator renders [`Scene::media`] and [`Scene::sun`]
+    pub fn renders_atmosphere(&self) -> bool {
+        !matches!(self, Self::Bidirectional(_))
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::AmbientOcclusion(_) => "ao",
+            Self::Whitted(_) => "whitted",
+            Self::PathTracer(_) => "path",
+            Self::Bidirectional(_) => "bdpt",
+        }
+    }
+}
+
+impl Integrator for NamedIntegrator {
+    fn integrate<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        ray: Ray,
+        spread: f64,
+    ) -> LightLayers {
+        match self {
+            Self::AmbientOcclusion(integrator) => integrator.integrate(scene, ctx, ray, spread),
+            Self::Whitted(integrator) => integrator.integrate(scene, ctx, ray, spread),
+            Self::PathTracer(integrator) => integrator.integrate(scene, ctx, ray, spread),
+            Self::Bidirectional(integrator) => integrator.integrate(scene, ctx, ray, spread),
+        }
+    }
+}
+
+/// Renders a scene through a camera with an integrator
+pub struct IntegratorRenderer<T: Camera, K: Intersectable, I: Integrator> {
+    pub camera: T,
+    pub scene: Scene<K>,
+    pub integrator: I,
+}
+
+impl<T: Camera, K: Intersectable, I: Integrator> FragmentRender for IntegratorRenderer<T, K, I> {
+    fn render_fragment(&self, ctx: &FragmentContext, pos: Vec2) -> Vec3 {
+        self.render_layers(ctx, pos).beauty()
+    }
+}
+
+impl<T: Camera, K: Intersectable, I: Integrator> LayeredFragmentRender
+    for IntegratorRenderer<T, K, I>
+{
+    fn render_layers(&self, ctx: &FragmentContext, pos: Vec2) -> LightLayers {
+        let ray = self.camera.get_ray(ctx, pos);
+        let spread = self.camera.spread(ctx);
+        self.integrator.integrate(&self.scene, ctx, ray, spread)
+    }
+}
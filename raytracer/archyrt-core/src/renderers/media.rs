@@ -0,0 +1,301 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::utilities::{math::Vec3, ray::Ray};
+
+/// Homogeneous participating medium, such as fog or smoke
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Medium {
+    /// Extinction coefficient, the fraction of light scattered or absorbed per meter
+    pub density: f64,
+    /// Fraction of extinguished light which is scattered instead of absorbed
+    pub albedo: Vec3,
+    /// Henyey-Greenstein asymmetry between -1 and 1, positive values scatter light forward
+    pub anisotropy: f64,
+}
+
+impl Default for Medium {
+    fn default() -> Self {
+        Self {
+            density: 0.05,
+            albedo: Vec3::from_single(0.9),
+            anisotropy: 0.6,
+        }
+    }
+}
+
+impl Medium {
+    /// Probability density of light being scattered by the angle with this cosine
+    pub fn phase(&self, cos: f64) -> f64 {
+        let g = self.anisotropy;
+        let denominator = 1.0 + g * g - 2.0 * g * cos;
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+    /// Samples the direction light travelling along `direction` is scattered into, distributed like [`Medium::phase`]
+    pub fn sample_phase<R: Rng>(&self, direction: Vec3, rng: &mut R) -> Vec3 {
+        let g = self.anisotropy;
+        let u: f64 = rng.gen();
+        let cos = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+            (1.0 + g * g - s * s) / (2.0 * g)
+        };
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let angle = rng.gen::<f64>() * 2.0 * PI;
+        let direction = direction.normalized();
+        let helper = if direction.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let tangent = direction.cross(helper).normalized();
+        let bitangent = direction.cross(tangent);
+        direction * cos + tangent * (sin * angle.cos()) + bitangent * (sin * angle.sin())
+    }
+    /// Medium behaving like several media filling the same space
+    fn mix(media: &[Medium]) -> Self {
+        let mut mixed = Self {
+            density: 0.0,
+            albedo: Vec3::default(),
+            anisotropy: 0.0,
+        };
+        for medium in media {
+            mixed.density += medium.density;
+            mixed.albedo += medium.albedo * medium.density;
+            mixed.anisotropy += medium.anisotropy * medium.density;
+        }
+        if mixed.density > 0.0 {
+            mixed.albedo /= mixed.density;
+            mixed.anisotropy /= mixed.density;
+        }
+        mixed
+    }
+}
+
+/// Convex region bounded by planes
+#[derive(Debug, Clone, Default)]
+pub struct Convex {
+    /// Outward normals and offsets, points `p` with `normal.dot(p) <= offset` are inside
+    pub planes: Vec<(Vec3, f64)>,
+}
+
+impl Convex {
+    pub fn aabb(min: Vec3, max: Vec3) -> Self {
+        let axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        let planes = axes
+            .iter()
+            .flat_map(|axis| [(*axis, axis.dot(max)), (-*axis, -axis.dot(min))])
+            .collect();
+        Self { planes }
+    }
+    pub fn contains(&self, pos: Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|(normal, offset)| normal.dot(pos) <= *offset)
+    }
+    /// Range of distances along the ray inside the region
+    pub fn clip(&self, ray: Ray) -> Option<(f64, f64)> {
+        let (mut near, mut far) = (0.0f64, f64::INFINITY);
+        for (normal, offset) in &self.planes {
+            let denominator = normal.dot(ray.direction);
+            let distance = offset - normal.dot(ray.origin);
+            if denominator == 0.0 {
+                if distance < 0.0 {
+                    return None;
+                }
+                continue;
+            }
+            let t = distance / denominator;
+            if denominator > 0.0 {
+                far = far.min(t);
+            } else {
+                near = near.max(t);
+            }
+        }
+        (near < far).then_some((near, far))
+    }
+}
+
+/// Region of a scene filled with a medium
+#[derive(Debug, Clone)]
+pub struct Volume {
+    pub shape: Convex,
+    pub medium: Medium,
+}
+
+/// Participating media of a scene
+#[derive(Debug, Clone)]
+pub struct Media {
+    /// Fog filling the whole scene
+    pub fog: Option<Medium>,
+    /// Distance rays leaving the scene travel through the fog, as the fog doesn't end
+    pub fog_extent: f64,
+    pub volumes: Vec<Volume>,
+}
+
+impl Default for Media {
+    fn default() -> Self {
+        Self {
+            fog: None,
+            fog_extent: 50.0,
+            volumes: Vec::new(),
+        }
+    }
+}
+
+impl Media {
+    pub fn is_empty(&self) -> bool {
+        self.fog.is_none() && self.volumes.is_empty()
+    }
+    /// Pieces of the ray up to the distance `max` with a constant medium, skipping empty space
+    fn segments(&self, ray: Ray, max: f64) -> Vec<(f64, f64, Medium)> {
+        let fog_end = match self.fog {
+            Some(_) => max.min(self.fog_extent / ray.direction.length()),
+            None => 0.0,
+        };
+        let ranges: Vec<(f64, f64)> = self
+            .volumes
+            .iter()
+            .map(|volume| match volume.shape.clip(ray) {
+                Some((near, far)) => (near, far.min(max)),
+                None => (0.0, 0.0),
+            })
+            .collect();
+        let mut bounds = vec![0.0, fog_end];
+        for (near, far) in &ranges {
+            if near < far {
+                bounds.extend([*near, *far]);
+            }
+        }
+        bounds.retain(|t| t.is_finite());
+        bounds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        bounds.dedup();
+        let mut segments = Vec::new();
+        for pair in bounds.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            let middle = (start + end) * 0.5;
+            let mut media: Vec<Medium> = ranges
+                .iter()
+                .zip(&self.volumes)
+                .filter(|((near, far), _)| (*near..*far).contains(&middle))
+                .map(|(_, volume)| volume.medium)
+                .collect();
+            if middle < fog_end {
+                media.extend(self.fog);
+            }
+            let medium = Medium::mix(&media);
+            if medium.density > 0.0 {
+                segments.push((start, end, medium));
+            }
+        }
+        segments
+    }
+    /// Fraction of light passing along the ray up to the distance `max`
+    pub fn transmittance(&self, ray: Ray, max: f64) -> f64 {
+        if self.is_empty() {
+            return 1.0;
+        }
+        let length = ray.direction.length();
+        let depth: f64 = self
+            .segments(ray, max)
+            .iter()
+            .map(|(start, end, medium)| medium.density * (end - start) * length)
+            .sum();
+        (-depth).exp()
+    }
+    /// Samples where light along the ray up to the distance `max` is scattered or absorbed, proportionally to the
+    /// transmittance. Returns the distance and the medium there, `None` if the light passes through.
+    pub fn sample_distance<R: Rng>(
+        &self,
+        ray: Ray,
+        max: f64,
+        rng: &mut R,
+    ) -> Option<(f64, Medium)> {
+        if self.is_empty() {
+            return None;
+        }
+        let length = ray.direction.length();
+        let mut depth = -(1.0 - rng.gen::<f64>()).ln();
+        for (start, end, medium) in self.segments(ray, max) {
+            let segment = medium.density * (end - start) * length;
+            if depth < segment {
+                return Some((start + depth / (medium.density * length), medium));
+            }
+            depth -= segment;
+        }
+        None
+    }
+}
+
+/// Distant light, such as the sun
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sun {
+    /// Direction towards the sun
+    pub direction: Vec3,
+    /// Irradiance of surfaces facing the sun, in the working space
+    pub color: Vec3,
+}
+
+impl Default for Sun {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::new(0.4, 0.8, 0.3),
+            color: Vec3::new(20.0, 18.0, 15.0),
+        }
+    }
+}
+
+/// Fog and sunlight settings of a render
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Atmosphere {
+    pub fog: Option<Medium>,
+    /// See [`Media::fog_extent`]
+    pub fog_extent: f64,
+    /// Medium filling the fog volumes of the scene
+    pub volumes: Medium,
+    pub sun: Option<Sun>,
+}
+
+impl Default for Atmosphere {
+    fn default() -> Self {
+        Self {
+            fog: None,
+            fog_extent: Media::default().fog_extent,
+            volumes: Medium::default(),
+            sun: None,
+        }
+    }
+}
+
+impl Atmosphere {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+    /// Media of a scene whose fog volumes have these shapes
+    pub fn media(&self, shapes: &[Convex]) -> Media {
+        Media {
+            fog: self.fog,
+            fog_extent: self.fog_extent,
+            volumes: shapes
+                .iter()
+                .map(|shape| Volume {
+                    shape: shape.clone(),
+                    medium: self.volumes,
+                })
+                .collect(),
+        }
+    }
+}
@@ -1,4 +1,12 @@
+pub mod ambient_occlusion;
 pub mod basic_renderer;
+pub mod bidirectional;
+pub mod heatmap;
+pub mod integrator;
+pub mod media;
 pub mod path_tracer;
 pub mod solid_renderers;
-pub mod sampling;
\ No newline at end of file
+pub mod sampling;
+pub mod whitted;
+#[cfg(test)]
+mod tests;
@@ -1,30 +1,36 @@
-use std::{f64::consts::PI};
-
 use rand_distr::{Distribution, UnitSphere};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     api::{
-        camera::Camera,
-        fragment_render::{FragmentContext, FragmentRender},
+        fragment_render::FragmentContext,
+        light_layers::{LightGroup, LightLayers},
     },
+    intersectables::bvh::record_depth,
+    loaders::amdl::repo::PropID,
     textures::{
-        color_provider::ColorProvider,
-        samplers::{nearest::NearestSampler, TextureSampler},
+        color_provider::{ColorProvider, Lobe, RayCone, SurfaceProperties},
         texture_repo::TextureRepository,
         TextureID,
     },
     utilities::{
-        math::{Vec2, Vec3},
+        math::Vec3,
+        random,
         ray::{Intersectable, Intersection, Ray},
     },
-    vector,
 };
 
-#[derive(Clone, Copy, Debug)]
+use super::integrator::{Integrator, Scene, EPSILON, HEMISPHERE_PDF};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Material {
     Diffuse,
     Emissive { power: f64 },
-    DiffuseAndEmissive {emissive_texture: TextureID},
+    DiffuseAndEmissive {
+        emissive_texture: TextureID,
+        /// Prop the surface belongs to, set once the prop is placed in a repository
+        prop: Option<PropID>,
+    },
 }
 
 impl Default for Material {
@@ -33,16 +39,58 @@ impl Default for Material {
     }
 }
 
-const EPSILON: f64 = 0.00001;
-
 impl Material {
-    pub fn reflect<C: ColorProvider>(self, intersection: Intersection<C>) -> Option<Ray> {
+    pub fn is_emissive(self) -> bool {
+        !matches!(self, Material::Diffuse)
+    }
+    /// Light emitted by a surface with this material, and its source
+    pub fn emission<C: ColorProvider>(
+        self,
+        provider: &C,
+        repo: &TextureRepository,
+        cone: RayCone,
+    ) -> Option<(LightGroup, Vec3)> {
+        match self {
+            Material::Diffuse => None,
+            Material::Emissive { power } => Some((
+                LightGroup::Emissive,
+                provider.get_color_filtered(repo, cone) * power,
+            )),
+            Material::DiffuseAndEmissive { emissive_texture, prop } => Some((
+                prop.map_or(LightGroup::EmissiveTexture(emissive_texture), LightGroup::Prop),
+                provider.sample_filtered(repo, emissive_texture, cone) * 50.0,
+            )),
+        }
+    }
+    pub fn reflect<C: ColorProvider>(
+        self,
+        intersection: &Intersection<C>,
+        surface: &SurfaceProperties,
+        lobe: Lobe,
+    ) -> Option<Ray> {
         match self {
-            Material::Diffuse | Material::DiffuseAndEmissive{emissive_texture: _} => {
-                let p: [f64; 3] = UnitSphere.sample(&mut rand::thread_rng());
+            Material::Diffuse | Material::DiffuseAndEmissive { .. } => {
+                let p: [f64; 3] = UnitSphere.sample(&mut random::rng());
                 let p = Vec3::new(p[0], p[1], p[2]);
-                let p = if intersection.get_normal().dot(p) < 0.0 {
-                    -p
+                let p = match lobe {
+                    Lobe::Diffuse => {
+                        if surface.normal.dot(p) < 0.0 {
+                            -p
+                        } else {
+                            p
+                        }
+                    }
+                    Lobe::Specular => {
+                        //Mirror reflection, blurred by the roughness
+                        let d = intersection.get_ray().direction.normalized();
+                        let mirror = d - surface.normal * (2.0 * d.dot(surface.normal));
+                        (mirror + p * surface.roughness).normalized()
+                    }
+                };
+                //Shading normals can point directions below the actual surface
+                let normal = intersection.get_normal();
+                let p = if normal.dot(p) < 0.0 {
+                    p - normal * (2.0 * normal.dot(p))
                 } else {
                     p
                 };
@@ -58,46 +106,175 @@ impl Material {
         self,
         intersection: &Intersection<C>,
         repo: &TextureRepository,
-        emissive: &mut Vec3,
+        shading: &Shading,
+        emissive: &mut LightLayers,
         diffusive: &mut Vec3,
     ) {
-        match self {
-            Material::Diffuse => {
-                (*diffusive) *= intersection.get_color(repo);
-            }
-            Material::Emissive { power } => {
-                (*emissive) += intersection.get_color(repo) * power * (*diffusive);
-            }
-            Material::DiffuseAndEmissive { emissive_texture } => {
-                (*emissive) += intersection.ref_color_provider().sample(repo, emissive_texture) * 50.0 * (*diffusive);
-                (*diffusive) *= intersection.get_color(repo);
-                
-            },
+        let Shading { cone, surface, lobe } = *shading;
+        //Lobes are picked with probabilities matching their weights, which cancel out
+        let reflectance = |albedo: Vec3| match lobe {
+            Lobe::Diffuse => albedo * surface.occlusion,
+            Lobe::Specular => surface.specular_tint(albedo),
+        };
+        if let Some((group, light)) = self.emission(intersection.ref_color_provider(), repo, cone) {
+            emissive.add(group, light * (*diffusive));
+        }
+        if !matches!(self, Material::Emissive { .. }) {
+            (*diffusive) *= reflectance(intersection.get_color_filtered(repo, cone));
         }
     }
 }
 
-pub struct PathTracer<T: Camera, K: Intersectable> {
-    pub camera: T,
-    pub object: K,
+/// How a hit is shaded: the ray cone reaching it, its surface maps and the lobe it reflects from
+#[derive(Clone, Copy)]
+pub struct Shading {
+    pub cone: RayCone,
+    pub surface: SurfaceProperties,
+    pub lobe: Lobe,
+}
+
+/// Unidirectional path tracing, sampling the sky through portals and the sun at diffuse bounces and in media
+pub struct PathTracer {
     pub bounces: usize,
-    pub skybox: Option<TextureID>,
 }
 
-impl<T: Camera, K: Intersectable> FragmentRender for PathTracer<T, K> {
-    fn render_fragment(&self, ctx: &FragmentContext, pos: Vec2) -> Vec3 {
-        let mut ray = self.camera.get_ray(ctx, pos);
-        let mut emissive = Vec3::default();
-        let mut diffusive = Vec3::from_single(1.0);
-        for bounce in 0..self.bounces {
-            match self.object.intersect(ray) {
+impl Default for PathTracer {
+    fn default() -> Self {
+        Self { bounces: 5 }
+    }
+}
+
+/// State of a path being traced
+struct Path {
+    ray: Ray,
+    bounce: usize,
+    diffusive: Vec3,
+    /// Probability density of the last bounce's direction, if that bounce has already sampled the sky through the portals
+    sampled_portals: Option<f64>,
+}
+
+impl PathTracer {
+    /// Light reflected towards the normal by a white diffuse surface at a point, scaled like the surfaces hit by
+    /// [`PathTracer::integrate`]. Used to bake lightmaps.
+    pub fn gather<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        pos: Vec3,
+        normal: Vec3,
+    ) -> LightLayers {
+        let mut emissive = LightLayers::new();
+        if let Some((sun, light)) = scene.sunlight(pos + normal * EPSILON) {
+            let cos = sun.dot(normal).max(0.0);
+            emissive.add(LightGroup::Sun, light * (cos * HEMISPHERE_PDF));
+        }
+        let portals = scene.skybox.is_some() && !scene.portals.is_empty();
+        if portals {
+            if let Some((light, portal_pdf)) = scene.sample_portals(ctx, pos, normal, 0) {
+                let weight = portal_pdf / (portal_pdf + HEMISPHERE_PDF);
+                emissive.add(LightGroup::Sky, light * weight);
+            }
+        }
+        let p: [f64; 3] = UnitSphere.sample(&mut random::rng());
+        let direction = Vec3::new(p[0], p[1], p[2]);
+        let direction = if direction.dot(normal) < 0.0 {
+            -direction
+        } else {
+            direction
+        };
+        let path = Path {
+            ray: Ray::new(pos + normal * EPSILON, direction),
+            bounce: 1,
+            diffusive: Vec3::from_single(direction.dot(normal)),
+            sampled_portals: portals.then_some(HEMISPHERE_PDF),
+        };
+        emissive += self.trace(scene, ctx, path, 0.0);
+        emissive
+    }
+    fn trace<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        path: Path,
+        spread: f64,
+    ) -> LightLayers {
+        let Path {
+            mut ray,
+            bounce: first,
+            mut diffusive,
+            mut sampled_portals,
+        } = path;
+        let mut emissive = LightLayers::new();
+        let mut rng = random::rng();
+        //Ray cone used to select texture mip levels
+        let mut cone_width = 0.0;
+        let portals = scene.skybox.is_some() && !scene.portals.is_empty();
+        for bounce in first..self.bounces {
+            let intersection = scene.object.intersect(ray);
+            let distance = intersection
+                .as_ref()
+                .map_or(f64::INFINITY, |intersection| intersection.get_distance());
+            //Light can be scattered by the media before reaching the surface or the sky
+            if let Some((t, medium)) = scene.media.sample_distance(ray, distance, &mut rng) {
+                let pos = ray.origin + ray.direction * t;
+                let direction = ray.direction.normalized();
+                cone_width += spread * t;
+                diffusive *= medium.albedo;
+                if let Some((sun, light)) = scene.sunlight(pos) {
+                    let phase = medium.phase(direction.dot(sun));
+                    emissive.add(LightGroup::Sun, diffusive * light * phase);
+                }
+                if portals {
+                    if let Some((sky, light, portal_pdf)) = scene.sample_sky(ctx, pos, bounce) {
+                        let phase = medium.phase(direction.dot(sky));
+                        //Balance heuristic against sampling the phase function
+                        let weight = portal_pdf / (portal_pdf + phase);
+                        emissive.add(LightGroup::Sky, diffusive * light * (phase / portal_pdf * weight));
+                    }
+                }
+                //Sampled proportionally to the phase function, which cancels out
+                let scattered = medium.sample_phase(direction, &mut rng);
+                sampled_portals = portals.then(|| medium.phase(direction.dot(scattered)));
+                ray = Ray::new(pos, scattered);
+                continue;
+            }
+            match intersection {
                 Some(intersection) => {
+                    record_depth(bounce + 1);
                     let normal = intersection.get_normal();
                     let material = intersection.get_material();
-                    material.color(&intersection, ctx.repo, &mut emissive, &mut diffusive);
-                    ray = match material.reflect(intersection) {
+                    cone_width += spread * intersection.get_distance();
+                    let cone = RayCone {
+                        width: cone_width,
+                        anisotropy: scene.anisotropy,
+                    };
+                    let surface = intersection.get_surface(ctx.repo, cone);
+                    let lobe = surface.choose_lobe(&mut rng);
+                    let shading = Shading { cone, surface, lobe };
+                    material.color(&intersection, ctx.repo, &shading, &mut emissive, &mut diffusive);
+                    //Lights are only sampled for the diffuse lobe
+                    let diffuse = lobe == Lobe::Diffuse && !matches!(material, Material::Emissive { .. });
+                    if diffuse {
+                        if let Some((sun, light)) = scene.sunlight(intersection.get_pos() + normal * EPSILON) {
+                            let cos = sun.dot(surface.normal).max(0.0);
+                            emissive.add(LightGroup::Sun, diffusive * light * (cos * HEMISPHERE_PDF));
+                        }
+                    }
+                    sampled_portals = (portals && diffuse).then_some(HEMISPHERE_PDF);
+                    if sampled_portals.is_some() {
+                        if let Some((light, portal_pdf)) =
+                            scene.sample_portals(ctx, intersection.get_pos(), surface.normal, bounce)
+                        {
+                            //Balance heuristic against the uniform hemisphere sampling of diffuse bounces
+                            let weight = portal_pdf / (portal_pdf + HEMISPHERE_PDF);
+                            emissive.add(LightGroup::Sky, diffusive * light * weight);
+                        }
+                    }
+                    ray = match material.reflect(&intersection, &surface, lobe) {
                         Some(ray) => {
-                            diffusive *= ray.direction.dot(normal);
+                            if lobe == Lobe::Diffuse {
+                                diffusive *= ray.direction.dot(surface.normal).max(0.0);
+                            }
                             let mut ray = ray;
                             ray.origin += normal * EPSILON;
                             ray
@@ -107,28 +284,12 @@ impl<T: Camera, K: Intersectable> FragmentRender for PathTracer<T, K> {
                 }
                 None => {
                     //The sky is blue
-                    let sky_color = match self.skybox {
-                        //Skybox color
-                        Some(skybox) => { 
-                            let sampler = NearestSampler {};
-                            let texture = ctx.repo.get(skybox).unwrap();
-                            let longitude = ray.direction.x().atan2(ray.direction.z());
-                            let latitude = -(ray.direction.y() / ray.direction.length()).asin();
-                            let longitude = (longitude / PI + 1.0) * 0.5;
-                            let latitude = (latitude / (PI / 2.0) + 1.0) * 0.5;
-                            let res = sampler.sample(texture, vector![longitude, latitude]);
-                            if bounce == 0{
-                                res
-                            }else{
-                                res * 3.0
-                            }
-                        }
-                        //Default skybox color
-                        _ => {
-                            Vec3::default()
-                        }
+                    let sky_color = scene.sky_color(ctx, ray.direction, bounce);
+                    let weight = match sampled_portals {
+                        Some(pdf) => pdf / (scene.portal_pdf(ray) + pdf),
+                        None => 1.0,
                     };
-                    emissive += diffusive * sky_color;
+                    emissive.add(LightGroup::Sky, diffusive * sky_color * weight);
                     break;
                 }
             }
@@ -136,3 +297,21 @@ impl<T: Camera, K: Intersectable> FragmentRender for PathTracer<T, K> {
         emissive
     }
 }
+
+impl Integrator for PathTracer {
+    fn integrate<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        ray: Ray,
+        spread: f64,
+    ) -> LightLayers {
+        let path = Path {
+            ray,
+            bounce: 0,
+            diffusive: Vec3::from_single(1.0),
+            sampled_portals: None,
+        };
+        self.trace(scene, ctx, path, spread)
+    }
+}
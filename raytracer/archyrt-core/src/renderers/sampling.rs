@@ -1,4 +1,13 @@
-use crate::{api::fragment_render::{FragmentRender, FragmentContext}, utilities::math::{Vec2, Vec3}};
+use crate::{
+    api::{
+        fragment_render::{FragmentContext, FragmentRender},
+        light_layers::{LayeredFragmentRender, LightLayers},
+    },
+    utilities::{
+        math::{Vec2, Vec3},
+        random,
+    },
+};
 
 pub struct SamplingRenderer<Renderer: FragmentRender + Sync + Send> {
     pub inner: Renderer,
@@ -19,3 +28,44 @@ impl<Renderer: FragmentRender + Sync + Send> FragmentRender for SamplingRenderer
             / (self.samples as f64)
     }
 }
+
+impl<Renderer: LayeredFragmentRender + Sync + Send> LayeredFragmentRender
+    for SamplingRenderer<Renderer>
+{
+    fn render_layers(&self, ctx: &FragmentContext, pos: Vec2) -> LightLayers {
+        (0..self.samples)
+            .map(|_| self.inner.render_layers(ctx, pos))
+            .fold(LightLayers::new(), |mut a, b| {
+                a += b;
+                a
+            })
+            / (self.samples as f64)
+    }
+}
+
+/// Seeds the random numbers of every fragment from its position, so renders can be repeated exactly
+pub struct SeededRenderer<Renderer: FragmentRender> {
+    pub inner: Renderer,
+    pub seed: u64,
+}
+
+impl<Renderer: FragmentRender> SeededRenderer<Renderer> {
+    fn seed_fragment(&self, pos: Vec2) {
+        //SplitMix64 finalizer of the seed and the position
+        let mut z = self.seed;
+        for value in pos.inner {
+            z = (z ^ value.to_bits()).wrapping_add(0x9e3779b97f4a7c15);
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^= z >> 31;
+        }
+        random::seed(z);
+    }
+}
+
+impl<Renderer: FragmentRender> FragmentRender for SeededRenderer<Renderer> {
+    fn render_fragment(&self, ctx: &FragmentContext, pos: Vec2) -> Vec3 {
+        self.seed_fragment(pos);
+        self.inner.render_fragment(ctx, pos)
+    }
+}
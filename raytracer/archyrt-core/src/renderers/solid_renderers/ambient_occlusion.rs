@@ -0,0 +1,37 @@
+use crate::{
+    api::{
+        camera::Camera,
+        fragment_render::{FragmentContext, FragmentRender},
+    },
+    renderers::ambient_occlusion::visibility,
+    utilities::{
+        math::{Vec2, Vec3},
+        ray::Intersectable,
+    },
+};
+
+/// White where the surface is open and dark where nearby geometry occludes it, background is black
+pub struct AmbientOcclusionRenderer<T: Camera, K: Intersectable> {
+    pub camera: T,
+    pub object: K,
+    /// Occluders further away than this are ignored
+    pub radius: f64,
+    /// Rays cast per fragment
+    pub samples: usize,
+}
+
+impl<T: Camera, K: Intersectable> FragmentRender for AmbientOcclusionRenderer<T, K> {
+    fn render_fragment(&self, ctx: &FragmentContext, pos: Vec2) -> Vec3 {
+        let ray = self.camera.get_ray(ctx, pos);
+        match self.object.intersect(ray) {
+            Some(intersection) => Vec3::from_single(visibility(
+                &self.object,
+                intersection.get_pos(),
+                intersection.get_normal(),
+                self.samples,
+                self.radius,
+            )),
+            None => Vec3::default(),
+        }
+    }
+}
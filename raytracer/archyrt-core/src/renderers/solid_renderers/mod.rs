@@ -1,2 +1,3 @@
 pub mod albedo;
+pub mod ambient_occlusion;
 pub mod normal;
@@ -0,0 +1,633 @@
+use crate::{
+    api::{fragment_render::FragmentContext, light_layers::LightLayers},
+    intersectables::area_light::AreaLights,
+    renderers::{
+        integrator::{Integrator, Scene},
+        media::Media,
+    },
+    textures::texture_repo::TextureRepository,
+    utilities::{
+        math::Vec3,
+        ray::{Intersectable, Ray},
+    },
+};
+
+/// Scene of an object without a skybox, lights or media
+fn scene<K: Intersectable>(object: K) -> Scene<K> {
+    Scene {
+        object,
+        skybox: None,
+        portals: Vec::new(),
+        lights: AreaLights::default(),
+        anisotropy: 1,
+        media: Media::default(),
+        sun: None,
+    }
+}
+
+fn integrate_scene<K: Intersectable, I: Integrator>(
+    integrator: I,
+    scene: &Scene<K>,
+    ray: Ray,
+) -> LightLayers {
+    let repo = TextureRepository::new();
+    let ctx = FragmentContext {
+        width: 1.0,
+        height: 1.0,
+        repo: &repo,
+    };
+    integrator.integrate(scene, &ctx, ray, 0.0)
+}
+
+fn integrate<K: Intersectable, I: Integrator>(integrator: I, object: K, ray: Ray) -> LightLayers {
+    integrate_scene(integrator, &scene(object), ray)
+}
+
+/// Ray along the z axis, starting at `z`
+fn forward(z: f64) -> Ray {
+    Ray::new(Vec3::new(0.0, 0.0, z), Vec3::new(0.0, 0.0, 1.0))
+}
+
+mod named {
+    use crate::renderers::integrator::NamedIntegrator;
+
+    #[test]
+    fn names() {
+        for name in NamedIntegrator::NAMES {
+            assert_eq!(NamedIntegrator::from_name(name).unwrap().name(), name);
+        }
+        assert!(NamedIntegrator::from_name("unknown").is_none());
+    }
+}
+
+mod ambient_occlusion {
+    use crate::{
+        api::light_layers::LightGroup,
+        intersectables::sphere::Sphere,
+        renderers::{
+            ambient_occlusion::AmbientOcclusion,
+            tests::{forward, integrate},
+        },
+        utilities::math::Vec3,
+    };
+
+    fn sphere(radius: f64) -> Sphere {
+        Sphere {
+            radius,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unoccluded() {
+        let layers = integrate(AmbientOcclusion::default(), sphere(1.0), forward(-5.0));
+        assert_eq!(layers.get(LightGroup::Sky), Vec3::from_single(1.0));
+    }
+
+    #[test]
+    fn enclosed() {
+        let ao = AmbientOcclusion {
+            samples: 16,
+            distance: 10.0,
+        };
+        let layers = integrate(ao, vec![sphere(1.0), sphere(1.5)], forward(-1.25));
+        assert_eq!(layers.get(LightGroup::Sky), Vec3::default());
+        //Occluders further away are ignored
+        let ao = AmbientOcclusion {
+            samples: 16,
+            distance: 0.1,
+        };
+        let layers = integrate(ao, vec![sphere(1.0), sphere(1.5)], forward(-1.25));
+        assert_eq!(layers.get(LightGroup::Sky), Vec3::from_single(1.0));
+    }
+}
+
+mod whitted {
+    use crate::{
+        api::{fragment_render::FragmentContext, light_layers::LightGroup},
+        intersectables::{area_light::AreaLights, sphere::Sphere, triangle::Triangle},
+        renderers::{
+            integrator::{Integrator, HEMISPHERE_PDF},
+            media::Sun,
+            path_tracer::Material,
+            tests::{forward, integrate, integrate_scene, scene},
+            whitted::Whitted,
+        },
+        textures::{texture::Texture, texture_repo::TextureRepository, TextureID},
+        utilities::{math::Vec3, ray::Ray},
+        vector,
+    };
+
+    #[test]
+    fn emissive() {
+        let lamp = Sphere {
+            color: Vec3::new(1.0, 0.5, 0.25),
+            material: Material::Emissive { power: 2.0 },
+            ..Default::default()
+        };
+        let layers = integrate(Whitted::default(), lamp, forward(-5.0));
+        assert_eq!(layers.get(LightGroup::Emissive), Vec3::new(2.0, 1.0, 0.5));
+        assert_eq!(layers.beauty(), Vec3::new(2.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn unlit() {
+        //Without a skybox nothing lights diffuse surfaces
+        let layers = integrate(Whitted::default(), Sphere::default(), forward(-5.0));
+        assert_eq!(layers.beauty(), Vec3::default());
+    }
+
+    #[test]
+    fn sunlit() {
+        let mut scene = scene(Sphere::default());
+        scene.sun = Some(Sun {
+            direction: Vec3::new(0.0, 0.0, -1.0),
+            color: Vec3::from_single(1.0),
+        });
+        let layers = integrate_scene(Whitted::default(), &scene, forward(-5.0));
+        assert!((layers.get(LightGroup::Sun).x() - HEMISPHERE_PDF).abs() < 1e-9);
+        //The back of the sphere is in its own shadow
+        scene.sun = Some(Sun {
+            direction: Vec3::new(0.0, 0.0, 1.0),
+            color: Vec3::from_single(1.0),
+        });
+        let layers = integrate_scene(Whitted::default(), &scene, forward(-5.0));
+        assert_eq!(layers.beauty(), Vec3::default());
+    }
+
+    #[test]
+    fn area_light() {
+        let white = TextureID::new(&"white");
+        let mut texture = Texture::new(1, 1);
+        texture.set(0, Vec3::from_single(1.0));
+        let mut repo = TextureRepository::new();
+        repo.insert(white, texture);
+        let uv = [vector![0.0, 0.0], vector![1.0, 0.0], vector![0.0, 1.0]];
+        let floor = Triangle::new(
+            [
+                Vec3::new(-10.0, 0.0, -10.0),
+                Vec3::new(-10.0, 0.0, 10.0),
+                Vec3::new(10.0, 0.0, -10.0),
+            ],
+            uv,
+            white,
+            Material::Diffuse,
+        );
+        //Facing down
+        let lamp = Triangle::new(
+            [
+                Vec3::new(-1.0, 1.0, -1.0),
+                Vec3::new(1.0, 1.0, -1.0),
+                Vec3::new(-1.0, 1.0, 1.0),
+            ],
+            uv,
+            white,
+            Material::Emissive { power: 1.0 },
+        );
+        let triangles = vec![floor, lamp];
+        let mut scene = scene(triangles.clone());
+        scene.lights = AreaLights::new(&triangles);
+        let ctx = FragmentContext {
+            width: 1.0,
+            height: 1.0,
+            repo: &repo,
+        };
+        let ray = Ray::new(Vec3::new(-2.0, 0.5, -2.0), Vec3::new(0.0, -1.0, 0.0));
+        let layers = Whitted::default().integrate(&scene, &ctx, ray, 0.0);
+        let light = layers.get(LightGroup::Emissive);
+        assert!(light.x() > 0.0);
+        assert_eq!(layers.beauty(), light);
+        //One shadow ray per light, so the estimate has no noise
+        let again = Whitted::default().integrate(&scene, &ctx, ray, 0.0);
+        assert_eq!(again.get(LightGroup::Emissive), light);
+    }
+}
+
+mod path_tracer {
+    use crate::{
+        api::light_layers::LightGroup,
+        intersectables::sphere::Sphere,
+        renderers::{
+            integrator::NamedIntegrator,
+            path_tracer::Material,
+            tests::{forward, integrate},
+        },
+        utilities::math::Vec3,
+    };
+
+    #[test]
+    fn emissive() {
+        let lamp = Sphere {
+            color: Vec3::from_single(1.0),
+            material: Material::Emissive { power: 3.0 },
+            ..Default::default()
+        };
+        let integrator = NamedIntegrator::from_name("path").unwrap();
+        let layers = integrate(integrator, lamp, forward(-5.0));
+        assert_eq!(layers.get(LightGroup::Emissive), Vec3::from_single(3.0));
+    }
+}
+
+mod bidirectional {
+    use crate::{
+        api::fragment_render::{FragmentContext, FragmentRender},
+        cameras::perspective::PerspectiveCamera,
+        intersectables::{area_light::AreaLights, triangle::Triangle},
+        renderers::{
+            bidirectional::BidirectionalPathTracer,
+            integrator::{Integrator, IntegratorRenderer, NamedIntegrator, Scene},
+            media::Media,
+            path_tracer::{Material, PathTracer},
+        },
+        textures::{texture::Texture, texture_repo::TextureRepository, TextureID},
+        utilities::{math::Vec3, random},
+        vector,
+    };
+
+    /// Two triangles facing towards `u` × `v`
+    fn quad(
+        corner: Vec3,
+        u: Vec3,
+        v: Vec3,
+        texture: TextureID,
+        material: Material,
+    ) -> [Triangle; 2] {
+        let uv = [vector![0.0, 0.0], vector![1.0, 0.0], vector![1.0, 1.0]];
+        [
+            Triangle::new([corner, corner + u, corner + u + v], uv, texture, material),
+            Triangle::new([corner, corner + u + v, corner + v], uv, texture, material),
+        ]
+    }
+
+    /// Floor lit by a lamp facing down and its reflection off a wall
+    fn room(repo: &mut TextureRepository) -> Vec<Triangle> {
+        let white = TextureID::new(&"white");
+        let mut texture = Texture::new(1, 1);
+        texture.set(0, Vec3::from_single(0.8));
+        repo.insert(white, texture);
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        let mut triangles = Vec::new();
+        triangles.extend(quad(
+            Vec3::new(-5.0, 0.0, -5.0),
+            z * 10.0,
+            x * 10.0,
+            white,
+            Material::Diffuse,
+        ));
+        triangles.extend(quad(
+            Vec3::new(1.0, 0.0, -5.0),
+            z * 10.0,
+            y * 2.0,
+            white,
+            Material::Diffuse,
+        ));
+        let lamp = Material::Emissive { power: 1.0 };
+        triangles.extend(quad(Vec3::new(-0.5, 1.0, -0.5), x, z, white, lamp));
+        triangles
+    }
+
+    /// Mean of the red channel of the center of the room and the standard error of that mean
+    fn estimate<I: Integrator + Send + Sync>(
+        integrator: I,
+        samples: usize,
+        seed: u64,
+    ) -> (f64, f64) {
+        let mut repo = TextureRepository::new();
+        let triangles = room(&mut repo);
+        let scene = Scene {
+            lights: AreaLights::new(&triangles),
+            object: triangles,
+            skybox: None,
+            portals: Vec::new(),
+            anisotropy: 1,
+            media: Media::default(),
+            sun: None,
+        };
+        let ctx = FragmentContext {
+            width: 1.0,
+            height: 1.0,
+            repo: &repo,
+        };
+        //The center of the image is seen along the camera's direction
+        let camera =
+            PerspectiveCamera::new(Vec3::new(0.0, 0.5, -1.5), Vec3::new(0.0, -0.5, 1.5), 1.0);
+        let renderer = IntegratorRenderer {
+            camera,
+            scene,
+            integrator,
+        };
+        random::seed(seed);
+        let (sum, sum_squared) = (0..samples)
+            .map(|_| renderer.render_fragment(&ctx, vector![0.5, 0.5]).x())
+            .fold((0.0, 0.0), |(sum, sum_squared), x| {
+                (sum + x, sum_squared + x * x)
+            });
+        let n = samples as f64;
+        let mean = sum / n;
+        let variance = (sum_squared / n - mean * mean) * n / (n - 1.0);
+        (mean, (variance / n).sqrt())
+    }
+
+    #[test]
+    fn matches_path_tracing() {
+        for seed in 1..4 {
+            let (path, path_error) = estimate(PathTracer::default(), 100000, seed);
+            let (bidirectional, bidirectional_error) =
+                estimate(BidirectionalPathTracer::default(), 5000, seed);
+            assert!(path > 0.0);
+            //Both estimates are unbiased, so they differ by less than four standard errors of the difference
+            let tolerance = 4.0 * (path_error.powi(2) + bidirectional_error.powi(2)).sqrt();
+            //Tight enough to notice a bias of a few percent
+            assert!(tolerance < 0.05 * path);
+            assert!((bidirectional - path).abs() < tolerance);
+        }
+    }
+
+    #[test]
+    fn named() {
+        assert!(matches!(
+            NamedIntegrator::from_name("bdpt"),
+            Some(NamedIntegrator::Bidirectional(_))
+        ));
+        //Fog and sun are only rejected for the bidirectional path tracer
+        assert!(!NamedIntegrator::from_name("bdpt")
+            .unwrap()
+            .renders_atmosphere());
+        assert!(NamedIntegrator::from_name("path")
+            .unwrap()
+            .renders_atmosphere());
+    }
+}
+
+mod solid_renderers {
+    use crate::{
+        api::fragment_render::{FragmentContext, FragmentRender},
+        cameras::perspective::PerspectiveCamera,
+        intersectables::sphere::Sphere,
+        renderers::solid_renderers::ambient_occlusion::AmbientOcclusionRenderer,
+        textures::texture_repo::TextureRepository,
+        utilities::math::Vec3,
+        vector,
+    };
+
+    /// Ambient occlusion of a unit sphere inside a shell, seen from between them
+    fn enclosed(radius: f64) -> Vec3 {
+        let repo = TextureRepository::new();
+        let ctx = FragmentContext {
+            width: 1.0,
+            height: 1.0,
+            repo: &repo,
+        };
+        let shell = Sphere {
+            radius: 1.5,
+            ..Default::default()
+        };
+        let renderer = AmbientOcclusionRenderer {
+            camera: PerspectiveCamera::new(
+                Vec3::new(0.0, 0.0, -1.25),
+                Vec3::new(0.0, 0.0, 1.0),
+                1.0,
+            ),
+            object: vec![Sphere::default(), shell],
+            radius,
+            samples: 16,
+        };
+        renderer.render_fragment(&ctx, vector![0.5, 0.5])
+    }
+
+    #[test]
+    fn ambient_occlusion() {
+        assert_eq!(enclosed(10.0), Vec3::default());
+        //The shell is further than the radius
+        assert_eq!(enclosed(0.1), Vec3::from_single(1.0));
+    }
+}
+
+mod heatmap {
+    use crate::{renderers::heatmap::false_color, utilities::math::Vec3};
+
+    #[test]
+    fn gradient() {
+        assert_eq!(false_color(0.0), Vec3::default());
+        assert_eq!(false_color(1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(false_color(5.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(false_color(0.2), Vec3::new(0.0, 0.0, 1.0));
+    }
+}
+
+#[cfg(feature = "stats")]
+mod heatmap_renderer {
+    use crate::{
+        api::fragment_render::{FragmentContext, FragmentRender},
+        cameras::perspective::PerspectiveCamera,
+        intersectables::sphere::Sphere,
+        renderers::{
+            heatmap::{false_color, HeatmapMetric, HeatmapRenderer},
+            integrator::IntegratorRenderer,
+            path_tracer::PathTracer,
+            tests::scene,
+        },
+        textures::texture_repo::TextureRepository,
+        utilities::math::Vec3,
+        vector,
+    };
+
+    #[test]
+    fn names() {
+        for name in HeatmapMetric::NAMES {
+            assert_eq!(HeatmapMetric::from_name(name).unwrap().name(), name);
+        }
+        assert!(HeatmapMetric::from_name("bounces").is_none());
+    }
+    fn depth(direction: Vec3) -> f64 {
+        let repo = TextureRepository::new();
+        let ctx = FragmentContext {
+            width: 1.0,
+            height: 1.0,
+            repo: &repo,
+        };
+        let renderer = IntegratorRenderer {
+            camera: PerspectiveCamera::new(Vec3::new(0.0, 0.0, -5.0), direction, 1.0),
+            scene: scene(Sphere::default()),
+            integrator: PathTracer::default(),
+        };
+        let heatmap = HeatmapRenderer::new(&renderer, HeatmapMetric::Depth);
+        let cost = heatmap.cost(&ctx, vector![0.5, 0.5]);
+        assert_eq!(
+            heatmap.render_fragment(&ctx, vector![0.5, 0.5]),
+            false_color(cost / HeatmapMetric::Depth.default_scale())
+        );
+        cost
+    }
+
+    #[test]
+    fn bounces() {
+        //Rays leaving a convex object never hit it again
+        assert_eq!(depth(Vec3::new(0.0, 0.0, 1.0)), 1.0);
+        assert_eq!(depth(Vec3::new(0.0, 0.0, -1.0)), 0.0);
+    }
+}
+
+mod media {
+    use std::f64::consts::PI;
+
+    use crate::{
+        api::light_layers::LightGroup,
+        intersectables::sphere::Sphere,
+        renderers::{
+            integrator::{Scene, HEMISPHERE_PDF},
+            media::{Atmosphere, Convex, Media, Medium, Sun, Volume},
+            path_tracer::PathTracer,
+            tests::{forward, integrate_scene, scene},
+        },
+        utilities::{
+            math::Vec3,
+            ray::{Intersectable, Ray},
+        },
+    };
+
+    /// Medium which scatters all light it extinguishes equally in every direction
+    fn medium(density: f64) -> Medium {
+        Medium {
+            density,
+            albedo: Vec3::from_single(1.0),
+            anisotropy: 0.0,
+        }
+    }
+    /// Unit cube between z=0 and z=1 filled with a medium
+    fn cube(density: f64) -> Media {
+        Media {
+            volumes: vec![Volume {
+                shape: Convex::aabb(Vec3::new(-0.5, -0.5, 0.0), Vec3::new(0.5, 0.5, 1.0)),
+                medium: medium(density),
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// Mean sunlight seen from the origin in a direction
+    fn sunlight<K: Intersectable>(scene: &Scene<K>, direction: Vec3) -> f64 {
+        let samples = 2000;
+        let ray = Ray::new(Vec3::default(), direction);
+        (0..samples)
+            .map(|_| {
+                integrate_scene(PathTracer::default(), scene, ray)
+                    .get(LightGroup::Sun)
+                    .x()
+            })
+            .sum::<f64>()
+            / samples as f64
+    }
+
+    #[test]
+    fn transmittance() {
+        let media = cube(1.0);
+        assert!((media.transmittance(forward(-5.0), f64::INFINITY) - (-1.0f64).exp()).abs() < 1e-9);
+        //Stopping before the cube or missing it
+        assert_eq!(media.transmittance(forward(-5.0), 4.0), 1.0);
+        let ray = Ray::new(Vec3::new(2.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(media.transmittance(ray, f64::INFINITY), 1.0);
+        //Overlapping media add up
+        let media = Media {
+            fog: Some(medium(0.1)),
+            fog_extent: 10.0,
+            ..cube(1.0)
+        };
+        assert!((media.transmittance(forward(0.0), 5.0) - (-1.5f64).exp()).abs() < 1e-9);
+        assert!((media.transmittance(forward(0.0), f64::INFINITY) - (-2.0f64).exp()).abs() < 1e-9);
+    }
+    #[test]
+    fn free_paths() {
+        let media = cube(1.0);
+        let mut rng = rand::thread_rng();
+        let samples = 10000;
+        let mut passed = 0;
+        for _ in 0..samples {
+            match media.sample_distance(forward(-5.0), f64::INFINITY, &mut rng) {
+                Some((t, _)) => assert!((5.0..=6.0).contains(&t)),
+                None => passed += 1,
+            }
+        }
+        let passed = passed as f64 / samples as f64;
+        assert!((passed - (-1.0f64).exp()).abs() < 0.03);
+    }
+    #[test]
+    fn phase() {
+        let medium = Medium {
+            anisotropy: 0.6,
+            ..medium(1.0)
+        };
+        //The phase function integrates to 1 over the sphere
+        let steps = 1000;
+        let integral: f64 = (0..steps)
+            .map(|i| {
+                let cos = -1.0 + (i as f64 + 0.5) * 2.0 / steps as f64;
+                medium.phase(cos) * 2.0 * PI * 2.0 / steps as f64
+            })
+            .sum();
+        assert!((integral - 1.0).abs() < 1e-3);
+        //The mean cosine of scattered directions is the anisotropy
+        let direction = Vec3::new(0.0, 0.0, 1.0);
+        let mut rng = rand::thread_rng();
+        let samples = 20000;
+        let mean: f64 = (0..samples)
+            .map(|_| medium.sample_phase(direction, &mut rng).dot(direction))
+            .sum::<f64>()
+            / samples as f64;
+        assert!((mean - 0.6).abs() < 0.02);
+    }
+    #[test]
+    fn sunlit_surface() {
+        let sphere = Sphere {
+            color: Vec3::from_single(1.0),
+            ..Default::default()
+        };
+        let mut lit = scene(sphere);
+        lit.sun = Some(Sun {
+            direction: Vec3::new(0.0, 0.0, -1.0),
+            color: Vec3::from_single(2.0),
+        });
+        let layers = integrate_scene(PathTracer::default(), &lit, forward(-5.0));
+        assert!(
+            (layers.get(LightGroup::Sun) - Vec3::from_single(2.0 * HEMISPHERE_PDF)).length() < 1e-9
+        );
+        //Sun behind the sphere
+        lit.sun = Some(Sun {
+            direction: Vec3::new(0.0, 0.0, 1.0),
+            color: Vec3::from_single(2.0),
+        });
+        let layers = integrate_scene(PathTracer::default(), &lit, forward(-5.0));
+        assert_eq!(layers.get(LightGroup::Sun), Vec3::default());
+    }
+    #[test]
+    fn god_rays() {
+        let atmosphere = Atmosphere {
+            fog: Some(Medium {
+                density: 0.5,
+                ..Default::default()
+            }),
+            fog_extent: 10.0,
+            sun: Some(Sun {
+                direction: Vec3::new(0.0, 0.0, 1.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut foggy = scene(Vec::<Sphere>::new());
+        foggy.media = atmosphere.media(&[]);
+        foggy.sun = atmosphere.sun;
+        //Light is scattered forward, towards the sun
+        let towards = sunlight(&foggy, Vec3::new(0.0, 0.0, 1.0));
+        let away = sunlight(&foggy, Vec3::new(0.0, 0.0, -1.0));
+        assert!(towards > away * 2.0 && away > 0.0);
+        //Without fog, there is no sunlight
+        foggy.media = Media::default();
+        assert_eq!(sunlight(&foggy, Vec3::new(0.0, 0.0, 1.0)), 0.0);
+        assert_eq!(
+            Atmosphere::from_json(&atmosphere.to_json().unwrap()).unwrap(),
+            atmosphere
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use rand_distr::{Distribution, UnitSphere};
+
+use crate::{
+    api::{
+        fragment_render::FragmentContext,
+        light_layers::{LightGroup, LightLayers},
+    },
+    intersectables::bvh::record_depth,
+    textures::color_provider::{ColorProvider, RayCone},
+    utilities::{
+        math::Vec3,
+        random,
+        ray::{Intersectable, Ray},
+    },
+};
+
+use super::{
+    integrator::{Integrator, Scene, EPSILON, HEMISPHERE_PDF},
+    path_tracer::Material,
+};
+
+/// Shadow rays hitting closer than this fraction of the distance to the light are blocked
+const SHADOW_BIAS: f64 = 1e-4;
+
+/// Direct light with one shadow ray towards the sun and the center of every area light, and sharp mirror
+/// reflections followed up to a depth. Skylight is sampled through a random portal, or a random direction
+/// without portals, so it is the only noisy part of the image.
+///
+/// Light is attenuated by the media along shadow rays, but not scattered by them.
+pub struct Whitted {
+    /// Maximum number of surfaces a ray is reflected off
+    pub depth: usize,
+}
+
+impl Default for Whitted {
+    fn default() -> Self {
+        Self { depth: 3 }
+    }
+}
+
+impl Whitted {
+    /// Light arriving at a diffuse surface, scaled like the path tracer's diffuse bounces
+    fn direct_light<K: Intersectable>(
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        pos: Vec3,
+        normal: Vec3,
+        bounce: usize,
+    ) -> LightLayers {
+        let mut layers = LightLayers::new();
+        let origin = pos + normal * EPSILON;
+        if let Some((sun, light)) = scene.sunlight(origin) {
+            let cos = sun.dot(normal).max(0.0);
+            layers.add(LightGroup::Sun, light * (cos * HEMISPHERE_PDF));
+        }
+        for light in &scene.lights.lights {
+            let sample = match light.center() {
+                Some(sample) => sample,
+                None => continue,
+            };
+            let offset = sample.pos - origin;
+            let distance = offset.length();
+            let direction = offset / distance;
+            let cos = direction.dot(normal);
+            let cos_light = -direction.dot(sample.normal);
+            if cos <= 0.0 || cos_light <= 0.0 {
+                continue;
+            }
+            //Anything closer than the light itself casts a shadow
+            let ray = Ray::new(origin, direction);
+            let blocked = scene
+                .object
+                .intersect(ray)
+                .is_some_and(|hit| hit.get_distance() < distance * (1.0 - SHADOW_BIAS));
+            if blocked {
+                continue;
+            }
+            let material = sample.color.get_material();
+            if let Some((group, emission)) =
+                material.emission(&sample.color, ctx.repo, RayCone::default())
+            {
+                let solid_angle = light.triangle.area() * cos_light / (distance * distance);
+                let transmittance = scene.media.transmittance(ray, distance);
+                layers.add(
+                    group,
+                    emission * transmittance * (cos * solid_angle * HEMISPHERE_PDF),
+                );
+            }
+        }
+        if scene.skybox.is_none() {
+            return layers;
+        }
+        if !scene.portals.is_empty() {
+            if let Some((light, _)) = scene.sample_portals(ctx, pos, normal, bounce) {
+                layers.add(LightGroup::Sky, light);
+            }
+            return layers;
+        }
+        //Uniformly sampled hemisphere direction
+        let p: [f64; 3] = UnitSphere.sample(&mut random::rng());
+        let p = Vec3::new(p[0], p[1], p[2]);
+        let direction = if p.dot(normal) < 0.0 { -p } else { p };
+        let ray = Ray::new(origin, direction);
+        if scene.object.intersect(ray).is_none() {
+            let sky = scene.sky_color(ctx, direction, bounce + 1) * direction.dot(normal);
+            layers.add(LightGroup::Sky, sky);
+        }
+        layers
+    }
+}
+
+impl Integrator for Whitted {
+    fn integrate<K: Intersectable>(
+        &self,
+        scene: &Scene<K>,
+        ctx: &FragmentContext,
+        ray: Ray,
+        spread: f64,
+    ) -> LightLayers {
+        let mut ray = ray;
+        let mut layers = LightLayers::new();
+        let mut throughput = Vec3::from_single(1.0);
+        let mut cone_width = 0.0;
+        for bounce in 0..self.depth {
+            let intersection = match scene.object.intersect(ray) {
+                Some(intersection) => intersection,
+                None => {
+                    let sky_color = scene.sky_color(ctx, ray.direction, bounce);
+                    layers.add(LightGroup::Sky, throughput * sky_color);
+                    break;
+                }
+            };
+            record_depth(bounce + 1);
+            cone_width += spread * intersection.get_distance();
+            let cone = RayCone {
+                width: cone_width,
+                anisotropy: scene.anisotropy,
+            };
+            let color = intersection.get_color_filtered(ctx.repo, cone);
+            let material = intersection.get_material();
+            match material {
+                Material::Emissive { power } => {
+                    layers.add(LightGroup::Emissive, throughput * color * power);
+                    break;
+                }
+                Material::DiffuseAndEmissive { .. } => {
+                    if let Some((group, emission)) =
+                        material.emission(intersection.ref_color_provider(), ctx.repo, cone)
+                    {
+                        layers.add(group, throughput * emission);
+                    }
+                }
+                Material::Diffuse => {}
+            }
+            let surface = intersection.get_surface(ctx.repo, cone);
+            let specular = surface.specular_chance();
+            //Both lobes are evaluated, weighted by their probabilities
+            let direct = Self::direct_light(
+                scene,
+                ctx,
+                intersection.get_pos(),
+                surface.normal,
+                bounce,
+            );
+            layers += direct * (throughput * color * (surface.occlusion * (1.0 - specular)));
+            if specular <= 0.0 {
+                break;
+            }
+            throughput *= surface.specular_tint(color) * specular;
+            let d = ray.direction.normalized();
+            let mirror = d - surface.normal * (2.0 * d.dot(surface.normal));
+            ray = Ray::new(
+                intersection.get_pos() + intersection.get_normal() * EPSILON,
+                mirror,
+            );
+        }
+        layers
+    }
+}
@@ -4,10 +4,74 @@ use crate::{renderers::path_tracer::Material, utilities::math::Vec3};
 
 use super::texture_repo::TextureRepository;
 
+/// Cone of rays around a traced ray, used to filter textures over the area it covers
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RayCone {
+    /// Width of the cone where it hits the surface
+    pub width: f64,
+    /// Maximum number of anisotropic texture taps
+    pub anisotropy: usize,
+}
+
+/// Lobe of the surface's reflectance a bounce is sampled from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lobe {
+    Diffuse,
+    Specular,
+}
+
+/// Shading inputs at a surface point, read from its surface maps
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceProperties {
+    /// Shading normal, perturbed by normal or bump maps
+    pub normal: Vec3,
+    pub roughness: f64,
+    pub metallic: f64,
+    /// Ambient occlusion, darkens the diffuse reflectance of cavities
+    pub occlusion: f64,
+}
+
+impl SurfaceProperties {
+    /// Fully rough dielectric surface, which reflects purely diffusely
+    pub fn new(normal: Vec3) -> Self {
+        Self {
+            normal,
+            roughness: 1.0,
+            metallic: 0.0,
+            occlusion: 1.0,
+        }
+    }
+    /// Probability of reflecting specularly. Dielectrics reflect 4% at normal incidence, which fades out as they get rougher.
+    pub fn specular_chance(&self) -> f64 {
+        let dielectric = 0.04 * (1.0 - self.roughness);
+        dielectric + (1.0 - dielectric) * self.metallic
+    }
+    pub fn choose_lobe<R: rand::Rng>(&self, rng: &mut R) -> Lobe {
+        if rng.gen::<f64>() < self.specular_chance() {
+            Lobe::Specular
+        } else {
+            Lobe::Diffuse
+        }
+    }
+    /// Color of specular reflections, metals tint them with their albedo
+    pub fn specular_tint(&self, albedo: Vec3) -> Vec3 {
+        Vec3::from_single(1.0 - self.metallic) + albedo * self.metallic
+    }
+}
+
 pub trait ColorProvider {
     fn get_color(&self, repo: &TextureRepository) -> Vec3;
     fn get_material(&self) -> Material;
     fn sample(&self, repo: &TextureRepository, id: TextureID) -> Vec3;
+    fn get_color_filtered(&self, repo: &TextureRepository, _cone: RayCone) -> Vec3 {
+        self.get_color(repo)
+    }
+    fn sample_filtered(&self, repo: &TextureRepository, id: TextureID, _cone: RayCone) -> Vec3 {
+        self.sample(repo, id)
+    }
+    fn get_surface(&self, _repo: &TextureRepository, normal: Vec3, _cone: RayCone) -> SurfaceProperties {
+        SurfaceProperties::new(normal)
+    }
 }
 
 #[derive(Default, Clone)]
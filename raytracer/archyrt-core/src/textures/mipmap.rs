@@ -0,0 +1,56 @@
+use crate::utilities::math::Vec3;
+
+use super::texture::Texture;
+
+/// Texture with its successively halved versions, down to 1x1
+pub struct MipMap {
+    pub levels: Vec<Texture>,
+}
+
+impl MipMap {
+    pub fn new(texture: Texture) -> Self {
+        let mut levels = vec![texture];
+        loop {
+            let last = levels.last().unwrap();
+            if last.width() <= 1 && last.height() <= 1 {
+                break;
+            }
+            let next = downsample(last);
+            levels.push(next);
+        }
+        Self { levels }
+    }
+    /// Full resolution texture
+    pub fn base(&self) -> &Texture {
+        &self.levels[0]
+    }
+    pub fn level(&self, level: usize) -> &Texture {
+        &self.levels[level.min(self.levels.len() - 1)]
+    }
+    /// Size of all levels in bytes
+    pub fn memory(&self) -> usize {
+        self.levels.iter().map(|level| level.memory()).sum()
+    }
+}
+
+/// Averages every 2x2 block of texels
+fn downsample(texture: &Texture) -> Texture {
+    let w = texture.width();
+    let h = texture.height();
+    let mut output = Texture::with_format((w / 2).max(1), (h / 2).max(1), texture.format());
+    for y in 0..output.height() {
+        for x in 0..output.width() {
+            let x1 = (x * 2).min(w - 1);
+            let x2 = (x * 2 + 1).min(w - 1);
+            let y1 = (y * 2).min(h - 1);
+            let y2 = (y * 2 + 1).min(h - 1);
+            let sum = [(x1, y1), (x2, y1), (x1, y2), (x2, y2)]
+                .iter()
+                .map(|(x, y)| texture.data.get((y * w + x) as usize))
+                .fold(Vec3::default(), |a, b| a + b);
+            let index = (y * output.width() + x) as usize;
+            output.set(index, sum / 4.0);
+        }
+    }
+    output
+}
@@ -1,12 +1,18 @@
 use std::collections::hash_map::DefaultHasher;
+use std::fmt;
 use std::hash::{Hash, Hasher};
 
+use serde::{Deserialize, Serialize};
+
 pub mod color_provider;
+pub mod mipmap;
 pub mod samplers;
 pub mod texture;
 pub mod texture_repo;
+#[cfg(test)]
+mod tests;
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TextureID(u64);
 
 impl TextureID {
@@ -17,6 +23,12 @@ impl TextureID {
     }
 }
 
+impl fmt::Display for TextureID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
 impl Default for TextureID {
     fn default() -> Self {
         Self::new(&1u32)
@@ -4,13 +4,16 @@ use crate::{
     vector,
 };
 
-use super::{nearest::NearestSampler, TextureSampler};
+use super::{nearest::NearestSampler, TextureSampler, WrapMode};
 
-pub struct LinearSampler {}
+#[derive(Default)]
+pub struct LinearSampler {
+    pub wrap: WrapMode,
+}
 
 impl TextureSampler for LinearSampler {
     fn sample(&self, texture: &Texture, uv: Vec2) -> Vec3 {
-        let sampler = NearestSampler {};
+        let sampler = NearestSampler { wrap: self.wrap };
         let w = texture.width() as usize;
         let h = texture.height() as usize;
         let x = uv.x() * (w as f64);
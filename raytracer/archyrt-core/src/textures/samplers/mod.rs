@@ -1,10 +1,40 @@
 pub mod linear;
 pub mod nearest;
+pub mod trilinear;
+
+use serde::{Deserialize, Serialize};
 
 use crate::utilities::math::{Vec2, Vec3};
 
 use super::texture::Texture;
 
+/// How texture coordinates outside of the [0; 1] range are mapped onto the texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    /// Maps the coordinate into the [0; 1] range
+    pub fn apply(self, x: f64) -> f64 {
+        match self {
+            WrapMode::Repeat => x.rem_euclid(1.0),
+            WrapMode::Clamp => x.clamp(0.0, 1.0),
+            WrapMode::Mirror => {
+                let x = x.rem_euclid(2.0);
+                if x > 1.0 {
+                    2.0 - x
+                } else {
+                    x
+                }
+            }
+        }
+    }
+}
+
 pub trait TextureSampler {
     fn sample(&self, texture: &Texture, uv: Vec2) -> Vec3;
     fn sample_or_default(&self, texture: Option<&Texture>, uv: Vec2) -> Vec3 {
@@ -3,20 +3,21 @@ use crate::{
     utilities::math::{Vec2, Vec3},
 };
 
-use super::TextureSampler;
+use super::{TextureSampler, WrapMode};
 
-pub struct NearestSampler {}
+#[derive(Default)]
+pub struct NearestSampler {
+    pub wrap: WrapMode,
+}
 
 impl TextureSampler for NearestSampler {
     fn sample(&self, texture: &Texture, uv: Vec2) -> Vec3 {
         let w = texture.width() as usize;
         let h = texture.height() as usize;
-        let x = uv.x() % 1.0;
-        let y = uv.y() % 1.0;
-        let x = if x < 0.0 { 1.0 + x } else { x };
-        let y = if y < 0.0 { 1.0 + y } else { y };
-        let x = ((x * (w as f64)) as usize) % w;
-        let y = ((y * (h as f64)) as usize) % h;
+        let x = self.wrap.apply(uv.x());
+        let y = self.wrap.apply(uv.y());
+        let x = ((x * (w as f64)) as usize).min(w - 1);
+        let y = ((y * (h as f64)) as usize).min(h - 1);
         let index = y * w + x;
         texture
             .get(index)
@@ -0,0 +1,69 @@
+use crate::{
+    textures::mipmap::MipMap,
+    utilities::math::{Vec2, Vec3},
+};
+
+use super::{linear::LinearSampler, TextureSampler, WrapMode};
+
+/// Area of a surface covered by a ray cone, in uv units
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Footprint {
+    /// Width along the minor axis
+    pub width: f64,
+    /// Axis the footprint is stretched along, its length is the width along the major axis
+    pub major: Vec2,
+}
+
+impl Footprint {
+    pub fn scale(self, factor: f64) -> Self {
+        Self {
+            width: self.width * factor,
+            major: self.major * factor,
+        }
+    }
+}
+
+/// Blends linearly sampled mip levels, optionally taking multiple taps along stretched footprints
+pub struct TrilinearSampler {
+    /// Maximum number of taps along the major axis, 1 disables anisotropic filtering
+    pub anisotropy: usize,
+    pub wrap: WrapMode,
+}
+
+impl TrilinearSampler {
+    pub fn sample_level(&self, mipmap: &MipMap, uv: Vec2, level: f64) -> Vec3 {
+        let sampler = LinearSampler { wrap: self.wrap };
+        let max = (mipmap.levels.len() - 1) as f64;
+        let level = if level.is_nan() { 0.0 } else { level.clamp(0.0, max) };
+        let lower = level.floor();
+        let t = level - lower;
+        let c1 = sampler.sample(mipmap.level(lower as usize), uv);
+        if t == 0.0 {
+            return c1;
+        }
+        let c2 = sampler.sample(mipmap.level(lower as usize + 1), uv);
+        c1 * (1.0 - t) + c2 * t
+    }
+    pub fn sample_footprint(&self, mipmap: &MipMap, uv: Vec2, footprint: Footprint) -> Vec3 {
+        let base = mipmap.base();
+        let size = ((base.width() * base.height()) as f64).sqrt();
+        let major = footprint.major.length();
+        let taps = if self.anisotropy > 1 && footprint.width > 0.0 {
+            ((major / footprint.width).ceil() as usize).clamp(1, self.anisotropy)
+        } else {
+            1
+        };
+        let width = (major / (taps as f64)).max(footprint.width);
+        let level = (width * size).log2();
+        if taps == 1 {
+            return self.sample_level(mipmap, uv, level);
+        }
+        (0..taps)
+            .map(|i| {
+                let offset = (i as f64 + 0.5) / (taps as f64) - 0.5;
+                self.sample_level(mipmap, uv + footprint.major * offset, level)
+            })
+            .fold(Vec3::default(), |a, b| a + b)
+            / (taps as f64)
+    }
+}
@@ -0,0 +1,184 @@
+mod mipmap {
+    use crate::{
+        textures::{mipmap::MipMap, texture::Texture},
+        utilities::math::Vec3,
+    };
+
+    fn checkerboard(width: u32, height: u32) -> Texture {
+        let mut texture = Texture::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let c = ((x + y) % 2) as f64;
+                texture.set((y * width + x) as usize, Vec3::from_single(c));
+            }
+        }
+        texture
+    }
+
+    #[test]
+    fn levels() {
+        let mipmap = MipMap::new(checkerboard(8, 2));
+        let sizes: Vec<_> = mipmap
+            .levels
+            .iter()
+            .map(|level| (level.width(), level.height()))
+            .collect();
+        assert_eq!(sizes, vec![(8, 2), (4, 1), (2, 1), (1, 1)]);
+    }
+    #[test]
+    fn average() {
+        let mipmap = MipMap::new(checkerboard(4, 4));
+        for level in &mipmap.levels[1..] {
+            for i in 0..level.data.len() {
+                assert_eq!(level.data.get(i), Vec3::from_single(0.5));
+            }
+        }
+    }
+}
+
+mod trilinear {
+    use crate::{
+        textures::{
+            mipmap::MipMap,
+            samplers::{
+                trilinear::{Footprint, TrilinearSampler},
+                WrapMode,
+            },
+            texture::Texture,
+        },
+        utilities::math::{Vec2, Vec3},
+    };
+
+    fn stripes() -> MipMap {
+        let mut texture = Texture::new(4, 4);
+        for i in 0..16 {
+            texture.set(i, Vec3::from_single((i % 2) as f64));
+        }
+        MipMap::new(texture)
+    }
+
+    #[test]
+    fn small_footprint_uses_base() {
+        let sampler = TrilinearSampler {
+            anisotropy: 1,
+            wrap: WrapMode::Repeat,
+        };
+        let footprint = Footprint {
+            width: 0.01,
+            major: Vec2::default(),
+        };
+        let c = sampler.sample_footprint(&stripes(), Vec2::new(0.25, 0.0), footprint);
+        assert_eq!(c, Vec3::from_single(1.0));
+    }
+    #[test]
+    fn large_footprint_blurs() {
+        let sampler = TrilinearSampler {
+            anisotropy: 1,
+            wrap: WrapMode::Repeat,
+        };
+        let footprint = Footprint {
+            width: 1.0,
+            major: Vec2::default(),
+        };
+        let c = sampler.sample_footprint(&stripes(), Vec2::new(0.25, 0.0), footprint);
+        assert_eq!(c, Vec3::from_single(0.5));
+    }
+}
+
+mod wrap_mode {
+    use crate::textures::samplers::WrapMode;
+
+    #[test]
+    fn repeat() {
+        assert_eq!(WrapMode::Repeat.apply(1.25), 0.25);
+        assert_eq!(WrapMode::Repeat.apply(-0.25), 0.75);
+    }
+    #[test]
+    fn clamp() {
+        assert_eq!(WrapMode::Clamp.apply(1.25), 1.0);
+        assert_eq!(WrapMode::Clamp.apply(-0.25), 0.0);
+    }
+    #[test]
+    fn mirror() {
+        assert_eq!(WrapMode::Mirror.apply(1.25), 0.75);
+        assert_eq!(WrapMode::Mirror.apply(-0.25), 0.25);
+        assert_eq!(WrapMode::Mirror.apply(2.25), 0.25);
+    }
+}
+
+mod texel_data {
+    use crate::{
+        textures::texture::{TexelData, TexelFormat},
+        utilities::math::Vec3,
+    };
+
+    fn round_trip(format: TexelFormat, color: Vec3) -> Vec3 {
+        let mut data = TexelData::new(format, 1);
+        data.set(0, color);
+        data.get(0)
+    }
+
+    #[test]
+    fn srgb8() {
+        let color = Vec3::new(0.0, 0.2, 1.0);
+        let decoded = round_trip(TexelFormat::Srgb8, color);
+        assert!((decoded - color).length() < 0.005);
+    }
+    #[test]
+    fn linear8() {
+        let decoded = round_trip(TexelFormat::Linear8, Vec3::new(0.0, 0.5, 2.0));
+        assert!((decoded - Vec3::new(0.0, 0.5, 1.0)).length() < 0.005);
+    }
+    #[test]
+    fn f16_keeps_hdr() {
+        let color = Vec3::new(0.5, 4.0, 1024.0);
+        assert_eq!(round_trip(TexelFormat::F16, color), color);
+    }
+}
+
+mod texture_repo {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crate::textures::{texture::Texture, texture_repo::TextureRepository, TextureID};
+
+    fn register(repo: &mut TextureRepository, id: TextureID, loads: &Arc<AtomicUsize>) {
+        let loads = loads.clone();
+        repo.register(id, move || {
+            loads.fetch_add(1, Ordering::SeqCst);
+            Ok(Texture::new(1, 1))
+        });
+    }
+
+    #[test]
+    fn loads_on_require() {
+        let mut repo = TextureRepository::new();
+        let loads = Arc::new(AtomicUsize::new(0));
+        let id = TextureID::new(&0);
+        register(&mut repo, id, &loads);
+        assert!(repo.exists(id));
+        assert!(repo.get(id).is_none());
+        repo.require([id]).unwrap();
+        repo.require([id]).unwrap();
+        assert!(repo.get(id).is_some());
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+    }
+    #[test]
+    fn evicts_unused() {
+        let mut repo = TextureRepository::new();
+        //Room for a single 1x1 texture
+        repo.set_budget(Some(6));
+        let loads = Arc::new(AtomicUsize::new(0));
+        let (a, b) = (TextureID::new(&0), TextureID::new(&1));
+        register(&mut repo, a, &loads);
+        register(&mut repo, b, &loads);
+        repo.require([a]).unwrap();
+        repo.evict();
+        repo.require([b]).unwrap();
+        repo.evict();
+        assert!(repo.get(a).is_none());
+        assert!(repo.get(b).is_some());
+    }
+}
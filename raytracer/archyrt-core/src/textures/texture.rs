@@ -1,17 +1,161 @@
-use crate::utilities::math::Vec3;
+use std::sync::{Arc, OnceLock};
+
+use half::f16;
+
+use crate::utilities::math::{Vec2, Vec3};
+
+use super::samplers::WrapMode;
+
+/// Texels with a lower opacity are cut out of surfaces
+pub const ALPHA_CUTOFF: f64 = 0.5;
+
+/// How texels are stored in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TexelFormat {
+    /// 8 bits per channel, colors of the working space encoded with a 2.2 gamma
+    Srgb8,
+    /// 8 bits per channel, linear data such as normals or roughness
+    Linear8,
+    /// Half precision floats, for HDR images
+    F16,
+}
+
+/// Texels in a compact format, decoded when sampled
+pub enum TexelData {
+    Srgb8(Vec<[u8; 3]>),
+    Linear8(Vec<[u8; 3]>),
+    F16(Vec<[f16; 3]>),
+}
+
+/// Linear values of every 8-bit sRGB value
+fn srgb_table() -> &'static [f64; 256] {
+    static TABLE: OnceLock<[f64; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0.0; 256];
+        for (i, value) in table.iter_mut().enumerate() {
+            *value = (i as f64 / 255.0).powf(2.2);
+        }
+        table
+    })
+}
+
+fn quantize(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl TexelData {
+    pub fn new(format: TexelFormat, len: usize) -> Self {
+        match format {
+            TexelFormat::Srgb8 => Self::Srgb8(vec![[0; 3]; len]),
+            TexelFormat::Linear8 => Self::Linear8(vec![[0; 3]; len]),
+            TexelFormat::F16 => Self::F16(vec![[f16::ZERO; 3]; len]),
+        }
+    }
+    pub fn format(&self) -> TexelFormat {
+        match self {
+            Self::Srgb8(_) => TexelFormat::Srgb8,
+            Self::Linear8(_) => TexelFormat::Linear8,
+            Self::F16(_) => TexelFormat::F16,
+        }
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Srgb8(data) | Self::Linear8(data) => data.len(),
+            Self::F16(data) => data.len(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Size of the texels in bytes
+    pub fn memory(&self) -> usize {
+        match self {
+            Self::Srgb8(data) | Self::Linear8(data) => data.len() * 3,
+            Self::F16(data) => data.len() * 6,
+        }
+    }
+    /// Decodes a texel into linear color
+    pub fn get(&self, index: usize) -> Vec3 {
+        match self {
+            Self::Srgb8(data) => {
+                let table = srgb_table();
+                let [r, g, b] = data[index];
+                Vec3::new(table[r as usize], table[g as usize], table[b as usize])
+            }
+            Self::Linear8(data) => {
+                let [r, g, b] = data[index];
+                Vec3::new(r as f64, g as f64, b as f64) / 255.0
+            }
+            Self::F16(data) => {
+                let [r, g, b] = data[index];
+                Vec3::new(r.to_f64(), g.to_f64(), b.to_f64())
+            }
+        }
+    }
+    /// Encodes a linear color into a texel
+    pub fn set(&mut self, index: usize, color: Vec3) {
+        match self {
+            Self::Srgb8(data) => {
+                let color = color.to_srgb();
+                data[index] = [quantize(color.x()), quantize(color.y()), quantize(color.z())];
+            }
+            Self::Linear8(data) => {
+                data[index] = [quantize(color.x()), quantize(color.y()), quantize(color.z())];
+            }
+            Self::F16(data) => {
+                data[index] = [
+                    f16::from_f64(color.x()),
+                    f16::from_f64(color.y()),
+                    f16::from_f64(color.z()),
+                ];
+            }
+        }
+    }
+}
 
 pub struct Texture {
     pub width: u32,
     pub height: u32,
-    pub data: Vec<Vec3>,
+    pub data: TexelData,
+    /// Opacity, absent for fully opaque textures
+    pub alpha: Option<Arc<AlphaMask>>,
+}
+
+/// Opacity of every texel of a texture
+#[derive(Debug)]
+pub struct AlphaMask {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl AlphaMask {
+    /// Opacity of the nearest texel
+    pub fn sample(&self, uv: Vec2) -> f64 {
+        let w = self.width as usize;
+        let h = self.height as usize;
+        let x = WrapMode::Repeat.apply(uv.x());
+        let y = WrapMode::Repeat.apply(uv.y());
+        let x = ((x * (w as f64)) as usize).min(w - 1);
+        let y = ((y * (h as f64)) as usize).min(h - 1);
+        self.data[y * w + x] as f64 / 255.0
+    }
+    pub fn is_opaque(&self, uv: Vec2) -> bool {
+        self.sample(uv) >= ALPHA_CUTOFF
+    }
 }
 
 impl Texture {
+    /// Black HDR texture
     pub fn new(width: u32, height: u32) -> Self {
+        Self::with_format(width, height, TexelFormat::F16)
+    }
+    pub fn with_format(width: u32, height: u32, format: TexelFormat) -> Self {
         Self {
             width,
             height,
-            data: (0..width * height).map(|_| Vec3::default()).collect(),
+            data: TexelData::new(format, (width * height) as usize),
+            alpha: None,
         }
     }
     pub fn width(&self) -> u32 {
@@ -20,11 +164,21 @@ impl Texture {
     pub fn height(&self) -> u32 {
         self.height
     }
+    pub fn format(&self) -> TexelFormat {
+        self.data.format()
+    }
     pub fn get(&self, index: usize) -> Option<Vec3> {
         if index >= self.data.len() {
             None
         } else {
-            Some(self.data[index])
+            Some(self.data.get(index))
         }
     }
+    pub fn set(&mut self, index: usize, color: Vec3) {
+        self.data.set(index, color);
+    }
+    /// Size of the texels and opacity in bytes
+    pub fn memory(&self) -> usize {
+        self.data.memory() + self.alpha.as_ref().map_or(0, |alpha| alpha.data.len())
+    }
 }
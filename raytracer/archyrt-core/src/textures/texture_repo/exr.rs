@@ -1,6 +1,7 @@
 use std::path::Path;
 
 use crate::{
+    color_space::{ColorSpace, ColorTransform},
     textures::{texture::Texture, TextureID},
     utilities::math::Vec3,
 };
@@ -20,15 +21,21 @@ pub fn load_into(
     }
     Ok(())
 }
+/// Loads an image of linear Rec.709 colors, the usual contents of EXR files
 pub fn load(base: &str, name: &str) -> Result<Texture> {
+    load_in(base, name, ColorSpace::LinearSrgb)
+}
+/// Loads an image of a color space, converting it into the working space
+pub fn load_in(base: &str, name: &str, space: ColorSpace) -> Result<Texture> {
+    let transform = ColorTransform::to_working(space);
     let path = Path::new(base).join(name);
     let image = read_first_rgba_layer_from_file(
         path,
         |size, _| Texture::new(size.x() as u32, size.y() as u32),
-        |image, coords, (r, g, b, _): (f32, f32, f32, f32)| {
-            let color = Vec3::new(r as f64, g as f64, b as f64);
+        move |image, coords, (r, g, b, _): (f32, f32, f32, f32)| {
+            let color = transform.apply(Vec3::new(r as f64, g as f64, b as f64));
             let index = coords.y() * (image.width as usize) + coords.x();
-            image.data[index] = color;
+            image.set(index, color);
         },
     )?;
     let texture = image.layer_data.channel_data.pixels;
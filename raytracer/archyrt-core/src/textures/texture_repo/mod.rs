@@ -1,27 +1,135 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use super::{texture::Texture, TextureID};
+use anyhow::Result;
+
+use crate::utilities::lru::LruBudget;
+
+use super::{
+    mipmap::MipMap,
+    texture::{AlphaMask, Texture},
+    TextureID,
+};
 
 pub mod exr;
 pub mod png;
 
+/// Data textures describing the surface a diffuse texture is applied to
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SurfaceMaps {
+    /// Tangent space normals, OpenGL convention (green is up)
+    pub normal: Option<TextureID>,
+    /// Height field, only used when there is no normal map
+    pub bump: Option<TextureID>,
+    pub roughness: Option<TextureID>,
+    pub metallic: Option<TextureID>,
+    pub occlusion: Option<TextureID>,
+}
+
+/// Decodes a texture on demand
+pub type TextureSource = Box<dyn Fn() -> Result<Texture> + Send + Sync>;
+
+/// Resident textures, which textures with a source are loaded into when required
 pub struct TextureRepository {
-    pub textures: HashMap<TextureID, Texture>,
+    pub textures: HashMap<TextureID, MipMap>,
+    /// Surface maps of diffuse textures
+    pub maps: HashMap<TextureID, SurfaceMaps>,
+    /// Diffuse textures of materials without backface culling
+    pub double_sided: HashSet<TextureID>,
+    sources: HashMap<TextureID, TextureSource>,
+    /// Textures loaded from sources, inserted textures are never evicted
+    lru: LruBudget<TextureID>,
 }
 
 impl TextureRepository {
     pub fn new() -> Self {
         let t = HashMap::new();
-        Self { textures: t }
+        Self {
+            textures: t,
+            maps: HashMap::new(),
+            double_sided: HashSet::new(),
+            sources: HashMap::new(),
+            lru: LruBudget::new(None),
+        }
+    }
+    /// Limits the memory of textures loaded from sources, in bytes
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.lru.budget = budget;
     }
     pub fn get(&self, id: TextureID) -> Option<&Texture> {
         let texture = self.textures.get(&id)?;
-        Some(texture)
+        Some(texture.base())
     }
+    pub fn get_mipmap(&self, id: TextureID) -> Option<&MipMap> {
+        self.textures.get(&id)
+    }
+    /// Whether the texture is resident or can be loaded
     pub fn exists(&self, id: TextureID) -> bool{
-        self.textures.contains_key(&id)
+        self.textures.contains_key(&id) || self.sources.contains_key(&id)
     }
+    /// Inserts the texture and builds its mip levels
     pub fn insert(&mut self, id: TextureID, texture: Texture) {
-        self.textures.insert(id, texture);
+        self.textures.insert(id, MipMap::new(texture));
+    }
+    /// Registers a texture to be loaded when it is first required
+    pub fn register<F>(&mut self, id: TextureID, source: F)
+    where
+        F: Fn() -> Result<Texture> + Send + Sync + 'static,
+    {
+        self.sources.insert(id, Box::new(source));
+    }
+    /// Makes the textures and their surface maps resident, loading them from their sources if needed
+    pub fn require<I: IntoIterator<Item = TextureID>>(&mut self, ids: I) -> Result<()> {
+        let mut required: HashSet<TextureID> = HashSet::new();
+        for id in ids {
+            required.insert(id);
+            if let Some(maps) = self.maps.get(&id) {
+                required.extend(
+                    [maps.normal, maps.bump, maps.roughness, maps.metallic, maps.occlusion]
+                        .into_iter()
+                        .flatten(),
+                );
+            }
+        }
+        for id in required {
+            if self.lru.touch(id) || self.textures.contains_key(&id) {
+                continue;
+            }
+            if let Some(source) = self.sources.get(&id) {
+                let mipmap = MipMap::new(source()?);
+                self.lru.insert(id, mipmap.memory());
+                self.textures.insert(id, mipmap);
+            }
+        }
+        Ok(())
+    }
+    /// Unloads the least recently used textures not required since the last eviction, until the rest fit in the budget
+    pub fn evict(&mut self) {
+        for id in self.lru.evict() {
+            self.textures.remove(&id);
+        }
+    }
+    /// Opacity of a texture, if it has any transparent texels
+    pub fn get_cutout(&self, id: TextureID) -> Option<Arc<AlphaMask>> {
+        self.get(id)?.alpha.clone()
+    }
+    pub fn get_maps(&self, diffuse: TextureID) -> Option<&SurfaceMaps> {
+        self.maps.get(&diffuse)
+    }
+    pub fn insert_maps(&mut self, diffuse: TextureID, maps: SurfaceMaps) {
+        self.maps.insert(diffuse, maps);
+    }
+    /// Whether surfaces with the diffuse texture are visible from behind
+    pub fn is_double_sided(&self, diffuse: TextureID) -> bool {
+        self.double_sided.contains(&diffuse)
+    }
+    pub fn set_double_sided(&mut self, diffuse: TextureID, double_sided: bool) {
+        if double_sided {
+            self.double_sided.insert(diffuse);
+        } else {
+            self.double_sided.remove(&diffuse);
+        }
     }
 }
@@ -1,11 +1,15 @@
-use std::{path::Path};
+use std::{path::Path, sync::Arc};
 
 use anyhow::Result;
 use image::io::Reader as ImageReader;
 
 use crate::{
-    textures::{texture::Texture, TextureID},
-    vector,
+    color_space::{ColorSpace, ColorTransform},
+    textures::{
+        texture::{AlphaMask, TexelData, TexelFormat, Texture},
+        TextureID,
+    },
+    utilities::math::Vec3,
 };
 
 use super::TextureRepository;
@@ -22,23 +26,51 @@ pub fn load_into(
     Ok(())
 }
 pub fn load(base: &str, name: &str) -> Result<Texture> {
+    load_in(base, name, ColorSpace::Srgb)
+}
+/// Loads a color texture encoded in a color space, converting it into the working space
+pub fn load_in(base: &str, name: &str, space: ColorSpace) -> Result<Texture> {
+    let transform = ColorTransform::to_working(space);
+    let transfer = space.transfer();
+    let table: Vec<f64> = (0..=255)
+        .map(|i| transfer.decode(i as f64 / 255.0))
+        .collect();
+    load_with(base, name, |pixels| {
+        let mut data = TexelData::new(TexelFormat::Srgb8, pixels.len());
+        for (index, [r, g, b]) in pixels.into_iter().enumerate() {
+            let color = Vec3::new(table[r as usize], table[g as usize], table[b as usize]);
+            data.set(index, transform.linear(color));
+        }
+        data
+    })
+}
+/// Loads a texture storing data rather than colors, without decoding or converting it
+pub fn load_linear(base: &str, name: &str) -> Result<Texture> {
+    load_with(base, name, TexelData::Linear8)
+}
+fn load_with(
+    base: &str,
+    name: &str,
+    texels: impl FnOnce(Vec<[u8; 3]>) -> TexelData,
+) -> Result<Texture> {
     let path = Path::new(base).join(name).with_extension("png");
     let image = ImageReader::open(path)?.decode()?;
-    let image = image.into_rgb8();
-    let pixels: Vec<_> = image
-        .pixels()
-        .map(|a| {
-            vector![
-                a.0[0] as f64 / 255.0,
-                a.0[1] as f64 / 255.0,
-                a.0[2] as f64 / 255.0
-            ]
-            .from_srgb()
-        })
-        .collect();
+    let image = image.into_rgba8();
+    let pixels: Vec<[u8; 3]> = image.pixels().map(|a| [a.0[0], a.0[1], a.0[2]]).collect();
+    let alpha: Vec<u8> = image.pixels().map(|a| a.0[3]).collect();
+    let alpha = if alpha.iter().any(|a| *a < 255) {
+        Some(Arc::new(AlphaMask {
+            width: image.width(),
+            height: image.height(),
+            data: alpha,
+        }))
+    } else {
+        None
+    };
     Ok(Texture {
-        data: pixels,
+        data: texels(pixels),
         width: image.width(),
         height: image.height(),
+        alpha,
     })
 }
@@ -1,8 +1,30 @@
-use crate::utilities::math::{Vector, Vec3};
+use crate::{
+    color_space::{ColorSpace, ColorTransform},
+    utilities::math::{Vec3, Vector},
+};
 
-pub fn tonemap_fragment(c: Vec3)->Vec3{
-    let c = c*4.0;
-    let c = c/(c+Vector::from_single(1.0));
-    let c = c.powf(1.0/2.2);
-    c
-}
\ No newline at end of file
+/// Maps HDR colors of the working space to the 0..1 range of a display and encodes them for it
+#[derive(Debug, Clone, Copy)]
+pub struct OutputTransform {
+    transform: ColorTransform,
+}
+
+impl OutputTransform {
+    pub fn new(display: ColorSpace) -> Self {
+        Self {
+            transform: ColorTransform::from_working(display),
+        }
+    }
+    pub fn apply(&self, c: Vec3) -> Vec3 {
+        //Colors outside the display's gamut are clipped
+        let c = self.transform.linear(c).max(Vec3::default());
+        let c = c * asset::TONEMAP_EXPOSURE as f64;
+        let c = c / (c + Vector::from_single(1.0));
+        self.transform.to.encode(c)
+    }
+}
+
+/// Tonemaps a color of the working space for an sRGB display
+pub fn tonemap_fragment(c: Vec3) -> Vec3 {
+    OutputTransform::new(ColorSpace::Srgb).apply(c)
+}
@@ -0,0 +1,25 @@
+use std::{
+    fs,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Result;
+
+/// Writes started by this process, giving each one its own temporary file
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes a file through a temporary file next to it, so other processes and threads never read a partial file
+/// and a crash while writing keeps the previous one
+pub fn write_atomic<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<()> {
+    let path = path.as_ref();
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    let write = WRITES.fetch_add(1, Ordering::Relaxed);
+    temp.push(format!(".{}.{}.tmp", std::process::id(), write));
+    fs::write(&temp, data)?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Keeps track of the size and last use of cached assets, to pick which ones to evict to stay within a memory budget.
+///
+/// Assets used since the last eviction are never evicted, so everything a scene requires stays loaded while it renders.
+pub struct LruBudget<K> {
+    /// Maximum total size in bytes, `None` is unbounded
+    pub budget: Option<usize>,
+    generation: u64,
+    /// Size and generation of last use
+    entries: HashMap<K, (usize, u64)>,
+}
+
+impl<K: Hash + Eq + Copy> LruBudget<K> {
+    pub fn new(budget: Option<usize>) -> Self {
+        Self {
+            budget,
+            generation: 0,
+            entries: HashMap::new(),
+        }
+    }
+    pub fn insert(&mut self, key: K, size: usize) {
+        self.entries.insert(key, (size, self.generation));
+    }
+    /// Marks an asset as used, returns false if it isn't tracked
+    pub fn touch(&mut self, key: K) -> bool {
+        match self.entries.get_mut(&key) {
+            Some(entry) => {
+                entry.1 = self.generation;
+                true
+            }
+            None => false,
+        }
+    }
+    /// Total size of the tracked assets
+    pub fn used(&self) -> usize {
+        self.entries.values().map(|(size, _)| size).sum()
+    }
+    /// Stops tracking the least recently used assets until the rest fit in the budget, and returns them
+    pub fn evict(&mut self) -> Vec<K> {
+        let mut evicted = Vec::new();
+        if let Some(budget) = self.budget {
+            let mut used = self.used();
+            let mut candidates: Vec<(K, usize, u64)> = self
+                .entries
+                .iter()
+                .filter(|(_, (_, generation))| *generation < self.generation)
+                .map(|(key, (size, generation))| (*key, *size, *generation))
+                .collect();
+            candidates.sort_by_key(|(_, _, generation)| *generation);
+            for (key, size, _) in candidates {
+                if used <= budget {
+                    break;
+                }
+                self.entries.remove(&key);
+                used -= size;
+                evicted.push(key);
+            }
+        }
+        self.generation += 1;
+        evicted
+    }
+}
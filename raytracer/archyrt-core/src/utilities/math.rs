@@ -1,10 +1,15 @@
 use std::cmp::Ordering;
 use std::{
-    fmt::Debug,
+    fmt::{self, Debug},
     ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
 use cgmath::{Vector2, Vector3, Matrix3, Quaternion};
+use serde::{
+    de::{Error, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 
 #[derive(Copy, Clone)]
 pub enum Axis3 {
@@ -85,6 +90,42 @@ impl<const N: usize> Debug for Vector<N> {
     }
 }
 
+impl<const N: usize> Serialize for Vector<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for component in &self.inner {
+            tuple.serialize_element(component)?;
+        }
+        tuple.end()
+    }
+}
+
+/// Reads the components straight into the vector, without allocating
+struct VectorVisitor<const N: usize>;
+
+impl<'de, const N: usize> Visitor<'de> for VectorVisitor<N> {
+    type Value = Vector<N>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a vector of {} components", N)
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut inner = [0.0; N];
+        for (i, component) in inner.iter_mut().enumerate() {
+            *component = seq
+                .next_element()?
+                .ok_or_else(|| A::Error::invalid_length(i, &self))?;
+        }
+        Ok(Vector { inner })
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Vector<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(N, VectorVisitor)
+    }
+}
+
 impl<const N: usize> Index<usize> for Vector<N> {
     type Output = f64;
 
@@ -418,6 +459,13 @@ impl Matrix3x3 {
         }
         Some(o)
     }
+    pub fn inverse(self) -> Option<Self> {
+        Some(matrix![
+            self.cramer(vector![1.0, 0.0, 0.0])?,
+            self.cramer(vector![0.0, 1.0, 0.0])?,
+            self.cramer(vector![0.0, 0.0, 1.0])?
+        ])
+    }
     pub fn rotate_x(self, angle: f64) -> Self {
         let sin = angle.sin();
         let cos = angle.cos();
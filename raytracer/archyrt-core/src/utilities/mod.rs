@@ -1,3 +1,6 @@
+pub mod files;
+pub mod lru;
 pub mod math;
+pub mod random;
 pub mod ray;
-mod tests;
+mod tests;
\ No newline at end of file
@@ -0,0 +1,36 @@
+use std::cell::RefCell;
+
+use rand::{rngs::StdRng, Error, RngCore, SeedableRng};
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Random number generator of the current thread. Renderers use it instead of `rand::thread_rng`,
+/// so renders can be repeated by [`seed`]ing it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadRng;
+
+pub fn rng() -> ThreadRng {
+    ThreadRng
+}
+
+/// Restarts the random numbers of the current thread from a seed
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+impl RngCore for ThreadRng {
+    fn next_u32(&mut self) -> u32 {
+        RNG.with(|rng| rng.borrow_mut().next_u32())
+    }
+    fn next_u64(&mut self) -> u64 {
+        RNG.with(|rng| rng.borrow_mut().next_u64())
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RNG.with(|rng| rng.borrow_mut().fill_bytes(dest))
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        RNG.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+    }
+}
@@ -1,7 +1,12 @@
+use std::sync::Arc;
+
 use crate::{
     intersectables::union::UnionIntersector,
     renderers::path_tracer::Material,
-    textures::{color_provider::ColorProvider, texture_repo::TextureRepository},
+    textures::{
+        color_provider::{ColorProvider, RayCone, SurfaceProperties},
+        texture_repo::TextureRepository,
+    },
 };
 
 use super::math::Vec3;
@@ -100,6 +105,12 @@ impl<C: ColorProvider> Intersection<C> {
     pub fn get_color(&self, repo: &TextureRepository) -> Vec3 {
         self.0.color_provider.get_color(repo)
     }
+    pub fn get_color_filtered(&self, repo: &TextureRepository, cone: RayCone) -> Vec3 {
+        self.0.color_provider.get_color_filtered(repo, cone)
+    }
+    pub fn get_surface(&self, repo: &TextureRepository, cone: RayCone) -> SurfaceProperties {
+        self.0.color_provider.get_surface(repo, self.0.normal, cone)
+    }
     pub fn get_material(&self) -> Material {
         self.0.color_provider.get_material()
     }
@@ -145,6 +156,15 @@ where
         (*self).intersect(ray)
     }
 }
+impl<T> Intersectable for Arc<T>
+where
+    T: Intersectable,
+{
+    type C = T::C;
+    fn intersect(&self, ray: Ray) -> Option<Intersection<T::C>> {
+        self.as_ref().intersect(ray)
+    }
+}
 impl<T> Intersectable for Option<T>
 where
     T: Intersectable,
@@ -135,6 +135,19 @@ mod vectors {
         assert_eq!(v1.x(), 1.0);
         assert_eq!(v1.y(), 2.0);
     }
+    #[test]
+    fn serde() {
+        let v = Vec3::new(1.0, -2.5, 3.0);
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(json, "[1.0,-2.5,3.0]");
+        assert_eq!(serde_json::from_str::<Vec3>(&json).unwrap(), v);
+        assert!(serde_json::from_str::<Vec3>("[1.0,2.0]").is_err());
+        assert!(serde_json::from_str::<Vec3>("[1.0,2.0,3.0,4.0]").is_err());
+        //Stored without a length, like an array
+        let data = bincode::serialize(&v).unwrap();
+        assert_eq!(data.len(), 3 * 8);
+        assert_eq!(bincode::deserialize::<Vec3>(&data).unwrap(), v);
+    }
 }
 
 #[cfg(test)]
@@ -239,6 +252,24 @@ mod matrices {
         let solution = m.cramer(res).unwrap();
         assert_eq!(solution, Vec3::new(-2.0 / 5.0, 17.0 / 5.0, -7.0 / 5.0));
     }
+    #[test]
+    fn inverse() {
+        let m: Matrix3x3 = matrix!(
+            vector!(1.0, -3.0, 2.0),
+            vector!(3.0, -1.0, 3.0),
+            vector!(2.0, -3.0, 1.0)
+        );
+        let v = Vec3::new(7.0, 2.0, 8.0);
+        let inverse = m.inverse().unwrap();
+        assert!((inverse * (m * v) - v).length() < 1e-9);
+        assert!(((m * inverse)[1] - vector!(0.0, 1.0, 0.0)).length() < 1e-9);
+        let singular: Matrix3x3 = matrix!(
+            vector!(1.0, 2.0, 3.0),
+            vector!(4.0, 5.0, 6.0),
+            vector!(7.0, 8.0, 9.0)
+        );
+        assert!(singular.inverse().is_none());
+    }
 }
 
 #[cfg(test)]
@@ -351,3 +382,71 @@ mod quadratic {
         }
     }
 }
+
+#[cfg(test)]
+mod lru {
+    use crate::utilities::lru::LruBudget;
+
+    #[test]
+    fn evicts_least_recent() {
+        let mut lru = LruBudget::new(Some(2));
+        lru.insert(1, 1);
+        lru.evict();
+        lru.insert(2, 1);
+        lru.evict();
+        lru.touch(1);
+        lru.insert(3, 1);
+        assert_eq!(lru.evict(), vec![2]);
+        assert_eq!(lru.used(), 2);
+    }
+    #[test]
+    fn keeps_recently_used() {
+        let mut lru = LruBudget::new(Some(1));
+        lru.insert(1, 1);
+        lru.insert(2, 1);
+        //Both were used since the last eviction
+        assert!(lru.evict().is_empty());
+        assert_eq!(lru.evict().len(), 1);
+    }
+}
+
+#[cfg(test)]
+mod files {
+    use std::{env, fs, sync::Barrier, thread};
+
+    use crate::utilities::files::write_atomic;
+
+    #[test]
+    fn replaces_file() {
+        let directory = env::temp_dir().join(format!("archyrt-files-{}", std::process::id()));
+        let path = directory.join("nested").join("data.bin");
+        write_atomic(&path, &[1, 2, 3]).unwrap();
+        write_atomic(&path, &[4, 5]).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), vec![4, 5]);
+        //No temporary files are left behind
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+    #[test]
+    fn concurrent_writes() {
+        let directory = env::temp_dir().join(format!("archyrt-files-concurrent-{}", std::process::id()));
+        let path = directory.join("data.bin");
+        let contents: Vec<Vec<u8>> = (0..2u8).map(|i| vec![i; 1 << 20]).collect();
+        let barrier = Barrier::new(contents.len());
+        thread::scope(|scope| {
+            for data in &contents {
+                let (path, barrier) = (&path, &barrier);
+                scope.spawn(move || {
+                    barrier.wait();
+                    for _ in 0..20 {
+                        write_atomic(path, data).unwrap();
+                    }
+                });
+            }
+        });
+        //The file is whole and written by one of the threads
+        assert!(contents.contains(&fs::read(&path).unwrap()));
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
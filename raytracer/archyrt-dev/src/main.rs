@@ -1,20 +1,31 @@
 use std::f64::consts::PI;
+use std::sync::Arc;
 
 use archyrt_core::api::camera::Camera;
 use archyrt_core::api::fragment_render::{FragmentContext, FragmentRender};
+use archyrt_core::api::light_layers::{LayeredFragmentRender, LightLayers};
 
 use archyrt_core::cameras::jitter::JitterCamera;
+use archyrt_core::cameras::path::CameraPath;
 use archyrt_core::cameras::perspective::PerspectiveCamera;
 use archyrt_core::collector::image_collector::ImageCollector;
+use archyrt_core::collector::layer_collector::LayerCollector;
 use archyrt_core::collector::raw_collector::RawCollector;
 use archyrt_core::intersectables::apply_matrix::ApplyMatrix;
+use archyrt_core::intersectables::area_light::AreaLights;
 use archyrt_core::intersectables::bvh::BVH;
+use archyrt_core::intersectables::packed_bvh::PackedBVH;
 use archyrt_core::intersectables::sphere::Sphere;
 use archyrt_core::intersectables::transform::Transform;
+use archyrt_core::lightmap::{self, LightmapAtlas};
 use archyrt_core::loaders::amdl::repo::{PropRepository, PropType};
 use archyrt_core::loaders::amdl::{self, AMDLLoader};
 use archyrt_core::loaders::ascn::{amdl_textures, ASCNLoader};
+use archyrt_core::postprocess::FloatImage;
 use archyrt_core::renderers::basic_renderer::BasicRenderer;
+use archyrt_core::renderers::heatmap::{HeatmapMetric, HeatmapRenderer};
+use archyrt_core::renderers::integrator::{IntegratorRenderer, NamedIntegrator, Scene};
+use archyrt_core::renderers::media::Atmosphere;
 use archyrt_core::renderers::path_tracer::{Material, PathTracer};
 use archyrt_core::renderers::sampling::SamplingRenderer;
 use archyrt_core::renderers::solid_renderers::albedo::AlbedoRenderer;
@@ -46,23 +57,35 @@ impl<Renderer: FragmentRender + Sync + Send> FragmentRender for ParallelSampling
     }
 }
 
-fn render_pathtraced<O: Intersectable + Sync, C: Camera + Sync>(
-    object: O,
+impl<Renderer: LayeredFragmentRender + Sync + Send> LayeredFragmentRender
+    for ParallelSamplingRenderer<Renderer>
+{
+    fn render_layers(&self, ctx: &FragmentContext, pos: Vec2) -> LightLayers {
+        (0..self.samples)
+            .into_par_iter()
+            .map(|_| self.inner.render_layers(ctx, pos))
+            .reduce(LightLayers::new, |mut a, b| {
+                a += b;
+                a
+            })
+            / (self.samples as f64)
+    }
+}
+
+fn render_integrated<O: Intersectable + Sync, C: Camera + Sync>(
+    scene: &Scene<O>,
     camera: C,
-    mut repo: TextureRepository,
+    integrator: &NamedIntegrator,
+    repo: &TextureRepository,
     w: usize,
     h: usize,
 ) -> image::ImageBuffer<Rgb<u8>, Vec<u8>> {
     let aa_camera = JitterCamera::new(&camera, w, h); //Camera used for anti-aliasing
-    let skybox_id = TextureID::new(&"skybox");
-    texture_repo::exr::load_into(&mut repo, "../assets", &[(skybox_id, "skybox.exr")]).unwrap();
-    let skybox = Some(skybox_id);
     //Set renderers up
-    let pathtracer = PathTracer {
-        skybox,
-        object: &object,
+    let pathtracer = IntegratorRenderer {
         camera: &aa_camera,
-        bounces: 5,
+        scene: scene.by_ref(),
+        integrator,
     };
     let pathtracer = ParallelSamplingRenderer {
         inner: pathtracer,
@@ -70,7 +93,7 @@ fn render_pathtraced<O: Intersectable + Sync, C: Camera + Sync>(
     };
     //Albedo and Normal renderers are required by OIDN
     let albedo = AlbedoRenderer {
-        object: &object,
+        object: &scene.object,
         camera: &aa_camera,
     };
     //Make sure albedo is anti-aliased
@@ -79,16 +102,16 @@ fn render_pathtraced<O: Intersectable + Sync, C: Camera + Sync>(
         samples: 5,
     };
     let normal = NormalRenderer {
-        object: &object,
+        object: &scene.object,
         camera: &camera,
     };
 
     //Collect images to arrays
     println!("Rendering image");
     let collector = RawCollector {};
-    let pathtracer_image = collector.collect(&pathtracer, &repo, w, h);
-    let albedo_image = collector.collect(&albedo, &repo, w, h);
-    let normal_image = collector.collect(&normal, &repo, w, h);
+    let pathtracer_image = collector.collect(&pathtracer, repo, w, h);
+    let albedo_image = collector.collect(&albedo, repo, w, h);
+    let normal_image = collector.collect(&normal, repo, w, h);
 
     //Using OIDN for denoising
     println!("Denoising");
@@ -137,6 +160,103 @@ fn render_pathtraced<O: Intersectable + Sync, C: Camera + Sync>(
     image
 }
 
+fn render_heatmap<O: Intersectable + Sync, C: Camera + Sync>(
+    scene: Scene<O>,
+    camera: C,
+    integrator: NamedIntegrator,
+    metric: HeatmapMetric,
+    repo: TextureRepository,
+    w: usize,
+    h: usize,
+) -> RgbImage {
+    let renderer = IntegratorRenderer {
+        camera,
+        scene,
+        integrator,
+    };
+    let renderer = HeatmapRenderer::new(renderer, metric);
+    ImageCollector {}.collect(renderer, &repo, w, h).unwrap()
+}
+
+/// Bakes the lightmap of the solids of a scene file, returning the encoded asset
+fn bake_lightmap<O: Intersectable + Sync>(
+    scene: Scene<O>,
+    path: &str,
+    repo: TextureRepository,
+) -> Vec<u8> {
+    let data = std::fs::read(path).unwrap();
+    let atlas = LightmapAtlas::from_bytes(&data, lightmap::DENSITY, lightmap::MAX_SIZE).unwrap();
+    println!("Baking {}x{} lightmap", atlas.width, atlas.height);
+    let image = atlas.bake(&scene, &PathTracer::default(), &repo, 64);
+
+    println!("Denoising");
+    let (w, h) = (atlas.width as usize, atlas.height as usize);
+    let mut output = vec![0f32; w * h * 3];
+    let device = oidn::Device::new();
+    oidn::RayTracing::new(&device)
+        .srgb(false)
+        .hdr(true)
+        .image_dimensions(w, h)
+        .filter(&image.to_raw(), &mut output)
+        .unwrap();
+    //The denoiser blends the padding with neighbouring faces
+    let mut image = FloatImage::from_raw(w, h, &output);
+    atlas.dilate(&mut image);
+    atlas.encode(&image).encode().unwrap()
+}
+
+/// Renders the light of every group of the scene to a separate linear image, which add up to the full image
+fn render_layers<O: Intersectable + Sync, C: Camera + Sync>(
+    scene: &Scene<O>,
+    camera: C,
+    integrator: &NamedIntegrator,
+    repo: &TextureRepository,
+    w: usize,
+    h: usize,
+) {
+    let aa_camera = JitterCamera::new(&camera, w, h);
+    let renderer = IntegratorRenderer {
+        camera: &aa_camera,
+        scene: scene.by_ref(),
+        integrator,
+    };
+    let renderer = ParallelSamplingRenderer {
+        inner: renderer,
+        samples: 64,
+    };
+    println!("Rendering layers");
+    let layers = LayerCollector {}.collect(&renderer, repo, w, h);
+    std::fs::create_dir_all("layers").unwrap();
+    for (group, image) in layers {
+        let path = format!("layers/{}.exr", group.name());
+        println!("Saving {}", path);
+        exr::prelude::write_rgb_file(&path, w, h, |x, y| {
+            let color = image[y][x];
+            (color.x() as f32, color.y() as f32, color.z() as f32)
+        })
+        .unwrap();
+    }
+}
+
+/// Renders the frames of a camera path to numbered images, reusing the scene for every frame
+fn render_animation<O: Intersectable + Sync>(
+    scene: &Scene<O>,
+    path: &CameraPath,
+    fps: f64,
+    integrator: &NamedIntegrator,
+    repo: &TextureRepository,
+    w: usize,
+    h: usize,
+) {
+    std::fs::create_dir_all("frames").unwrap();
+    let frames: Vec<PerspectiveCamera> = path.frames(fps).collect();
+    for (frame, camera) in frames.into_iter().enumerate() {
+        println!("Frame {}", frame);
+        let image = render_integrated(scene, camera, integrator, repo, w, h);
+        image.save(format!("frames/frame_{:04}.png", frame)).unwrap();
+    }
+}
+
 fn render_albedo<O: Intersectable, C: Camera>(
     object: O,
     camera: C,
@@ -184,6 +304,41 @@ fn render_albedo<O: Intersectable, C: Camera>(
 fn main() {
     let w = 512;
     let h = 512;
+    //Integrator picked by the first argument. "bake" bakes the lightmap of the scene instead,
+    //"animate" renders the frames of a camera path and "layers" its light groups with the path tracer.
+    let mode = std::env::args().nth(1);
+    let bake = mode.as_deref() == Some("bake");
+    let animate = mode.as_deref() == Some("animate");
+    let layers = mode.as_deref() == Some("layers");
+    let integrator = mode
+        .filter(|_| !bake && !animate && !layers)
+        .unwrap_or_else(|| "path".to_string());
+    let integrator = NamedIntegrator::from_name(&integrator).unwrap_or_else(|| {
+        panic!(
+            "Unknown integrator {}, expected one of {:?}",
+            integrator,
+            NamedIntegrator::NAMES
+        )
+    });
+    //"animate" takes the camera path file and optionally the frames per second
+    let animation = std::env::args().nth(2).filter(|_| animate);
+    let fps: f64 = std::env::args()
+        .nth(3)
+        .filter(|_| animate)
+        .map_or(24.0, |fps| fps.parse().expect("Frames per second should be a number"));
+    if animate && animation.is_none() {
+        panic!("Usage: animate <camera path file> [frames per second]");
+    }
+    //Cost to show instead of the image, picked by the optional second argument
+    let metric = std::env::args().nth(2).filter(|_| !animate).map(|metric| {
+        HeatmapMetric::from_name(&metric).unwrap_or_else(|| {
+            panic!(
+                "Unknown heatmap {}, expected one of {:?}",
+                metric,
+                HeatmapMetric::NAMES
+            )
+        })
+    });
 
     //Loading textures and skybox
     println!("Load file");
@@ -191,18 +346,58 @@ fn main() {
     amdl_textures::load_into(&mut textures, "../assets").unwrap();
 
     let mut props = PropRepository::new();
-    amdl::repo::load_into(&mut props, &textures, "../assets").unwrap();
+    amdl::repo::load_into(&mut props, "../assets").unwrap();
 
     //Load model
-    let loader = ASCNLoader::from_path("../assets/ottoman.ascn").unwrap();
+    let scene_path = "../assets/ottoman.ascn";
+    let mut loader = ASCNLoader::from_path(scene_path).unwrap();
+    textures.require(loader.get_textures()).unwrap();
+    props.require(loader.get_prop_requests(), &mut textures).unwrap();
+    loader.load_materials(&textures);
+    //Loaded once for every mode, heatmaps and bakes trace the skybox too
+    let skybox = TextureID::new(&"skybox");
+    texture_repo::exr::load_into(&mut textures, "../assets", &[(skybox, "skybox.exr")]).unwrap();
     let camera = loader.get_camera();
     let object = loader.get_triangles();
-    let object = BVH::from_triangles(&object);
-    let props = props.fulfill_all(loader.get_prop_requests()).unwrap();
-    let object = object.union(props);
+    let prop_emitters = props.emitters(loader.get_prop_requests()).unwrap();
+    let lights = AreaLights::new(object.iter().chain(prop_emitters.iter()));
+    let object = BVH::from_triangles(&object).map(|bvh| Arc::new(PackedBVH::new(&bvh)));
+    let object = props.build_scene(object, loader.get_prop_requests()).unwrap();
 
+    if !integrator.renders_atmosphere() && !loader.get_volumes().is_empty() {
+        println!("The {} integrator renders without the fog of the scene", integrator.name());
+    }
     println!("Render");
-    let image = render_pathtraced(object, camera, textures, w, h);
+    let scene = Scene {
+        object,
+        skybox: Some(skybox),
+        portals: loader.get_portals().clone(),
+        lights,
+        anisotropy: 8,
+        media: Atmosphere::default().media(loader.get_volumes()),
+        sun: None,
+    };
+    if bake {
+        let lightmap = bake_lightmap(scene, scene_path, textures);
+        std::fs::write("../assets/ottoman.almp", lightmap).unwrap();
+        return;
+    }
+    if let Some(animation) = animation {
+        let data = std::fs::read(animation).unwrap();
+        let path = CameraPath::from_bytes(&data).unwrap();
+        render_animation(&scene, &path, fps, &integrator, &textures, w, h);
+        return;
+    }
+    if layers {
+        render_layers(&scene, camera, &integrator, &textures, w, h);
+        return;
+    }
+    if let Some(metric) = metric {
+        let image = render_heatmap(scene, camera, integrator, metric, textures, w, h);
+        image.save(format!("heatmap_{}.png", metric.name())).unwrap();
+        return;
+    }
+    let image = render_integrated(&scene, camera, &integrator, &textures, w, h);
     //let image = render_albedo(object, camera, textures, w, h);
     image.save("image.png").unwrap();
 }
@@ -1,16 +1,22 @@
-use std::{env, path::Path, sync::Arc};
+use std::{collections::HashMap, env, path::Path, sync::{Arc, Mutex}, time::{Duration, Instant}};
 
 use anyhow::{anyhow, Result};
 use archyrt_core::{
     api::fragment_collector::FragmentCollector,
+    checkpoint::{Accumulator, Checkpoint, CheckpointDirectory, RenderSettings},
     collector::raw_collector::RawCollector,
-    intersectables::bvh::BVH,
+    intersectables::{area_light::{emitters, AreaLights}, bvh::BVH, packed_bvh::PackedBVH},
+    lightmap::{self, LightmapAtlas},
     loaders::{
         ascn::{amdl_textures, ASCNLoader},
+        bvh_cache::{content_key, BVHCache},
         Loader, amdl::{repo::PropRepository, self},
     },
-    renderers::{solid_renderers::{albedo::AlbedoRenderer, normal::NormalRenderer}, sampling::SamplingRenderer},
-    textures::texture_repo::TextureRepository, vector, utilities::{math::Vec3, ray::Intersectable}, tonemapping::tonemap_fragment, cameras::jitter::JitterCamera,
+    renderers::{solid_renderers::{albedo::AlbedoRenderer, normal::NormalRenderer}, sampling::SamplingRenderer, media::Atmosphere, integrator::{NamedIntegrator, Scene}, path_tracer::PathTracer},
+    textures::{texture_repo::{self, TextureRepository}, TextureID}, vector, utilities::{files::write_atomic, math::Vec3}, tonemapping::OutputTransform,
+    cameras::{jitter::JitterCamera, path::{keyframe_camera, keyframe_from_json, keyframe_to_json, CameraPath}, perspective::PerspectiveCamera},
+    postprocess::{Effect, FloatImage, PostProcess},
+    color_space::ColorSpace,
 };
 use dotenv::dotenv;
 
@@ -23,22 +29,117 @@ use lapin::{
     Channel, Connection, ConnectionProperties, Queue,
 };
 use mongodb::{
-    bson::{doc, oid::ObjectId, Document, DateTime},
+    bson::{doc, oid::ObjectId, Bson, Document, DateTime},
     options::{ClientOptions, UpdateOptions},
     Collection,
 };
 use uuid::Uuid;
 
+/// Time between saving checkpoints of a render, each one copies the full sums
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Seconds checkpoints are kept on Redis, when they aren't stored in `CHECKPOINT_DIR`
+const CHECKPOINT_TTL: usize = 7 * 24 * 60 * 60;
+/// Seconds the marker of a failed bake is kept on Redis, the next bake of the project removes it
+const BAKE_ERROR_TTL: usize = 24 * 60 * 60;
+/// Settings of a render on Redis, given to every frame of a camera path
+const RENDER_KEYS: [&str; 9] = ["width", "height", "samples", "integrator", "clay", "postprocess", "display", "atmosphere", "scene"];
+
+fn get_tensor(redis_client: &mut redis::Client, key: &str) -> Vec<f32> {
+    let data: Vec<u8> = redis::cmd("AI.TENSORGET")
+        .arg(key)
+        .arg("BLOB")
+        .query(redis_client).unwrap();
+    data
+        .chunks(4)
+        .map(|a| {
+            let a: [u8; 4] = a.try_into().unwrap();
+            f32::from_le_bytes(a)
+        })
+        .collect()
+}
+
+fn set_tensor(redis_client: &mut redis::Client, key: &str, width: usize, height: usize, data: &[f32]) {
+    let data: Vec<u8> = data.iter().flat_map(|a| a.to_le_bytes()).collect();
+    let _: () = redis::cmd("AI.TENSORSET")
+        .arg(key)
+        .arg("FLOAT")
+        .arg(height)
+        .arg(width)
+        .arg(3)
+        .arg("BLOB")
+        .arg(data)
+        .query(redis_client).unwrap();
+}
+
+/// Samples summed up on Redis so far, each pixel has its resumed samples and the ones its tile received since
+fn load_accumulator(redis_client: &mut redis::Client, image_key: &str, squared_key: &str, settings: &RenderSettings, resumed: &[u32], tile_samples: &HashMap<(usize, usize), u32>) -> Accumulator {
+    let (width, height) = (settings.width, settings.height);
+    let mut samples = resumed.to_vec();
+    for (&(x, y), &count) in tile_samples {
+        for py in y..y + height / 4 {
+            for px in x..x + width / 4 {
+                samples[py * width + px] += count;
+            }
+        }
+    }
+    let sum = get_tensor(redis_client, image_key);
+    let sum_squared = get_tensor(redis_client, squared_key);
+    Accumulator::from_raw(width, height, &sum, &sum_squared, samples)
+}
+
+fn load_checkpoint(redis_client: &mut redis::Client, directory: Option<&CheckpointDirectory>, key: &str) -> Option<Checkpoint> {
+    if let Some(directory) = directory {
+        return directory.load(key);
+    }
+    let data: Option<Vec<u8>> = redis::Cmd::get(format!("archyrt:checkpoint:{}", key)).query(redis_client).unwrap();
+    data.and_then(|data| Checkpoint::decode(&data).ok())
+}
+
+fn save_checkpoint(redis_client: &mut redis::Client, directory: Option<&CheckpointDirectory>, key: &str, checkpoint: &Checkpoint) {
+    if let Some(directory) = directory {
+        //A failed save only loses the samples since the previous one
+        if let Err(e) = directory.store(key, checkpoint) {
+            println!("Could not save checkpoint {}: {}", key, e);
+        }
+        return;
+    }
+    let data = checkpoint.encode().unwrap();
+    let _: () = redis::cmd("SET")
+        .arg(format!("archyrt:checkpoint:{}", key))
+        .arg(data)
+        .arg("EX")
+        .arg(CHECKPOINT_TTL)
+        .query(redis_client).unwrap();
+}
+
+/// BVH of the solids of a scene, the subscribers have most likely built it already
+fn load_bvh(redis_client: &mut redis::Client, data: &[u8], scene: &ASCNLoader) -> Option<BVH> {
+    let cached: Option<Vec<u8>> = redis::Cmd::get(format!("archyrt:bvh:{}", content_key(data))).query(redis_client).unwrap();
+    match cached.and_then(|cached| BVH::decode(&cached).ok()) {
+        Some(bvh) => Some(bvh),
+        None => BVH::from_triangles(scene.get_triangles()),
+    }
+}
+
 #[cfg(feature="oidn")]
-fn denoise(width: usize, height: usize, image: Vec<f32>, scene: &str, redis_client: &mut redis::Client, props: &Arc<PropRepository>, render_id: ObjectId, textures: &Arc<TextureRepository>) -> Vec<f32>{
+fn denoise(width: usize, height: usize, image: Vec<f32>, scene: &str, camera: Option<PerspectiveCamera>, redis_client: &mut redis::Client, props: &Arc<Mutex<PropRepository>>, render_id: ObjectId, textures: &Arc<Mutex<TextureRepository>>) -> Vec<f32>{
     //Render Albedo and Normal
-    let scene: Vec<u8> =
+    let data: Vec<u8> =
     redis::Cmd::get(format!("archyrt:{}:scene", scene)).query(redis_client).unwrap();
-    let scene = ASCNLoader::from_bytes(&scene).unwrap();
-    let bvh = BVH::from_triangles(scene.get_triangles());
-    let props = props.fulfill_all(scene.get_prop_requests()).unwrap();
-    let camera = scene.get_camera();
-    let object = bvh.union(props);
+    let scene = ASCNLoader::from_bytes(&data).unwrap();
+    let mut textures = textures.lock().unwrap();
+    let mut props = props.lock().unwrap();
+    textures.require(scene.get_textures()).unwrap();
+    props.require(scene.get_prop_requests(), &mut textures).unwrap();
+    props.evict();
+    textures.evict();
+    let mut bvh = load_bvh(redis_client, &data, &scene);
+    if let Some(bvh) = &mut bvh {
+        bvh.load_materials(&textures);
+    }
+    let bvh = bvh.map(|bvh| Arc::new(PackedBVH::new(&bvh)));
+    let camera = camera.unwrap_or_else(|| scene.get_camera().clone());
+    let object = props.build_scene(bvh, scene.get_prop_requests()).unwrap();
     let albedo = AlbedoRenderer {
         object: &object,
         camera: JitterCamera::new(&camera, width, height),
@@ -65,10 +166,186 @@ fn denoise(width: usize, height: usize, image: Vec<f32>, scene: &str, redis_clie
     output
 }
 #[cfg(not(feature="oidn"))]
-fn denoise(width: usize, height: usize, image: Vec<f32>, scene: &str, redis_client: &mut redis::Client, props: &Arc<PropRepository>, render_id: ObjectId, textures: &Arc<TextureRepository>) -> Vec<f32>{
+fn denoise(width: usize, height: usize, image: Vec<f32>, scene: &str, camera: Option<PerspectiveCamera>, redis_client: &mut redis::Client, props: &Arc<Mutex<PropRepository>>, render_id: ObjectId, textures: &Arc<Mutex<TextureRepository>>) -> Vec<f32>{
+    image
+}
+
+#[cfg(feature="oidn")]
+fn denoise_lightmap(width: usize, height: usize, image: Vec<f32>) -> Vec<f32> {
+    let mut output = vec![0f32; image.len()];
+    let device = oidn::Device::new();
+    oidn::RayTracing::new(&device)
+        .srgb(false)
+        .hdr(true)
+        .image_dimensions(width, height)
+        .filter(&image, &mut output).unwrap();
+    output
+}
+#[cfg(not(feature="oidn"))]
+fn denoise_lightmap(_width: usize, _height: usize, image: Vec<f32>) -> Vec<f32> {
     image
 }
 
+/// Textures and props of the assets directory
+fn load_repositories() -> Result<(TextureRepository, PropRepository)> {
+    let mut textures = TextureRepository::new();
+    amdl_textures::load_into(&mut textures, "../assets")?;
+    let mut props = PropRepository::new();
+    amdl::repo::load_into(&mut props, "../assets")?;
+    props.set_cache(env::var("BVH_CACHE_DIR").ok().map(BVHCache::new));
+    Ok((textures, props))
+}
+
+/// Bakes the lightmap of the solids of a scene for the editor, saving it next to the project file
+fn handle_bake(
+    mut redis_client: redis::Client,
+    task: &str,
+    path: &str,
+    textures: &Mutex<TextureRepository>,
+    props: &Mutex<PropRepository>,
+) -> Result<()> {
+    println!("[{}] Baking lightmap", task);
+    let samples: usize =
+        redis::Cmd::get(format!("archyrt:{}:samples", task)).query(&mut redis_client)?;
+    let atmosphere: Option<String> =
+        redis::Cmd::get(format!("archyrt:{}:atmosphere", task)).query(&mut redis_client)?;
+    let atmosphere = match atmosphere {
+        Some(json) => Atmosphere::from_json(&json)?,
+        None => Atmosphere::default(),
+    };
+    let data: Vec<u8> = redis::Cmd::get(format!("archyrt:{}:scene", task)).query(&mut redis_client)?;
+    let scene = ASCNLoader::from_bytes(&data)?;
+    let atlas = LightmapAtlas::from_bytes(&data, lightmap::DENSITY, lightmap::MAX_SIZE)?;
+    let image = {
+        let mut textures = textures.lock().unwrap();
+        let mut props = props.lock().unwrap();
+        textures.require(scene.get_textures())?;
+        props.require(scene.get_prop_requests(), &mut textures)?;
+        props.evict();
+        textures.evict();
+        let mut bvh = load_bvh(&mut redis_client, &data, &scene);
+        if let Some(bvh) = &mut bvh {
+            bvh.load_materials(&textures);
+        }
+        let scene_emitters = bvh.as_ref().map_or(Vec::new(), |bvh| emitters(bvh.triangles()));
+        let prop_emitters = props.emitters(scene.get_prop_requests())?;
+        let bvh = bvh.map(|bvh| Arc::new(PackedBVH::new(&bvh)));
+        let world = Scene {
+            object: props.build_scene(bvh, scene.get_prop_requests())?,
+            skybox: Some(TextureID::new(&"skybox")),
+            portals: scene.get_portals().clone(),
+            lights: AreaLights::new(scene_emitters.iter().chain(prop_emitters.iter())),
+            anisotropy: 8,
+            media: atmosphere.media(scene.get_volumes()),
+            sun: atmosphere.sun,
+        };
+        println!("[{}] Baking {}x{} texels", task, atlas.width, atlas.height);
+        atlas.bake(&world, &PathTracer::default(), &textures, samples)
+    };
+    println!("[{}] Denoising lightmap", task);
+    let (width, height) = (atlas.width as usize, atlas.height as usize);
+    let output = denoise_lightmap(width, height, image.to_raw());
+    //The denoiser blends the padding with neighbouring faces
+    let mut image = FloatImage::from_raw(width, height, &output);
+    atlas.dilate(&mut image);
+    let lightmap = atlas.encode(&image).encode().ok_or_else(|| anyhow!("Could not encode lightmap"))?;
+    //Written atomically, so the backend never sends a partial lightmap
+    write_atomic(Path::new(&env::var("IMAGES").unwrap()).join(path).with_extension("almp"), &lightmap)?;
+    clear_bake(&mut redis_client, task)?;
+    println!("[{}] Lightmap saved", task);
+    Ok(())
+}
+
+/// Removes the settings of a bake from Redis
+fn clear_bake(redis_client: &mut redis::Client, task: &str) -> redis::RedisResult<()> {
+    redis::pipe()
+        .del(format!("archyrt:{}:samples", task))
+        .del(format!("archyrt:{}:atmosphere", task))
+        .del(format!("archyrt:{}:scene", task))
+        .query(redis_client)
+}
+
+/// Removes the settings of a failed bake, leaving a marker for the editor waiting on its lightmap
+fn fail_bake(redis_client: &mut redis::Client, task: &str, path: &str, err: &anyhow::Error) -> redis::RedisResult<()> {
+    clear_bake(redis_client, task)?;
+    redis::cmd("SET")
+        .arg(format!("archyrt:lightmap:{}:error", path))
+        .arg(err.to_string())
+        .arg("EX")
+        .arg(BAKE_ERROR_TTL)
+        .query(redis_client)
+}
+
+/// Settings of a render read from Redis, the effects and color space of its image and the ones its samples depend on
+fn load_settings(redis_client: &mut redis::Client, task: &str, width: usize, height: usize) -> Result<(PostProcess, OutputTransform, RenderSettings)> {
+    //Effects applied to the denoised float buffer before tonemapping, checked before any work is queued
+    let postprocess: Option<String> =
+        redis::Cmd::get(format!("archyrt:{}:postprocess", task)).query(redis_client)?;
+    let postprocess = match postprocess {
+        Some(json) => PostProcess::from_json(&json)?,
+        None => PostProcess::default(),
+    };
+    //Color space the image is encoded for
+    let display: Option<String> =
+        redis::Cmd::get(format!("archyrt:{}:display", task)).query(redis_client)?;
+    let display = display.unwrap_or_else(|| "srgb".to_string());
+    let display = ColorSpace::from_name(&display)
+        .filter(|space| ColorSpace::DISPLAYS.contains(space))
+        .ok_or_else(|| anyhow!("Unknown display color space: {}", display))?;
+    let output_transform = OutputTransform::new(display);
+    //Settings the samples depend on, a render with the same settings and scene resumes the checkpoint of this one
+    let integrator: Option<String> =
+        redis::Cmd::get(format!("archyrt:{}:integrator", task)).query(redis_client)?;
+    let clay: Option<bool> = redis::Cmd::get(format!("archyrt:{}:clay", task)).query(redis_client)?;
+    let atmosphere: Option<String> =
+        redis::Cmd::get(format!("archyrt:{}:atmosphere", task)).query(redis_client)?;
+    let keyframe: Option<String> =
+        redis::Cmd::get(format!("archyrt:{}:camera", task)).query(redis_client)?;
+    let settings = RenderSettings {
+        width,
+        height,
+        integrator: integrator.unwrap_or_else(|| "path".to_string()),
+        clay: clay.unwrap_or(false),
+        atmosphere: match atmosphere {
+            Some(json) => Atmosphere::from_json(&json)?,
+            None => Atmosphere::default(),
+        },
+        camera: keyframe.map(|json| keyframe_from_json(&json)).transpose()?,
+    };
+    Ok((postprocess, output_transform, settings))
+}
+
+/// Marks a render which can't be finished as failed, removing its settings from Redis
+async fn fail_render(
+    users: &Collection<Document>,
+    redis_client: &mut redis::Client,
+    task: &str,
+    user: ObjectId,
+    project_id: ObjectId,
+    render_id: ObjectId,
+    err: &anyhow::Error,
+) -> Result<()> {
+    users
+        .update_many(
+            doc! {"_id": user},
+            doc! {"$set":{"projects.$[project].renders.$[render].error": err.to_string()}},
+            UpdateOptions::builder()
+                .array_filters(vec![
+                    doc! {"render._id": render_id},
+                    doc! {"project._id": project_id},
+                ])
+                .build(),
+        )
+        .await?;
+    let keys: Vec<String> = RENDER_KEYS
+        .iter()
+        .chain(&["camera"])
+        .map(|key| format!("archyrt:{}:{}", task, key))
+        .collect();
+    let _: () = redis::Cmd::del(keys).query(redis_client)?;
+    Ok(())
+}
+
 async fn handle_job(
     users: Collection<Document>,
     mut redis_client: redis::Client,
@@ -76,8 +353,8 @@ async fn handle_job(
     delivery: Delivery,
     response_queue: Queue,
     task_queue: Queue,
-    textures: Arc<TextureRepository>,
-    props: Arc<PropRepository>,
+    textures: Arc<Mutex<TextureRepository>>,
+    props: Arc<Mutex<PropRepository>>,
 ) -> Result<()> {
     let response_queue = response_queue.name().as_str();
     let task_queue = task_queue.name().as_str();
@@ -95,22 +372,60 @@ async fn handle_job(
     let width: usize = redis::Cmd::get(format!("archyrt:{}:width", s)).query(&mut redis_client).unwrap();
     let height: usize =
         redis::Cmd::get(format!("archyrt:{}:height", s)).query(&mut redis_client).unwrap();
-    //Create image storage on Redis
+    let (postprocess, output_transform, settings) = match load_settings(&mut redis_client, &s, width, height) {
+        Ok(settings) => settings,
+        Err(err) => {
+            //Settings that can't be read never will be, so the job is dropped instead of being redelivered
+            println!("[{}] Error: {}", render_id, err);
+            channel
+                .basic_ack(delivery.delivery_tag, Default::default())
+                .await?;
+            channel
+                .queue_delete(response_queue, Default::default())
+                .await?;
+            fail_render(&users, &mut redis_client, &s, user, project_id, render_id, &err).await?;
+            return Ok(());
+        }
+    };
+    //Frames of camera paths are seen from their keyframe instead of the scene's camera
+    let camera = settings.camera.as_ref().map(keyframe_camera);
+    let scene_data: Vec<u8> =
+        redis::Cmd::get(format!("archyrt:{}:scene", s)).query(&mut redis_client).unwrap();
+    let scene_key = content_key(&scene_data);
+    //Fog and sun are rejected by the backend for integrators that ignore them, fog solids only show up in the scene
+    if NamedIntegrator::from_name(&settings.integrator).map_or(false, |integrator| !integrator.renders_atmosphere()) {
+        let volumes = ASCNLoader::from_bytes(&scene_data).map_or(false, |scene| !scene.get_volumes().is_empty());
+        if volumes || settings.atmosphere.fog.is_some() || settings.atmosphere.sun.is_some() {
+            println!("[{}] The {} integrator renders without the fog and sunlight of the scene", render_id, settings.integrator);
+        }
+    }
+    //Checkpoints are kept on disk if a directory is set, otherwise on Redis
+    let checkpoints = env::var("CHECKPOINT_DIR").ok().map(CheckpointDirectory::new);
+    let checkpoint_key = Checkpoint::key(&scene_key, &settings);
+    let checkpoint = load_checkpoint(&mut redis_client, checkpoints.as_ref(), &checkpoint_key)
+        .filter(|checkpoint| checkpoint.resumes(&scene_key, &settings));
+    if checkpoint.is_some() {
+        println!("[{}] Resuming from checkpoint", render_id);
+    }
+    let accumulator = checkpoint.map_or_else(|| Accumulator::new(width, height), |checkpoint| checkpoint.accumulator);
+    //Create image storage on Redis, holding the sums of the samples and of their squares
     let image_key = format!("archyrt:{}:image", s);
-    let _: () = redis::cmd("AI.TENSORSET")
-        .arg(&image_key)
-        .arg("FLOAT")
-        .arg(height)
-        .arg(width)
-        .arg(3)
-        .query(&mut redis_client).unwrap();
+    let squared_key = format!("archyrt:{}:squared", s);
+    set_tensor(&mut redis_client, &image_key, width, height, &accumulator.sum.to_raw());
+    set_tensor(&mut redis_client, &squared_key, width, height, &accumulator.sum_squared.to_raw());
     let payload = s.clone();
-    //Put as many tasks on the queue as there are samples*16
+    //Put the samples missing from each of the 16 tiles on the queue, counting the ones received for each tile
+    let mut tile_samples: HashMap<(usize, usize), u32> = HashMap::new();
+    let mut missing = 0;
     for x in 0..4 {
         for y in 0..4{
             let x = width/4*x;
             let y = height/4*y;
-            futures::stream::iter(0..samples)
+            let done = accumulator.region_samples(x, y, width/4, height/4);
+            tile_samples.insert((x, y), 0);
+            let count = (samples as u32).saturating_sub(done);
+            missing += count;
+            futures::stream::iter(0..count)
                 .for_each(|_| async {
                     let id = Uuid::new_v4();
                     let payload = format!("{}#{}#{}#{}#{}", payload, id, response_queue, x, y);
@@ -131,6 +446,8 @@ async fn handle_job(
                 .await;
         }
     }
+    //Pixels of a checkpoint may have more samples than the fewest of their tile
+    let resumed = accumulator.samples;
     channel
         .basic_ack(delivery.delivery_tag, Default::default())
         .await.unwrap();
@@ -147,88 +464,120 @@ async fn handle_job(
         )
         .await.unwrap();
     let mut counter = 0;
+    let mut saved = Instant::now();
+    let mut failed = None;
     println!("[{}] Waiting for workers to finish", render_id);
-    while let Some(delivery) = consumer.next().await {
-        let (_, _delivery) = delivery.unwrap();
+    //A fully resumed render has no samples to wait for
+    if missing > 0 {
+        while let Some(delivery) = consumer.next().await {
+            let (_, _delivery) = delivery.unwrap();
 
 
-        let msg = String::from_utf8(_delivery.data).unwrap();
-        let msg: Vec<&str> = msg.split("#").collect();
-        let temp = msg[0];
-        let x: usize = msg[1].parse().unwrap();
-        let y: usize = msg[2].parse().unwrap();
-        //Add image to accumulator
-        let _: () = redis::cmd("AI.SCRIPTEXECUTE")
-            .arg("archyrt:scripts")
-            .arg("add")
-            .arg("INPUTS")
-            .arg(2)
-            .arg(&temp)
-            .arg(&image_key)
-            .arg("ARGS")
-            .arg(2)
-            .arg(x)
-            .arg(y)
-            .arg("OUTPUTS")
-            .arg(1)
-            .arg(&image_key)
-            .execute(&mut redis_client);
-        //Remove temporary storage
-        let _: () = redis::Cmd::del(temp).query(&mut redis_client).unwrap();
-        counter += 1;
-        if counter >= samples*16 {
-            break;
-        }
+            let msg = String::from_utf8(_delivery.data).unwrap();
+            //Workers report tiles they can't render, which would never arrive
+            if let Some(error) = msg.strip_prefix("error#") {
+                failed = Some(anyhow!("A worker could not render a tile: {}", error));
+                break;
+            }
+            let msg: Vec<&str> = msg.split("#").collect();
+            let temp = msg[0];
+            let x: usize = msg[1].parse().unwrap();
+            let y: usize = msg[2].parse().unwrap();
+            //Add image to accumulator
+            let _: () = redis::cmd("AI.SCRIPTEXECUTE")
+                .arg("archyrt:scripts")
+                .arg("add")
+                .arg("INPUTS")
+                .arg(2)
+                .arg(&temp)
+                .arg(&image_key)
+                .arg("ARGS")
+                .arg(2)
+                .arg(x)
+                .arg(y)
+                .arg("OUTPUTS")
+                .arg(1)
+                .arg(&image_key)
+                .execute(&mut redis_client);
+            let _: () = redis::cmd("AI.SCRIPTEXECUTE")
+                .arg("archyrt:scripts")
+                .arg("add_squared")
+                .arg("INPUTS")
+                .arg(2)
+                .arg(&temp)
+                .arg(&squared_key)
+                .arg("ARGS")
+                .arg(2)
+                .arg(x)
+                .arg(y)
+                .arg("OUTPUTS")
+                .arg(1)
+                .arg(&squared_key)
+                .execute(&mut redis_client);
+            //Remove temporary storage
+            let _: () = redis::Cmd::del(temp).query(&mut redis_client).unwrap();
+            *tile_samples.get_mut(&(x, y)).unwrap() += 1;
+            counter += 1;
+            if counter >= missing {
+                break;
+            }
+            if saved.elapsed() >= CHECKPOINT_INTERVAL {
+                let accumulator = load_accumulator(&mut redis_client, &image_key, &squared_key, &settings, &resumed, &tile_samples);
+                save_checkpoint(&mut redis_client, checkpoints.as_ref(), &checkpoint_key, &Checkpoint {
+                    scene: scene_key.clone(),
+                    settings: settings.clone(),
+                    accumulator,
+                });
+                saved = Instant::now();
+            }
 
-        //Update percentage
-        let percentage = (counter as f32) / (samples as f32) / 16.0;
-        users
-            .update_many(
-                doc! {"_id": user},
-                doc! {"$set":{"projects.$[project].renders.$[render].status": percentage}},
-                UpdateOptions::builder()
-                    .array_filters(vec![
-                        doc! {"render._id": render_id},
-                        doc! {"project._id": project_id},
-                    ])
-                    .build(),
-            )
-            .await.unwrap();
+            //Update percentage
+            let total = samples as u32 * 16;
+            let percentage = (total - missing + counter) as f32 / total as f32;
+            users
+                .update_many(
+                    doc! {"_id": user},
+                    doc! {"$set":{"projects.$[project].renders.$[render].status": percentage}},
+                    UpdateOptions::builder()
+                        .array_filters(vec![
+                            doc! {"render._id": render_id},
+                            doc! {"project._id": project_id},
+                        ])
+                        .build(),
+                )
+                .await.unwrap();
+        }
     }
     channel
         .queue_delete(response_queue, Default::default())
         .await.unwrap();
+    if let Some(err) = failed {
+        println!("[{}] Error: {}", render_id, err);
+        fail_render(&users, &mut redis_client, &s, user, project_id, render_id, &err).await?;
+        let _: () = redis::Cmd::del(&image_key).query(&mut redis_client)?;
+        let _: () = redis::Cmd::del(&squared_key).query(&mut redis_client)?;
+        return Ok(());
+    }
     println!("[{}] Retrieving data", render_id);
-    let _: () = redis::cmd("AI.SCRIPTEXECUTE")
-        .arg("archyrt:scripts")
-        .arg("divide")
-        .arg("INPUTS")
-        .arg(1)
-        .arg(&image_key)
-        .arg("ARGS")
-        .arg(1)
-        .arg(samples)
-        .arg("OUTPUTS")
-        .arg(1)
-        .arg(&image_key)
-        .query(&mut redis_client).unwrap();
-    let image: Vec<u8> = redis::cmd("AI.TENSORGET")
-        .arg(&image_key)
-        .arg("BLOB")
-        .query(&mut redis_client).unwrap();
-    let image: Vec<f32> = image
-        .chunks(4)
-        .map(|a| {
-            let a: [u8; 4] = a.try_into().unwrap();
-            f32::from_le_bytes(a)
-        })
-        .collect();
+    let accumulator = load_accumulator(&mut redis_client, &image_key, &squared_key, &settings, &resumed, &tile_samples);
+    let image = accumulator.mean().to_raw();
+    //Later renders with more samples continue from the finished one
+    save_checkpoint(&mut redis_client, checkpoints.as_ref(), &checkpoint_key, &Checkpoint {
+        scene: scene_key,
+        settings,
+        accumulator,
+    });
 
     let width: usize = redis::Cmd::get(format!("archyrt:{}:width", s)).query(&mut redis_client).unwrap();
     let height: usize =
         redis::Cmd::get(format!("archyrt:{}:height", s)).query(&mut redis_client).unwrap();
 
-    let output: Vec<f32> = denoise(width, height, image, &s, &mut redis_client, &props, render_id, &textures);
+    let output: Vec<f32> = denoise(width, height, image, &s, camera, &mut redis_client, &props, render_id, &textures);
+
+    println!("[{}] Post-processing", render_id);
+    let mut output = FloatImage::from_raw(width, height, &output);
+    postprocess.apply(&mut output);
+    let output = output.to_raw();
 
     println!("[{}] Saving", render_id);
     let mut image = image::RgbImage::new(width as u32, height as u32);
@@ -240,7 +589,7 @@ async fn handle_job(
         let index = y as usize * width + x as usize;
         let c = output[index];
         
-        let c = tonemap_fragment(c);
+        let c = output_transform.apply(c);
 
         let r = c.x()*255.0;
         let g = c.y()*255.0;
@@ -259,10 +608,77 @@ async fn handle_job(
     image.save(path).unwrap();
     users.update_many(doc! {"_id": user}, doc!{"$set":{"projects.$[project].renders.$[render].finished": DateTime::now(), "projects.$[project].renders.$[render].status": 1.0, "projects.$[project].renders.$[render].icon": render_id.to_hex()}}, UpdateOptions::builder().array_filters(vec![doc!{"render._id": render_id}, doc!{"project._id": project_id}]).build()).await.unwrap();
     let _: () = redis::Cmd::del(&image_key).query(&mut redis_client).unwrap();
+    let _: () = redis::Cmd::del(&squared_key).query(&mut redis_client).unwrap();
     println!("[{}] Done!", render_id);
     Ok(())
 }
 
+/// Queues a render of every frame of a camera path, which show up as renders of the project
+async fn handle_animation(
+    users: Collection<Document>,
+    mut redis_client: redis::Client,
+    channel: Channel,
+    delivery: Delivery,
+    dispatch_queue: Queue,
+) -> Result<()> {
+    let s = String::from_utf8(delivery.data)?;
+    let s: Vec<&str> = s.split('#').collect();
+    let task = s[0];
+    let user = ObjectId::parse_str(s[1])?;
+    let project_id = ObjectId::parse_str(s[2])?;
+    let name: String = redis::Cmd::get(format!("archyrt:{}:name", task)).query(&mut redis_client)?;
+    let fps: f64 = redis::Cmd::get(format!("archyrt:{}:fps", task)).query(&mut redis_client)?;
+    let path: Vec<u8> = redis::Cmd::get(format!("archyrt:{}:path", task)).query(&mut redis_client)?;
+    let path = CameraPath::from_bytes(&path)?;
+    println!("[{}] Queueing {} frames", task, path.frame_count(fps));
+    for (frame, keyframe) in path.frame_keyframes(fps).enumerate() {
+        //Frames are added to the project like the renders created by the backend
+        let render_id = ObjectId::new();
+        let render = doc! {
+            "_id": render_id,
+            "name": format!("{} {:04}", name, frame),
+            "status": 0.0,
+            "started": DateTime::now(),
+            "finished": Bson::Null,
+            "icon": "",
+        };
+        users
+            .update_one(
+                doc! {"_id": user},
+                doc! {"$push": {"projects.$[project].renders": render}},
+                UpdateOptions::builder()
+                    .array_filters(vec![doc! {"project._id": project_id}])
+                    .build(),
+            )
+            .await?;
+        let frame_task = render_id.to_hex();
+        for key in RENDER_KEYS {
+            let value: Option<Vec<u8>> = redis::Cmd::get(format!("archyrt:{}:{}", task, key)).query(&mut redis_client)?;
+            if let Some(value) = value {
+                let _: () = redis::Cmd::set(format!("archyrt:{}:{}", frame_task, key), value).query(&mut redis_client)?;
+            }
+        }
+        let _: () = redis::Cmd::set(format!("archyrt:{}:camera", frame_task), keyframe_to_json(&keyframe)).query(&mut redis_client)?;
+        channel
+            .basic_publish(
+                "",
+                dispatch_queue.name().as_str(),
+                Default::default(),
+                format!("{}#{}#{}", frame_task, user.to_hex(), project_id.to_hex()).into_bytes(),
+                Default::default(),
+            )
+            .await?;
+    }
+    let keys: Vec<String> = RENDER_KEYS
+        .iter()
+        .chain(&["name", "fps", "path"])
+        .map(|key| format!("archyrt:{}:{}", task, key))
+        .collect();
+    let _: () = redis::Cmd::del(keys).query(&mut redis_client)?;
+    println!("[{}] Frames queued", task);
+    Ok(())
+}
+
 static TORCHSCRIPT: &str = "
 def add(tensors: List[Tensor], keys: List[str], args: List[str]):
     x = int(args[0])
@@ -272,8 +688,14 @@ def add(tensors: List[Tensor], keys: List[str], args: List[str]):
     t = torch.clone(tensors[1])
     t[y:y+h,x:x+w,:] += tensors[0]
     return t
-def divide(tensors: List[Tensor], keys: List[str], args: List[str]):
-    return tensors[0]/int(args[0])
+def add_squared(tensors: List[Tensor], keys: List[str], args: List[str]):
+    x = int(args[0])
+    y = int(args[1])
+    w = tensors[0].shape[1]
+    h = tensors[0].shape[0]
+    t = torch.clone(tensors[1])
+    t[y:y+h,x:x+w,:] += tensors[0]*tensors[0]
+    return t
 ";
 
 fn main() -> Result<()> {
@@ -301,7 +723,7 @@ fn main() -> Result<()> {
             .arg("ENTRY_POINTS")
             .arg(2)
             .arg("add")
-            .arg("divide")
+            .arg("add_squared")
             .arg("SOURCE")
             .arg(TORCHSCRIPT)
             .query(&mut redis_client).unwrap();
@@ -314,6 +736,12 @@ fn main() -> Result<()> {
         let task_queue = channel
             .queue_declare("archyrt:taskqueue", Default::default(), Default::default())
             .await.unwrap();
+        let bake_queue = channel
+            .queue_declare("archyrt:bake", Default::default(), Default::default())
+            .await.unwrap();
+        let animation_queue = channel
+            .queue_declare("archyrt:animation", Default::default(), Default::default())
+            .await.unwrap();
 
         let mut consumer = channel
             .basic_consume(
@@ -323,13 +751,92 @@ fn main() -> Result<()> {
                 FieldTable::default(),
             )
             .await.unwrap();
-        let mut textures = TextureRepository::new();
-        amdl_textures::load_into(&mut textures, "../assets").unwrap();
-        let textures = Arc::new(textures);
+        let (textures, props) = load_repositories().unwrap();
+        let textures = Arc::new(Mutex::new(textures));
+        let props = Arc::new(Mutex::new(props));
+
+        //Lightmaps are baked one at a time, away from the threads handling the renders
+        let mut bake_consumer = channel
+            .basic_consume(
+                bake_queue.name().as_str(),
+                "archyrt:baker",
+                BasicConsumeOptions::default(),
+                FieldTable::default(),
+            )
+            .await.unwrap();
+        //The baker has repositories of its own, so renders never wait for a bake to release them
+        let (mut bake_textures, bake_props) = load_repositories().unwrap();
+        //Lightmaps are lit by the sky
+        texture_repo::exr::load_into(
+            &mut bake_textures,
+            "../assets",
+            &[(TextureID::new(&"skybox"), "skybox.exr")],
+        ).unwrap();
+        async_global_executor::spawn({
+            let redis_client = redis_client.clone();
+            let channel = channel.clone();
+            let textures = Arc::new(Mutex::new(bake_textures));
+            let props = Arc::new(Mutex::new(bake_props));
+            async move {
+                while let Some(delivery) = bake_consumer.next().await {
+                    let (_, delivery) = delivery.unwrap();
+                    channel
+                        .basic_ack(delivery.delivery_tag, Default::default())
+                        .await.unwrap();
+                    let s = String::from_utf8(delivery.data).unwrap();
+                    let mut redis_client = redis_client.clone();
+                    let textures = textures.clone();
+                    let props = props.clone();
+                    async_global_executor::spawn_blocking(move || {
+                        let s: Vec<&str> = s.split('#').collect();
+                        if let Err(err) = handle_bake(redis_client.clone(), s[0], s[1], &textures, &props) {
+                            println!("[{}] Error: {}", s[0], err);
+                            if let Err(err) = fail_bake(&mut redis_client, s[0], s[1], &err) {
+                                println!("[{}] Could not report failed bake: {}", s[0], err);
+                            }
+                        }
+                    })
+                    .await;
+                }
+            }
+        })
+        .detach();
 
-        let mut props = PropRepository::new();
-        amdl::repo::load_into(&mut props, &textures, "../assets").unwrap();
-        let props = Arc::new(props);
+        //Camera paths are split into a render for each frame
+        let mut animation_consumer = channel
+            .basic_consume(
+                animation_queue.name().as_str(),
+                "archyrt:animator",
+                BasicConsumeOptions::default(),
+                FieldTable::default(),
+            )
+            .await.unwrap();
+        async_global_executor::spawn({
+            let users = users.clone();
+            let redis_client = redis_client.clone();
+            let channel = channel.clone();
+            let queue = queue.clone();
+            async move {
+                while let Some(delivery) = animation_consumer.next().await {
+                    let (_, delivery) = delivery.unwrap();
+                    channel
+                        .basic_ack(delivery.delivery_tag, Default::default())
+                        .await.unwrap();
+                    let result = handle_animation(
+                        users.clone(),
+                        redis_client.clone(),
+                        channel.clone(),
+                        delivery,
+                        queue.clone(),
+                    )
+                    .await;
+                    if let Err(err) = result {
+                        println!("Error: {}", err);
+                    }
+                }
+            }
+        })
+        .detach();
 
         while let Some(delivery) = consumer.next().await {
             let (_, delivery) = delivery.unwrap();
@@ -343,7 +850,7 @@ fn main() -> Result<()> {
                     Default::default(),
                 )
                 .await.unwrap();
-            async_global_executor::spawn(handle_job(
+            let job = handle_job(
                 users.clone(),
                 redis_client.clone(),
                 channel.clone(),
@@ -352,7 +859,12 @@ fn main() -> Result<()> {
                 task_queue.clone(),
                 textures.clone(),
                 props.clone()
-            ))
+            );
+            async_global_executor::spawn(async move {
+                if let Err(err) = job.await {
+                    println!("Error: {}", err);
+                }
+            })
             .detach();
         }
 
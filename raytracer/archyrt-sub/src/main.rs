@@ -1,22 +1,32 @@
 mod shifted_view;
 
-use std::env;
+use std::{collections::HashSet, env, sync::{Arc, Mutex}};
 
 use anyhow::{anyhow, Result};
 use archyrt_core::{
-    api::fragment_collector::FragmentCollector,
-    cameras::{perspective::PerspectiveCamera, jitter::JitterCamera},
+    api::{fragment_collector::FragmentCollector, fragment_render::FragmentRender},
+    cameras::{perspective::PerspectiveCamera, jitter::JitterCamera, path::{keyframe_camera, keyframe_from_json}},
     collector::array_collector::ArrayCollector,
-    intersectables::bvh::BVH,
+    intersectables::{
+        area_light::{emitters, AreaLights},
+        bvh::BVH,
+        packed_bvh::PackedBVH,
+        portal::Portal,
+        triangle::Triangle,
+    },
     loaders::{
         ascn::{amdl_textures, ASCNLoader},
+        bvh_cache::{content_key, BVHCache},
         Loader, amdl::{repo::{PropRequest, PropRepository}, self},
     },
-    renderers::{path_tracer::PathTracer},
+    renderers::{
+        integrator::{IntegratorRenderer, NamedIntegrator, Scene},
+        media::{Atmosphere, Convex},
+    },
     textures::{
         texture_repo::{self, TextureRepository},
         TextureID,
-    }, utilities::ray::Intersectable,
+    },
 };
 use dotenv::dotenv;
 use futures::{StreamExt, future::JoinAll, Future};
@@ -26,11 +36,57 @@ use redis::AsyncCommands;
 
 use crate::shifted_view::ShiftedView;
 
-struct SceneData(Option<BVH>, JitterCamera<PerspectiveCamera>, Vec<PropRequest>);
+/// Parts of a scene kept between the tiles of a render
+struct SceneData {
+    bvh: Option<Arc<PackedBVH>>,
+    camera: JitterCamera<PerspectiveCamera>,
+    prop_requests: Vec<PropRequest>,
+    portals: Vec<Portal>,
+    textures: HashSet<TextureID>,
+    /// Emissive triangles of the solids, the ones of props are placed for every render
+    emitters: Vec<Triangle>,
+    volumes: Vec<Convex>,
+}
+
+/// How long built scene BVHs are kept in Redis, in seconds
+const BVH_EXPIRY: usize = 24 * 60 * 60;
+
+/// Loads the scene's BVH from the local cache or Redis, building and caching it if neither has it
+fn load_bvh(
+    data: &[u8],
+    scene: &ASCNLoader,
+    bvh_cache: Option<&BVHCache>,
+    redis_client: &mut redis::Client,
+) -> Result<Option<BVH>> {
+    let key = content_key(data);
+    if let Some(bvh) = bvh_cache.and_then(|cache| cache.load(&key)) {
+        return Ok(Some(bvh));
+    }
+    let redis_key = format!("archyrt:bvh:{}", key);
+    let cached: Option<Vec<u8>> = redis::Cmd::get(&redis_key).query(redis_client)?;
+    if let Some(encoded) = cached {
+        if let Ok(bvh) = BVH::decode(&encoded) {
+            if let Some(cache) = bvh_cache {
+                cache.store(&key, &encoded).ok();
+            }
+            return Ok(Some(bvh));
+        }
+    }
+    let bvh = BVH::from_triangles(scene.get_triangles());
+    if let Some(bvh) = &bvh {
+        let encoded = bvh.encode()?;
+        let _: () = redis::Cmd::set_ex(&redis_key, &encoded, BVH_EXPIRY).query(redis_client)?;
+        if let Some(cache) = bvh_cache {
+            cache.store(&key, &encoded).ok();
+        }
+    }
+    Ok(bvh)
+}
 
 async fn render(
-    texture_repo: &TextureRepository,
-    prop_repo: &PropRepository,
+    texture_repo: &Mutex<TextureRepository>,
+    prop_repo: &Mutex<PropRepository>,
+    bvh_cache: Option<&BVHCache>,
     cache: &mut LruCache<String, SceneData>,
     redis_client: &mut redis::Client,
     channel: &Channel,
@@ -46,49 +102,93 @@ async fn render(
     let y: usize = s[4].parse()?;
     let width: usize = redis::Cmd::get(format!("archyrt:{}:width", task)).query(redis_client)?;
     let height: usize = redis::Cmd::get(format!("archyrt:{}:height", task)).query(redis_client)?;
+    let integrator: Option<String> =
+        redis::Cmd::get(format!("archyrt:{}:integrator", task)).query(redis_client)?;
+    let integrator = integrator.unwrap_or_else(|| "path".to_string());
+    let integrator = NamedIntegrator::from_name(&integrator)
+        .ok_or_else(|| anyhow!("Unknown integrator: {}", integrator))?;
+    let clay: Option<bool> = redis::Cmd::get(format!("archyrt:{}:clay", task)).query(redis_client)?;
+    let clay = clay.unwrap_or(false);
+    let atmosphere: Option<String> =
+        redis::Cmd::get(format!("archyrt:{}:atmosphere", task)).query(redis_client)?;
+    let atmosphere = match atmosphere {
+        Some(json) => Atmosphere::from_json(&json)?,
+        None => Atmosphere::default(),
+    };
+    //Frames of camera paths are seen from their keyframe instead of the scene's camera
+    let keyframe: Option<String> =
+        redis::Cmd::get(format!("archyrt:{}:camera", task)).query(redis_client)?;
     let part_width = width/4;
     let part_height = height/4;
+    let mut texture_repo = texture_repo.lock().unwrap();
+    let mut prop_repo = prop_repo.lock().unwrap();
     let scene = match cache.get(&task) {
         Some(a) => a,
         None => {
-            let scene: Vec<u8> =
+            let data: Vec<u8> =
                 redis::Cmd::get(format!("archyrt:{}:scene", task)).query(redis_client)?;
-            let scene = ASCNLoader::from_bytes(&scene)?;
-            let bvh = BVH::from_triangles(scene.get_triangles());
-            let camera = scene.get_camera().clone();
+            let scene = ASCNLoader::from_bytes(&data)?;
+            let textures = scene.get_textures();
+            texture_repo.require(textures.iter().copied())?;
+            let mut bvh = load_bvh(&data, &scene, bvh_cache, redis_client)?;
+            if let Some(bvh) = &mut bvh {
+                bvh.load_materials(&texture_repo);
+            }
+            let scene_emitters = bvh.as_ref().map_or(Vec::new(), |bvh| emitters(bvh.triangles()));
+            let bvh = bvh.map(|bvh| Arc::new(PackedBVH::new(&bvh)));
+            let camera = match &keyframe {
+                Some(json) => keyframe_camera(&keyframe_from_json(json)?),
+                None => scene.get_camera().clone(),
+            };
             let camera = JitterCamera::new(camera, width, height);
             let prop_requests = scene.get_prop_requests().clone();
-            let data = SceneData(bvh, camera, prop_requests);
+            let portals = scene.get_portals().clone();
+            let volumes = scene.get_volumes().clone();
+            let data = SceneData {
+                bvh,
+                camera,
+                prop_requests,
+                portals,
+                textures,
+                emitters: scene_emitters,
+                volumes,
+            };
             cache.put(task.clone(), data);
             cache.get(&task).unwrap()
         }
     };
-    let props = prop_repo.fulfill_all(&scene.2)?;
-    let object = &scene.0;
-    let object = object.union(props);
-    let renderer = PathTracer {
-        camera: &scene.1,
+    //Load the assets of the scene, unloading ones of earlier scenes over the budget
+    texture_repo.require(scene.textures.iter().copied())?;
+    prop_repo.require(&scene.prop_requests, &mut texture_repo)?;
+    prop_repo.evict();
+    texture_repo.evict();
+    let object = prop_repo.build_scene(scene.bvh.clone(), &scene.prop_requests)?;
+    let prop_emitters = prop_repo.emitters(&scene.prop_requests)?;
+    let lights = AreaLights::new(scene.emitters.iter().chain(prop_emitters.iter()));
+    let world = Scene {
         object,
-        bounces: 5,
         skybox: Some(TextureID::new(&"skybox")),
+        portals: scene.portals.clone(),
+        lights,
+        anisotropy: 8,
+        media: atmosphere.media(&scene.volumes),
+        sun: atmosphere.sun,
     };
-    let renderer = ShiftedView{
-        inner: renderer,
-        full_w: width,
-        full_h: height,
-        x: (x as f64)/(width as f64),
-        y: (y as f64)/(height as f64)
+    let image = if clay {
+        let renderer = IntegratorRenderer {
+            camera: &scene.camera,
+            scene: world.into_clay(),
+            integrator,
+        };
+        render_tile(renderer, &texture_repo, width, height, x, y)
+    } else {
+        let renderer = IntegratorRenderer {
+            camera: &scene.camera,
+            scene: world,
+            integrator,
+        };
+        render_tile(renderer, &texture_repo, width, height, x, y)
     };
-    let image = ArrayCollector {}.collect(renderer, texture_repo, part_width, part_height);
-    //Convert image into bytes
-    let image: Vec<u8> = image
-        .into_iter()
-        .flatten()
-        .map(|vec| vec.inner)
-        .flatten()
-        .map(|b| (b as f32).to_le_bytes())
-        .flatten()
-        .collect();
     let temp = format!("archyrt:temp:{}", id);
     let image_key = format!("archyrt:{}:image", task);
     let channel = channel.clone();
@@ -128,6 +228,51 @@ async fn render(
     Ok(())
 }
 
+/// Acks a task which failed to render, telling its dispatcher so it fails the render instead of waiting for the tile
+async fn report_failure(channel: &Channel, delivery_tag: u64, response: Option<&str>, err: &anyhow::Error) -> Result<()> {
+    if let Some(response) = response {
+        channel
+            .basic_publish(
+                "",
+                response,
+                Default::default(),
+                format!("error#{}", err).into_bytes(),
+                Default::default(),
+            )
+            .await?;
+    }
+    channel
+        .basic_ack(delivery_tag, Default::default())
+        .await?;
+    Ok(())
+}
+
+/// Renders the tile of the image starting at a pixel, as little endian floats
+fn render_tile<T: FragmentRender>(renderer: T, texture_repo: &TextureRepository, width: usize, height: usize, x: usize, y: usize) -> Vec<u8> {
+    let renderer = ShiftedView{
+        inner: renderer,
+        full_w: width,
+        full_h: height,
+        x: (x as f64)/(width as f64),
+        y: (y as f64)/(height as f64)
+    };
+    let image = ArrayCollector {}.collect(renderer, texture_repo, width/4, height/4);
+    //Convert image into bytes
+    image
+        .into_iter()
+        .flatten()
+        .map(|vec| vec.inner)
+        .flatten()
+        .map(|b| (b as f32).to_le_bytes())
+        .flatten()
+        .collect()
+}
+
+/// Memory budget in bytes from an environment variable given in megabytes, unbounded if it is not set
+fn budget(key: &str) -> Option<usize> {
+    env::var(key).ok()?.parse::<usize>().ok().map(|mb| mb * 1024 * 1024)
+}
+
 fn main() -> Result<()> {
     dotenv().ok();
     println!("Hello, world!");
@@ -135,6 +280,7 @@ fn main() -> Result<()> {
     let amqp_addr = env::var("AMQP_ADDR").unwrap();
     let redis_addr = env::var("REDIS_ADDR").unwrap();
     let mut textures = TextureRepository::new();
+    textures.set_budget(budget("TEXTURE_BUDGET_MB"));
     amdl_textures::load_into(&mut textures, "../assets")?;
     texture_repo::exr::load_into(
         &mut textures,
@@ -143,7 +289,22 @@ fn main() -> Result<()> {
     )?;
 
     let mut props = PropRepository::new();
-    amdl::repo::load_into(&mut props, &textures, "../assets")?;
+    props.set_budget(budget("PROP_BUDGET_MB"));
+    amdl::repo::load_into(&mut props, "../assets")?;
+    let bvh_cache = env::var("BVH_CACHE_DIR").ok();
+    props.set_cache(bvh_cache.as_ref().map(BVHCache::new));
+    let bvh_cache = bvh_cache.map(BVHCache::new);
+    let textures = Mutex::new(textures);
+    let props = Mutex::new(props);
+    //The backend only accepts renders with the integrators the workers know
+    let mut redis_client = redis::Client::open(redis_addr.clone())?;
+    let _: () = redis::pipe()
+        .atomic()
+        .del("archyrt:integrators")
+        .ignore()
+        .sadd("archyrt:integrators", &NamedIntegrator::NAMES[..])
+        .ignore()
+        .query(&mut redis_client)?;
     
     let cores = num_cpus::get();
     let f = futures::future::join_all((0..cores).map(|instance| async {
@@ -172,9 +333,14 @@ fn main() -> Result<()> {
             .await?;
         while let Some(delivery) = consumer.next().await {
             let (_, delivery) = delivery.unwrap();
-            let future = render(&textures, &props, &mut cache, &mut redis_client, &channel, delivery);
+            let delivery_tag = delivery.delivery_tag;
+            let response = String::from_utf8_lossy(&delivery.data).split('#').nth(2).map(str::to_string);
+            let future = render(&textures, &props, bvh_cache.as_ref(), &mut cache, &mut redis_client, &channel, delivery);
             if let Err(err) = future.await {
                 println!("Error: {}", err);
+                if let Err(err) = report_failure(&channel, delivery_tag, response.as_deref(), &err).await {
+                    println!("Could not report error: {}", err);
+                }
             }
         }
         let r: Result<(), anyhow::Error> = anyhow::Result::Ok(());